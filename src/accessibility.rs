@@ -0,0 +1,177 @@
+use bevy::prelude::*;
+
+use crate::{
+    cube::{CubeSize, Face},
+    cubie::{Cubie, CubieFace},
+};
+
+/// Whether each sticker shows a symbol for its color, so colors that look alike, like red and
+/// orange or green and blue, can still be told apart.
+#[derive(Debug, Default, PartialEq, Resource)]
+pub struct StickerSymbols(pub bool);
+
+/// Which kind of color vision the cube is shown as, to check what it looks like to someone with
+/// a color vision deficiency.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Resource)]
+pub enum ColorVision {
+    #[default]
+    Normal,
+    /// No red cones, so reds look darker and closer to greens.
+    Protanopia,
+    /// No green cones, so reds and greens look alike.
+    Deuteranopia,
+}
+
+impl ColorVision {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Normal => "normal",
+            Self::Protanopia => "protanopia",
+            Self::Deuteranopia => "deuteranopia",
+        }
+    }
+
+    pub fn next(&self) -> Self {
+        match self {
+            Self::Normal => Self::Protanopia,
+            Self::Protanopia => Self::Deuteranopia,
+            Self::Deuteranopia => Self::Normal,
+        }
+    }
+
+    /// What `color` looks like with this kind of color vision, using the matrices from Machado,
+    /// Oliveira and Fernandes (2009) for a complete deficiency.
+    pub fn simulate(&self, color: Color) -> Color {
+        let matrix = match self {
+            Self::Normal => return color,
+            Self::Protanopia => Mat3::from_cols_array(&[
+                0.152286, 0.114503, -0.003882, //
+                1.052583, 0.786281, -0.048116, //
+                -0.204868, 0.099216, 1.051998,
+            ]),
+            Self::Deuteranopia => Mat3::from_cols_array(&[
+                0.367322, 0.280085, -0.011820, //
+                0.860646, 0.672501, 0.042940, //
+                -0.227968, 0.047413, 0.968881,
+            ]),
+        };
+
+        let linear = color.to_linear();
+        let [r, g, b] = (matrix * Vec3::new(linear.red, linear.green, linear.blue))
+            .clamp(Vec3::ZERO, Vec3::ONE)
+            .to_array();
+        LinearRgba::new(r, g, b, linear.alpha).into()
+    }
+}
+
+/// How far a symbol reaches from the middle of its sticker.
+const SYMBOL_RADIUS: f32 = 0.16;
+
+/// A dark symbol on top of a sticker.
+#[derive(Component)]
+pub struct StickerSymbol;
+
+/// The symbol for each color, shared by every sticker of that color.
+#[derive(Resource)]
+pub struct SymbolMeshes {
+    meshes: [Handle<Mesh>; 6],
+    material: Handle<StandardMaterial>,
+}
+
+impl FromWorld for SymbolMeshes {
+    fn from_world(world: &mut World) -> Self {
+        // a different shape for every color, and the ones often confused look nothing alike
+        let mut meshes = world.resource_mut::<Assets<Mesh>>();
+        let symbols = Face::variants().map(|face| match face {
+            Face::Top => meshes.add(Circle::new(SYMBOL_RADIUS)),
+            Face::Bottom => meshes.add(Capsule2d::new(SYMBOL_RADIUS / 2.0, SYMBOL_RADIUS)),
+            Face::Left => meshes.add(Rectangle::from_length(SYMBOL_RADIUS * 1.5)),
+            Face::Right => meshes.add(Annulus::new(SYMBOL_RADIUS * 0.6, SYMBOL_RADIUS)),
+            Face::Front => meshes.add(RegularPolygon::new(SYMBOL_RADIUS, 3)),
+            Face::Back => meshes.add(Rhombus::new(SYMBOL_RADIUS * 2.0, SYMBOL_RADIUS * 2.0)),
+        });
+        let material = world
+            .resource_mut::<Assets<StandardMaterial>>()
+            .add(Color::srgb_u8(30, 30, 30));
+
+        Self {
+            meshes: symbols,
+            material,
+        }
+    }
+}
+
+/// Give each new sticker on the outside of the cube a symbol for its color, and show or hide
+/// them all when symbols are switched on or off.
+pub fn update_sticker_symbols(
+    mut commands: Commands,
+    sticker_symbols: Res<StickerSymbols>,
+    symbol_meshes: Res<SymbolMeshes>,
+    cube_size: Res<CubeSize>,
+    stickers: Query<(Entity, &Face, &ChildOf), Added<CubieFace>>,
+    cubies: Query<&Cubie>,
+    mut symbols: Query<&mut Visibility, With<StickerSymbol>>,
+) {
+    let visibility = if sticker_symbols.0 {
+        Visibility::Inherited
+    } else {
+        Visibility::Hidden
+    };
+
+    for (sticker, face, parent) in &stickers {
+        let normal = face.normal().as_ivec3();
+        let outside = cubies
+            .get(parent.parent())
+            .is_ok_and(|cubie| cubie.home.dot(normal) == cube_size.outer().dot(normal.abs()));
+        if !outside {
+            continue;
+        }
+
+        // stickers face along their z axis, so symbols just sit a little in front of them
+        commands.entity(sticker).with_child((
+            StickerSymbol,
+            Mesh3d(symbol_meshes.meshes[face.index()].clone()),
+            MeshMaterial3d(symbol_meshes.material.clone()),
+            Transform::from_xyz(0.0, 0.0, 0.002),
+            visibility,
+            Pickable::IGNORE,
+        ));
+    }
+
+    if sticker_symbols.is_changed() {
+        for mut symbol in &mut symbols {
+            symbol.set_if_neq(visibility);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_simulated_color_vision() {
+        let red = Color::srgb(1.0, 0.0, 0.0);
+        let green = Color::srgb(0.0, 1.0, 0.0);
+        // how far apart the hues are, ignoring how bright they are
+        let hue_distance = |vision: ColorVision| {
+            let [red, green] = [red, green].map(|color| {
+                let linear = vision.simulate(color).to_linear();
+                Vec3::new(linear.red, linear.green, linear.blue).normalize()
+            });
+            red.distance(green)
+        };
+
+        assert_eq!(ColorVision::Normal.simulate(red), red);
+        // red and green look like the same hue without red or green cones
+        assert!(hue_distance(ColorVision::Normal) > 1.0);
+        assert!(hue_distance(ColorVision::Protanopia) < 0.1);
+        assert!(hue_distance(ColorVision::Deuteranopia) < 0.1);
+
+        // greys look the same to everyone
+        let grey = ColorVision::Deuteranopia
+            .simulate(Color::srgb(0.5, 0.5, 0.5))
+            .to_srgba();
+        assert!((grey.red - 0.5).abs() < 0.01 && (grey.blue - 0.5).abs() < 0.01);
+    }
+}
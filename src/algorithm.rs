@@ -0,0 +1,600 @@
+use std::{fmt, ops::RangeInclusive, str::FromStr};
+
+use crate::{
+    cube::{CubeSize, Face},
+    rotation::{Direction, Rotation},
+};
+
+/// Stands in for the layer an `M`, `E` or `S` slice turns until `Algorithm::for_size` knows
+/// which layer is the middle one.
+const MIDDLE_LAYER: usize = usize::MAX;
+
+/// A sequence of rotations, usually written in Singmaster notation e.g. `R U R' U'`.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Algorithm(Vec<Rotation>);
+
+impl Algorithm {
+    pub fn new(rotations: Vec<Rotation>) -> Self {
+        Self(rotations)
+    }
+
+    pub fn rotations(&self) -> &[Rotation] {
+        &self.0
+    }
+
+    /// How many moves the algorithm takes in the half turn metric (HTM), where a half turn
+    /// counts as one move however many layers it turns, as it's written in notation.
+    pub fn move_count(&self) -> usize {
+        self.moves().len()
+    }
+
+    /// The algorithm in notation as it turns a cube of the given size, which writes the middle
+    /// layer of a cube with an odd number of layers as a slice. See `notation`.
+    pub fn notation(&self, size: &CubeSize) -> String {
+        let tokens: Vec<_> = self
+            .moves()
+            .into_iter()
+            .map(|(rotation, is_double)| notation(rotation, is_double, size))
+            .collect();
+        tokens.join(" ")
+    }
+
+    /// The rotations as they're written, each along with whether it's doubled, merging
+    /// identical neighbouring quarter turns into double moves.
+    fn moves(&self) -> Vec<(&Rotation, bool)> {
+        let mut moves = Vec::new();
+        let mut i = 0;
+        while i < self.0.len() {
+            let rotation = &self.0[i];
+            let is_double = !rotation.is_half_turn() && self.0.get(i + 1) == Some(rotation);
+            moves.push((rotation, is_double));
+            i += if is_double { 2 } else { 1 };
+        }
+        moves
+    }
+
+    /// The algorithm that undoes this one i.e. every rotation inverted, in reverse order.
+    pub fn inverse(&self) -> Self {
+        Self(self.0.iter().rev().map(Rotation::inverse).collect())
+    }
+
+    /// The algorithm as it turns a cube of the given size. Slices turn the middle layer, which
+    /// only cubes with an odd number of layers have, and layer prefixes can't go past the last
+    /// layer. Faces of cuboids that only make half turns
+    /// can't turn a quarter on their own, so each pair of the same quarter turn, as written by
+    /// `R2`, becomes a single half turn.
+    pub fn for_size(&self, size: &CubeSize) -> Result<Self, NotationError> {
+        let resolved = self
+            .0
+            .iter()
+            .map(|rotation| {
+                let face = rotation.face();
+                if *rotation.layers() != (MIDDLE_LAYER..=MIDDLE_LAYER) {
+                    let last = *rotation.layers().end();
+                    if last >= size.layers(face.normal().as_ivec3()) {
+                        return Err(NotationError::NoSuchLayer(last + 1));
+                    }
+                    return Ok(rotation.clone());
+                }
+                let layer = size
+                    .middle_layer(face.normal().as_ivec3())
+                    .ok_or(NotationError::NoMiddleLayer(slice_letter(face).0))?;
+                Ok(Rotation::new(
+                    face.clone(),
+                    layer..=layer,
+                    rotation.direction().clone(),
+                ))
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let mut rotations = Vec::new();
+        let mut quarters = resolved.iter().peekable();
+        while let Some(rotation) = quarters.next() {
+            if rotation.is_half_turn() || size.allows_quarter_turns(rotation.face()) {
+                rotations.push(rotation.clone());
+            } else if quarters.next_if_eq(&rotation).is_some() {
+                rotations.push(Rotation::half_turn(
+                    rotation.face().clone(),
+                    rotation.layers().clone(),
+                    rotation.direction().clone(),
+                ));
+            } else {
+                return Err(NotationError::HalfTurnsOnly(letter(rotation.face())));
+            }
+        }
+
+        Ok(Self(rotations))
+    }
+
+    /// Write this algorithm as a commutator `[A, B]`, or a conjugated commutator `[S: [A, B]]`,
+    /// if it is one. Moves are compared as written, so nothing is cancelled along the way.
+    pub fn as_commutator(&self) -> Option<String> {
+        let rotations = &self.0;
+
+        // try the longest setup first, down to no setup at all
+        for setup_len in (0..=rotations.len() / 2).rev() {
+            let (setup, rest) = rotations.split_at(setup_len);
+            let (inner, undo) = rest.split_at(rest.len() - setup_len);
+            if Self(undo.to_vec()) != Self(setup.to_vec()).inverse() {
+                continue;
+            }
+
+            if let Some(commutator) = commutator(inner) {
+                return Some(if setup.is_empty() {
+                    commutator
+                } else {
+                    format!("[{}: {commutator}]", Self(setup.to_vec()))
+                });
+            }
+        }
+
+        None
+    }
+}
+
+/// `rotations` as `[A, B]` if it is `A B A' B'` for some non-empty `A` and `B`.
+fn commutator(rotations: &[Rotation]) -> Option<String> {
+    if !rotations.len().is_multiple_of(2) {
+        return None;
+    }
+
+    let half = rotations.len() / 2;
+    (1..half).find_map(|a_len| {
+        let a = Algorithm(rotations[..a_len].to_vec());
+        let b = Algorithm(rotations[a_len..half].to_vec());
+        let expected = [a.inverse().0, b.inverse().0].concat();
+        (rotations[half..] == expected[..]).then(|| format!("[{a}, {b}]"))
+    })
+}
+
+/// Why a piece of notation could not be read as an `Algorithm`.
+#[derive(Debug, PartialEq, Eq)]
+pub enum NotationError {
+    UnknownMove(char),
+    UnexpectedCharacter(char),
+    /// A bracket without a `,` or `:` separating its two parts.
+    MissingSeparator,
+    UnclosedBracket,
+    /// A layer prefix with no move after it.
+    MissingMove,
+    /// A quarter turn of a face that only makes half turns.
+    HalfTurnsOnly(char),
+    /// A slice on a cube with an even number of layers, which has no middle layer.
+    NoMiddleLayer(char),
+    /// A layer number past the last layer of the puzzle, counting from 1.
+    NoSuchLayer(usize),
+}
+
+impl fmt::Display for NotationError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::UnknownMove(c) => write!(f, "unknown move '{c}'"),
+            Self::UnexpectedCharacter(c) => write!(f, "unexpected '{c}'"),
+            Self::MissingSeparator => write!(f, "expected ',' or ':' inside brackets"),
+            Self::UnclosedBracket => write!(f, "missing ']'"),
+            Self::MissingMove => write!(f, "expected a move after the layer number"),
+            Self::HalfTurnsOnly(c) => write!(f, "'{c}' can only make half turns e.g. {c}2"),
+            Self::NoMiddleLayer(c) => write!(f, "'{c}' needs a middle layer to turn"),
+            Self::NoSuchLayer(layer) => write!(f, "there is no layer {layer}"),
+        }
+    }
+}
+
+impl FromStr for Algorithm {
+    type Err = NotationError;
+
+    /// Parse Singmaster notation. Whitespace between moves is optional, so `RUR'U'` and
+    /// `R U R' U'` are the same algorithm. A `2` repeats the move, so `U2` is two `U`s.
+    /// Inner layers of bigger cubes use SiGN notation e.g. `2R`, `Rw`, `r` or `3Fw`. The `M`,
+    /// `E` and `S` slices turn the middle layer, which is only known once the algorithm is fitted
+    /// to a cube with `for_size`.
+    ///
+    /// Commutators `[A, B]` expand to `A B A' B'` and conjugates `[A: B]` expand to `A B A'`.
+    /// These can be nested e.g. `[R: [U, D]]`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut chars = s.chars().peekable();
+        let algorithm = parse_sequence(&mut chars)?;
+
+        match chars.next() {
+            Some(c) => Err(NotationError::UnexpectedCharacter(c)),
+            None => Ok(algorithm),
+        }
+    }
+}
+
+type Chars<'a> = std::iter::Peekable<std::str::Chars<'a>>;
+
+/// Parse moves and brackets up to the end of the input, or up to a character that ends the
+/// sequence (`,`, `:` or `]`), which is left for the caller.
+fn parse_sequence(chars: &mut Chars) -> Result<Algorithm, NotationError> {
+    let mut rotations = Vec::new();
+
+    while let Some(&c) = chars.peek() {
+        match c {
+            ',' | ':' | ']' => break,
+            c if c.is_whitespace() => {
+                chars.next();
+            }
+            '[' => {
+                chars.next();
+                rotations.extend(parse_bracket(chars)?.0);
+            }
+            _ => rotations.extend(parse_move(chars)?),
+        }
+    }
+
+    Ok(Algorithm(rotations))
+}
+
+/// Parse the inside of a commutator or conjugate, after the opening `[`.
+fn parse_bracket(chars: &mut Chars) -> Result<Algorithm, NotationError> {
+    let first = parse_sequence(chars)?;
+    let separator = chars.next().ok_or(NotationError::UnclosedBracket)?;
+    if separator == ']' {
+        return Err(NotationError::MissingSeparator);
+    }
+
+    let second = parse_sequence(chars)?;
+    if chars.next() != Some(']') {
+        return Err(NotationError::UnclosedBracket);
+    }
+
+    let parts = if separator == ',' {
+        vec![
+            first.clone(),
+            second.clone(),
+            first.inverse(),
+            second.inverse(),
+        ]
+    } else {
+        vec![first.clone(), second, first.inverse()]
+    };
+
+    Ok(Algorithm(
+        parts.into_iter().flat_map(|part| part.0).collect(),
+    ))
+}
+
+/// Parse a single move along with any layer prefix before it, and any `w`, `2` or `'` after it.
+/// `2R` turns just the second layer from the right, while `3Rw` turns the three outer layers.
+fn parse_move(chars: &mut Chars) -> Result<Vec<Rotation>, NotationError> {
+    let prefix = parse_layer_prefix(chars)?;
+    let letter = chars.next().ok_or(NotationError::MissingMove)?;
+    let (face, layers, clockwise) = face_for_letter(letter)?;
+
+    let is_wide = chars.next_if_eq(&'w').is_some();
+    if *layers.end() != 0 && (prefix.is_some() || is_wide) {
+        // slices and lowercase wide moves already say which layers they turn
+        return Err(NotationError::UnexpectedCharacter(letter));
+    }
+    let layers = match (prefix, is_wide) {
+        (None, false) => layers,
+        (None, true) => 0..=1,
+        (Some((first, last)), false) if first == last => first..=last,
+        (Some((first, last)), true) if first == last => 0..=last,
+        (Some((first, last)), true) => first..=last,
+        (Some(_), false) => return Err(NotationError::UnexpectedCharacter(letter)),
+    };
+
+    let mut count = 1;
+    let mut prime = false;
+    while let Some(&modifier) = chars.peek() {
+        match modifier {
+            '2' => count = 2,
+            '\'' | '’' => prime = !prime,
+            _ => break,
+        }
+        chars.next();
+    }
+
+    let direction = if prime {
+        clockwise.opposite()
+    } else {
+        clockwise
+    };
+
+    Ok(vec![Rotation::new(face, layers, direction); count])
+}
+
+/// Parse the layers before a move, e.g. the `3` in `3Rw` or the `2-3` in `2-3Rw`, as indices
+/// counted from the face starting at 0.
+fn parse_layer_prefix(chars: &mut Chars) -> Result<Option<(usize, usize)>, NotationError> {
+    let Some(first) = parse_number(chars)? else {
+        return Ok(None);
+    };
+
+    let last = if chars.next_if_eq(&'-').is_some() {
+        parse_number(chars)?.ok_or(NotationError::MissingMove)?
+    } else {
+        first
+    };
+    if last < first {
+        return Err(NotationError::UnexpectedCharacter('-'));
+    }
+
+    Ok(Some((first - 1, last - 1)))
+}
+
+/// Parse a layer number, which counts from 1.
+fn parse_number(chars: &mut Chars) -> Result<Option<usize>, NotationError> {
+    let mut digits = String::new();
+    while let Some(digit) = chars.next_if(char::is_ascii_digit) {
+        digits.push(digit);
+    }
+
+    let Some(first_digit) = digits.chars().next() else {
+        return Ok(None);
+    };
+    match digits.parse::<usize>() {
+        Ok(0) => Err(NotationError::UnexpectedCharacter('0')),
+        Ok(number) => Ok(Some(number)),
+        // too many digits to be a layer
+        Err(_) => Err(NotationError::UnexpectedCharacter(first_digit)),
+    }
+}
+
+impl fmt::Display for Algorithm {
+    /// Writes the algorithm in Singmaster notation, merging identical neighbouring quarter turns
+    /// into double moves.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let tokens: Vec<_> = self
+            .moves()
+            .into_iter()
+            .map(|(rotation, is_double)| write_move(rotation, is_double, None))
+            .collect();
+
+        f.write_str(&tokens.join(" "))
+    }
+}
+
+/// The face a notation letter refers to, the layers it turns, and the direction it turns them in.
+fn face_for_letter(
+    letter: char,
+) -> Result<(Face, RangeInclusive<usize>, Direction), NotationError> {
+    // faces turn clockwise when looking at the face, which is backwards around its normal.
+    // M follows L, E follows D and S follows F. lowercase letters turn two layers.
+    let slice = |face| Ok((face, MIDDLE_LAYER..=MIDDLE_LAYER, Direction::Backward));
+    let face = match letter.to_ascii_uppercase() {
+        'U' => Face::Top,
+        'D' => Face::Bottom,
+        'L' => Face::Left,
+        'R' => Face::Right,
+        'F' => Face::Front,
+        'B' => Face::Back,
+        'M' => return slice(Face::Left),
+        'E' => return slice(Face::Bottom),
+        'S' => return slice(Face::Front),
+        _ if letter.is_alphabetic() => return Err(NotationError::UnknownMove(letter)),
+        _ => return Err(NotationError::UnexpectedCharacter(letter)),
+    };
+    let layers = if letter.is_lowercase() { 0..=1 } else { 0..=0 };
+
+    Ok((face, layers, Direction::Backward))
+}
+
+/// The notation letter for a face.
+fn letter(face: &Face) -> char {
+    match face {
+        Face::Top => 'U',
+        Face::Bottom => 'D',
+        Face::Left => 'L',
+        Face::Right => 'R',
+        Face::Front => 'F',
+        Face::Back => 'B',
+    }
+}
+
+/// The slice letter for the middle layer between `face` and its opposite, and whether the slice
+/// turns the same way as `face`. M follows L, E follows D and S follows F.
+fn slice_letter(face: &Face) -> (char, bool) {
+    match face {
+        Face::Left => ('M', true),
+        Face::Right => ('M', false),
+        Face::Bottom => ('E', true),
+        Face::Top => ('E', false),
+        Face::Front => ('S', true),
+        Face::Back => ('S', false),
+    }
+}
+
+/// Notation for a single rotation on a cube of the given size, or for two of the same rotation
+/// if `double` is set. The middle layer of a cube with an odd number of layers is written as a
+/// slice, and every other inner layer by its number.
+pub fn notation(rotation: &Rotation, double: bool, size: &CubeSize) -> String {
+    let middle = size.middle_layer(rotation.face().normal().as_ivec3());
+    write_move(rotation, double, middle)
+}
+
+/// Notation for a single rotation, writing it as a slice if it turns just the `middle` layer.
+/// Half turns are written the same way as two quarter turns.
+fn write_move(rotation: &Rotation, double: bool, middle: Option<usize>) -> String {
+    let letter = letter(rotation.face());
+    let (first, last) = (*rotation.layers().start(), *rotation.layers().end());
+    let (slice, slice_follows_face) = slice_letter(rotation.face());
+    let is_slice = first == last && (first == MIDDLE_LAYER || Some(first) == middle);
+    let turn = match (first, last) {
+        _ if is_slice => slice.to_string(),
+        (0, 0) => letter.to_string(),
+        (first, last) if first == last => format!("{}{letter}", first + 1),
+        (0, 1) => format!("{letter}w"),
+        (0, last) => format!("{}{letter}w", last + 1),
+        (first, last) => format!("{}-{}{letter}w", first + 1, last + 1),
+    };
+
+    // every face and slice letter turns clockwise when looking at the face it belongs to, and
+    // a slice turned from the other side turns the other way
+    let clockwise = *rotation.direction() == Direction::Backward;
+    let prime = if clockwise == (slice_follows_face || !is_slice) {
+        ""
+    } else {
+        "'"
+    };
+    let count = if double || rotation.is_half_turn() {
+        "2"
+    } else {
+        ""
+    };
+
+    format!("{turn}{count}{prime}")
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::state::CubeState;
+
+    #[test]
+    fn test_parse_and_display_round_trip() {
+        let algorithm: Algorithm = "R U R' U' M2 E'".parse().unwrap();
+        assert_eq!(algorithm.rotations().len(), 7);
+        assert_eq!(algorithm.move_count(), 6);
+        assert_eq!(algorithm.to_string(), "R U R' U' M2 E'");
+
+        let compact: Algorithm = "RUR'U'M2E'".parse().unwrap();
+        assert_eq!(compact, algorithm);
+    }
+
+    #[test]
+    fn test_layer_notation() {
+        let algorithm: Algorithm = "2R Rw r' 3Fw 2-3Uw S".parse().unwrap();
+        let layers = algorithm
+            .rotations()
+            .iter()
+            .map(|rotation| rotation.layers().clone())
+            .collect::<Vec<_>>();
+        assert_eq!(layers[..5], [1..=1, 0..=1, 0..=1, 0..=2, 1..=2]);
+        assert_eq!(algorithm.to_string(), "2R Rw Rw' 3Fw 2-3Uw S");
+        let fitted = algorithm.for_size(&CubeSize::cube(3)).unwrap();
+        assert_eq!(fitted.rotations()[5].layers(), &(1..=1));
+
+        assert_eq!("3".parse::<Algorithm>(), Err(NotationError::MissingMove));
+        assert_eq!(
+            "2M".parse::<Algorithm>(),
+            Err(NotationError::UnexpectedCharacter('M'))
+        );
+    }
+
+    #[test]
+    fn test_layer_prefix_errors() {
+        let parse = |s: &str, size: usize| {
+            s.parse::<Algorithm>()
+                .and_then(|algorithm| algorithm.for_size(&CubeSize::cube(size)))
+        };
+
+        // layers past the last one don't turn anything
+        assert_eq!(parse("5R", 3), Err(NotationError::NoSuchLayer(5)));
+        assert_eq!(parse("4Rw", 3), Err(NotationError::NoSuchLayer(4)));
+        assert_eq!(parse("2-5Uw", 4), Err(NotationError::NoSuchLayer(5)));
+        assert!(parse("3Rw 2-3Uw 3L", 3).is_ok());
+
+        assert_eq!(
+            "3-2Rw".parse::<Algorithm>(),
+            Err(NotationError::UnexpectedCharacter('-'))
+        );
+        assert_eq!(
+            "99999999999999999999R".parse::<Algorithm>(),
+            Err(NotationError::UnexpectedCharacter('9'))
+        );
+    }
+
+    #[test]
+    fn test_slices_turn_the_middle_layer() {
+        let parse = |s: &str, size: usize| {
+            s.parse::<Algorithm>()
+                .unwrap()
+                .for_size(&CubeSize::cube(size))
+        };
+        let turned = |algorithm: &Algorithm, size: usize| {
+            let mut state = CubeState::solved(CubeSize::cube(size));
+            state.apply_algorithm(algorithm);
+            state
+        };
+
+        // the middle of a 5x5x5 is the third layer in, which is written back as a slice
+        let five = parse("M E' S2 2L 3R Rw", 5).unwrap();
+        let layers: Vec<_> = five.rotations().iter().map(Rotation::layers).collect();
+        assert_eq!(layers[..4], [&(2..=2), &(2..=2), &(2..=2), &(2..=2)]);
+        let written = five.notation(&CubeSize::cube(5));
+        assert_eq!(written, "M E' S2 2L M' Rw");
+        assert_eq!(turned(&parse(&written, 5).unwrap(), 5), turned(&five, 5));
+
+        // even cubes have no middle layer, so inner layers are always numbered
+        assert_eq!(parse("M", 4), Err(NotationError::NoMiddleLayer('M')));
+        assert_eq!(parse("E'", 2), Err(NotationError::NoMiddleLayer('E')));
+        let four = parse("2L 2R' 3Fw2 2-3Uw", 4).unwrap();
+        let written = four.notation(&CubeSize::cube(4));
+        assert_eq!(written, "2L 2R' 3Fw2 2-3Uw");
+        assert_eq!(parse(&written, 4).unwrap(), four);
+    }
+
+    #[test]
+    fn test_inverse() {
+        let algorithm: Algorithm = "R U2 F'".parse().unwrap();
+        assert_eq!(algorithm.inverse().to_string(), "F U2' R'");
+    }
+
+    #[test]
+    fn test_half_turns_on_cuboids() {
+        let domino = CubeSize::cuboid(3, 2, 3);
+        let algorithm: Algorithm = "U R2 F2' D'".parse().unwrap();
+        let fitted = algorithm.for_size(&domino).unwrap();
+        assert_eq!(fitted.rotations().len(), 4);
+        assert!(fitted.rotations()[1].is_half_turn());
+        assert_eq!(fitted.to_string(), "U R2 F2' D'");
+        assert_eq!(fitted.inverse().to_string(), "D F2 R2' U'");
+
+        assert_eq!(
+            "U R".parse::<Algorithm>().unwrap().for_size(&domino),
+            Err(NotationError::HalfTurnsOnly('R'))
+        );
+    }
+
+    #[test]
+    fn test_commutators_and_conjugates() {
+        let parse = |s: &str| s.parse::<Algorithm>().unwrap();
+        assert_eq!(parse("[R, U]"), parse("R U R' U'"));
+        assert_eq!(parse("[F: R U]"), parse("F R U F'"));
+        assert_eq!(
+            parse("[R' : [U2, R D R']]"),
+            parse("R' U2 R D R' U2' R D' R' R")
+        );
+
+        assert_eq!(
+            parse("R U R' U'").as_commutator().as_deref(),
+            Some("[R, U]")
+        );
+        assert_eq!(
+            parse("[D: [R' D' R, U]]").as_commutator().as_deref(),
+            Some("[D: [R' D' R, U]]")
+        );
+        assert_eq!(parse("R U R' U").as_commutator(), None);
+    }
+
+    #[test]
+    fn test_bracket_errors() {
+        assert_eq!(
+            "[R U".parse::<Algorithm>(),
+            Err(NotationError::UnclosedBracket)
+        );
+        assert_eq!(
+            "[R; U]".parse::<Algorithm>(),
+            Err(NotationError::UnexpectedCharacter(';'))
+        );
+        assert_eq!(
+            "[R U]".parse::<Algorithm>(),
+            Err(NotationError::MissingSeparator)
+        );
+        assert_eq!(
+            "R U]".parse::<Algorithm>(),
+            Err(NotationError::UnexpectedCharacter(']'))
+        );
+    }
+
+    #[test]
+    fn test_unknown_move() {
+        assert_eq!(
+            "R X".parse::<Algorithm>(),
+            Err(NotationError::UnknownMove('X'))
+        );
+    }
+}
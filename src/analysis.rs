@@ -0,0 +1,263 @@
+use std::fmt;
+
+use bevy::prelude::*;
+
+use crate::{
+    algorithm::Algorithm,
+    cube::CubeSize,
+    cubie::Kind,
+    state::{CubeState, Orientation, sticker_normals},
+};
+
+/// The pieces an algorithm moves around in a loop, in the order it moves them.
+/// `twist` is how the first piece has been turned once it gets back to where it started:
+/// for corners 1 is a clockwise twist and 2 is anticlockwise, for edges 1 is a flip,
+/// and for centres it's the number of quarter turns.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Cycle {
+    pub positions: Vec<IVec3>,
+    pub twist: u8,
+}
+
+impl Cycle {
+    /// A cycle of one piece, i.e. a piece that stays where it is but may be twisted.
+    pub fn is_in_place(&self) -> bool {
+        self.positions.len() == 1
+    }
+}
+
+impl fmt::Display for Cycle {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let names = self
+            .positions
+            .iter()
+            .map(|position| piece_name(*position))
+            .collect::<Vec<_>>()
+            .join(" ");
+        let suffix = match (
+            sticker_normals(self.positions[0], &ANALYSIS_SIZE).len(),
+            self.twist,
+        ) {
+            (_, 0) => "",
+            (3, 1) => "+",
+            (3, _) => "-",
+            (2, _) => "*",
+            (_, 1) => "+",
+            (_, 2) => "2",
+            (_, _) => "-",
+        };
+
+        if self.is_in_place() {
+            write!(f, "{names}{suffix}")
+        } else {
+            write!(f, "({names}){suffix}")
+        }
+    }
+}
+
+/// Algorithms are studied on a 3x3x3, where every piece has a standard name.
+const ANALYSIS_SIZE: CubeSize = CubeSize::cube(3);
+
+/// What an algorithm does to the cube when applied to a solved cube.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct AlgorithmAnalysis {
+    /// How many times the algorithm must be repeated to get back to solved.
+    pub order: usize,
+    pub corners: Vec<Cycle>,
+    pub edges: Vec<Cycle>,
+    pub centres: Vec<Cycle>,
+}
+
+impl AlgorithmAnalysis {
+    pub fn new(algorithm: &Algorithm) -> Self {
+        let mut state = CubeState::solved(ANALYSIS_SIZE);
+        let algorithm = algorithm
+            .for_size(&ANALYSIS_SIZE)
+            .expect("every move turns a 3x3x3");
+        state.apply_algorithm(&algorithm);
+
+        let mut order = 1;
+        let mut corners = Vec::new();
+        let mut edges = Vec::new();
+        let mut centres = Vec::new();
+        let mut visited: Vec<IVec3> = Vec::new();
+
+        for piece in state.pieces() {
+            if visited.contains(&piece.home()) {
+                continue;
+            }
+
+            // follow the piece around the cycle, accumulating the turns applied on the way
+            let mut positions = vec![piece.home()];
+            let mut net = piece.orientation();
+            let mut position = piece.position();
+            while position != piece.home() {
+                positions.push(position);
+                let next = state.piece(position).expect("every position has a piece");
+                net = next.orientation().compose(&net);
+                position = next.position();
+            }
+            visited.extend(&positions);
+
+            // repeat the cycle until the first piece looks the same as it started
+            let normals = piece.sticker_normals();
+            let mut repeats = 1;
+            let mut repeated = net;
+            while !normals
+                .iter()
+                .all(|normal| repeated.apply(*normal) == *normal)
+            {
+                repeated = net.compose(&repeated);
+                repeats += 1;
+            }
+            order = lcm(order, positions.len() * repeats);
+
+            let twist = twist(piece.home(), &net);
+            if positions.len() == 1 && twist == 0 {
+                continue;
+            }
+
+            let cycle = Cycle { positions, twist };
+            match piece.kind() {
+                Kind::Corner => corners.push(cycle),
+                Kind::Edge => edges.push(cycle),
+                Kind::Centre => centres.push(cycle),
+            }
+        }
+
+        Self {
+            order,
+            corners,
+            edges,
+            centres,
+        }
+    }
+
+    /// One line per property e.g. "Order: 6", ready to show in the UI.
+    pub fn lines(&self) -> Vec<String> {
+        let mut lines = vec![format!("Order: {}", self.order)];
+        for (label, cycles) in [
+            ("Corners", &self.corners),
+            ("Edges", &self.edges),
+            ("Centres", &self.centres),
+        ] {
+            let moved = cycles.iter().filter(|cycle| !cycle.is_in_place());
+            let in_place = cycles.iter().filter(|cycle| cycle.is_in_place());
+            let moved = moved.map(Cycle::to_string).collect::<Vec<_>>();
+            let in_place = in_place.map(Cycle::to_string).collect::<Vec<_>>();
+
+            if !moved.is_empty() {
+                lines.push(format!("{label}: {}", moved.join(" ")));
+            }
+            if !in_place.is_empty() {
+                let effect = match label {
+                    "Corners" => "Twists",
+                    "Edges" => "Flips",
+                    _ => "Centre turns",
+                };
+                lines.push(format!("{effect}: {}", in_place.join(" ")));
+            }
+        }
+
+        lines
+    }
+}
+
+/// How the piece at `position` has been turned by `orientation`. See `Cycle::twist`.
+fn twist(position: IVec3, orientation: &Orientation) -> u8 {
+    let normals = sticker_normals(position, &ANALYSIS_SIZE);
+    if normals
+        .iter()
+        .all(|normal| orientation.apply(*normal) == *normal)
+    {
+        return 0;
+    }
+
+    match normals.len() {
+        // corners turn around the diagonal sticking out of the corner. clockwise when looking
+        // at the corner is backwards around the diagonal.
+        3 => {
+            let turned = orientation.apply(normals[0]);
+            if normals[0].cross(turned).dot(position) < 0 {
+                1
+            } else {
+                2
+            }
+        }
+        2 => 1,
+        // centres can be turned any number of quarter turns around their normal
+        _ => {
+            let normal = normals[0];
+            let reference = if normal.x == 0 { IVec3::X } else { IVec3::Y };
+            let mut turned = reference;
+            let mut quarter_turns = 0;
+            while turned != orientation.apply(reference) {
+                turned = Orientation::quarter_turn(normal, true).apply(turned);
+                quarter_turns += 1;
+            }
+            quarter_turns
+        }
+    }
+}
+
+/// The standard name for the piece at a position e.g. "UFR" or "DB".
+pub fn piece_name(position: IVec3) -> String {
+    let letters = [
+        (position.y, 'U', 'D'),
+        (position.z, 'F', 'B'),
+        (position.x, 'R', 'L'),
+    ];
+
+    letters
+        .into_iter()
+        .filter(|(coordinate, _, _)| *coordinate != 0)
+        .map(
+            |(coordinate, positive, negative)| {
+                if coordinate > 0 { positive } else { negative }
+            },
+        )
+        .collect()
+}
+
+fn gcd(a: usize, b: usize) -> usize {
+    if b == 0 { a } else { gcd(b, a % b) }
+}
+
+fn lcm(a: usize, b: usize) -> usize {
+    a / gcd(a, b) * b
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn analyse(notation: &str) -> AlgorithmAnalysis {
+        AlgorithmAnalysis::new(&notation.parse().unwrap())
+    }
+
+    #[test]
+    fn test_orders() {
+        assert_eq!(analyse("").order, 1);
+        assert_eq!(analyse("R").order, 4);
+        assert_eq!(analyse("R U R' U'").order, 6);
+        assert_eq!(analyse("R U").order, 105);
+        assert_eq!(analyse("R U2 D' B D'").order, 1260);
+    }
+
+    #[test]
+    fn test_sune_twists_corners() {
+        // sune twists the corners it moves and cycles three edges
+        let analysis = analyse("R U R' U R U2 R'");
+        assert_eq!(analysis.order, 6);
+        assert_eq!(analysis.edges.len(), 1);
+        assert_eq!(analysis.edges[0].positions.len(), 3);
+        assert!(analysis.corners.iter().all(|cycle| cycle.twist != 0));
+    }
+
+    #[test]
+    fn test_piece_names() {
+        assert_eq!(piece_name(IVec3::new(1, 1, 1)), "UFR");
+        assert_eq!(piece_name(IVec3::new(-1, 0, -1)), "BL");
+        assert_eq!(piece_name(IVec3::new(0, -1, 0)), "D");
+    }
+}
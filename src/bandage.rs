@@ -0,0 +1,249 @@
+use std::collections::HashMap;
+
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    cube::CubeSize,
+    cubie::{Cubie, CubieFace, position},
+    rotation::Rotation,
+    state::turn,
+};
+
+/// Where the bandaging is saved, so it's still there the next time the app starts.
+#[cfg(not(target_arch = "wasm32"))]
+const BANDAGING_PATH: &str = "bandaging.ron";
+
+/// Groups of cubies fused together into blocks, which always turn together. Turns that would
+/// split a block are refused. Cubies are identified by their `Cubie::home`, and the bandaging
+/// only applies to cubes of the size it was made for.
+#[derive(Clone, Debug, Default, PartialEq, Resource, Serialize, Deserialize)]
+pub struct Bandaging {
+    size: CubeSize,
+    blocks: Vec<Vec<[i32; 3]>>,
+}
+
+impl Bandaging {
+    /// The bandaging saved last time, or none if it was never saved or can't be read.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn load() -> Self {
+        std::fs::read_to_string(BANDAGING_PATH)
+            .ok()
+            .and_then(|text| ron::from_str(&text).ok())
+            .unwrap_or_default()
+    }
+
+    /// There are no files to read on the web, so bandaging only lasts until the page is closed.
+    #[cfg(target_arch = "wasm32")]
+    pub fn load() -> Self {
+        Self::default()
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn save(&self) -> Result<(), String> {
+        let text = ron::ser::to_string_pretty(self, ron::ser::PrettyConfig::default())
+            .map_err(|error| error.to_string())?;
+        std::fs::write(BANDAGING_PATH, text).map_err(|error| error.to_string())
+    }
+
+    #[cfg(target_arch = "wasm32")]
+    pub fn save(&self) -> Result<(), String> {
+        Ok(())
+    }
+
+    /// Whether any cubies are fused on a cube of the given size. The solvers don't know about
+    /// blocks, so bandaged cubes can't be solved.
+    pub fn is_bandaged(&self, size: &CubeSize) -> bool {
+        !self.blocks(size).is_empty()
+    }
+
+    /// The blocks on a cube of the given size.
+    fn blocks(&self, size: &CubeSize) -> &[Vec<[i32; 3]>] {
+        if self.size == *size {
+            &self.blocks
+        } else {
+            &[]
+        }
+    }
+
+    /// Fuse the cubies with the given homes into one block, along with every cubie already fused
+    /// to any of them. Bandaging for a cube of another size is replaced.
+    pub fn fuse(&mut self, size: CubeSize, homes: &[IVec3]) {
+        if self.size != size {
+            *self = Self {
+                size,
+                blocks: Vec::new(),
+            };
+        }
+
+        let mut block = homes.iter().map(|home| home.to_array()).collect::<Vec<_>>();
+        let (joined, apart): (Vec<_>, Vec<_>) = self
+            .blocks
+            .drain(..)
+            .partition(|existing| existing.iter().any(|home| block.contains(home)));
+        for home in joined.into_iter().flatten() {
+            if !block.contains(&home) {
+                block.push(home);
+            }
+        }
+        self.blocks = apart;
+        if block.len() > 1 {
+            self.blocks.push(block);
+        }
+    }
+
+    /// Free the cubie with the given home, along with the rest of its block.
+    pub fn unfuse(&mut self, home: IVec3) {
+        self.blocks
+            .retain(|block| !block.contains(&home.to_array()));
+    }
+
+    /// The homes of the cubies fused to each other, block by block.
+    pub fn block_homes(&self, size: &CubeSize) -> impl Iterator<Item = Vec<IVec3>> {
+        self.blocks(size)
+            .iter()
+            .map(|block| block.iter().map(|home| IVec3::from_array(*home)).collect())
+    }
+
+    /// Whether `rotation` would turn some of a block without the rest of it. `position` gives
+    /// where the cubie with each home is now, in half cubies.
+    pub fn splits_block(
+        &self,
+        rotation: &Rotation,
+        size: &CubeSize,
+        position: impl Fn(IVec3) -> Option<IVec3>,
+    ) -> bool {
+        self.blocks(size).iter().any(|block| {
+            let turned = block
+                .iter()
+                .filter_map(|home| position(IVec3::from_array(*home)))
+                .map(|position| turn(rotation, position, size).is_some())
+                .collect::<Vec<_>>();
+            turned.contains(&true) && turned.contains(&false)
+        })
+    }
+}
+
+/// The cubies picked to fuse together while editing the bandaging, or `None` when not editing.
+#[derive(Debug, Default, Resource)]
+pub struct BandageEditor(pub Option<Vec<IVec3>>);
+
+/// Pick the clicked cubie while editing the bandaging. Clicking a cubie that is already
+/// bandaged frees its block instead.
+pub fn pick_bandaged_cubie(
+    trigger: Trigger<Pointer<Click>>,
+    mut editor: ResMut<BandageEditor>,
+    mut bandaging: ResMut<Bandaging>,
+    cube_size: Res<CubeSize>,
+    faces: Query<&ChildOf, With<CubieFace>>,
+    cubies: Query<&Cubie>,
+) {
+    let Some(selected) = &mut editor.0 else {
+        return;
+    };
+    let Ok(cubie) = faces
+        .get(trigger.target())
+        .and_then(|face| cubies.get(face.parent()))
+    else {
+        return;
+    };
+
+    if bandaging
+        .block_homes(&cube_size)
+        .any(|block| block.contains(&cubie.home))
+    {
+        bandaging.unfuse(cubie.home);
+    } else if let Some(i) = selected.iter().position(|home| *home == cubie.home) {
+        selected.remove(i);
+    } else {
+        selected.push(cubie.home);
+    }
+}
+
+/// Draw a line between neighbouring cubies in each block, and a box around each cubie picked
+/// while editing. Lines are drawn through the cube so they can be seen from any side.
+pub fn draw_bandaging(
+    mut gizmos: Gizmos,
+    bandaging: Res<Bandaging>,
+    editor: Res<BandageEditor>,
+    cube_size: Res<CubeSize>,
+    cubies: Query<(&Cubie, &Transform)>,
+) {
+    let transforms = cubies
+        .iter()
+        .map(|(cubie, transform)| (cubie.home, *transform))
+        .collect::<HashMap<_, _>>();
+
+    for block in bandaging.block_homes(&cube_size) {
+        for (i, a) in block.iter().enumerate() {
+            for b in &block[i + 1..] {
+                // neighbours are a whole cubie, or two half cubies, apart
+                if a.distance_squared(*b) == 4
+                    && let (Some(a), Some(b)) = (transforms.get(a), transforms.get(b))
+                {
+                    gizmos.line(a.translation, b.translation, Color::BLACK);
+                }
+            }
+        }
+    }
+
+    for home in editor.0.iter().flatten() {
+        if let Some(transform) = transforms.get(home) {
+            gizmos.cuboid(*transform, Color::WHITE);
+        }
+    }
+}
+
+/// Draw gizmos on top of the cube, so bandaging inside it can be seen.
+pub fn draw_gizmos_on_top(mut config_store: ResMut<GizmoConfigStore>) {
+    config_store
+        .config_mut::<DefaultGizmoConfigGroup>()
+        .0
+        .depth_bias = -1.0;
+}
+
+/// Where each cubie is now, by its home, in half cubies.
+pub fn cubie_positions<'a>(
+    cubies: impl Iterator<Item = (&'a Cubie, &'a Transform)>,
+) -> HashMap<IVec3, IVec3> {
+    cubies
+        .map(|(cubie, transform)| (cubie.home, position(transform.translation)))
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{algorithm::Algorithm, state::CubeState};
+
+    #[test]
+    fn test_turns_that_split_blocks() {
+        let size = CubeSize::default();
+        let mut bandaging = Bandaging::default();
+        // the up-front-right corner fused to the up-front edge
+        bandaging.fuse(size, &[IVec3::new(2, 2, 2), IVec3::new(0, 2, 2)]);
+
+        let state = CubeState::solved(size);
+        let splits = |bandaging: &Bandaging, notation: &str| {
+            let algorithm = notation.parse::<Algorithm>().unwrap().for_size(&size);
+            let rotation = algorithm.unwrap().rotations()[0].clone();
+            bandaging.splits_block(&rotation, &size, |home| {
+                state.piece(home).map(|piece| piece.position())
+            })
+        };
+        assert!(splits(&bandaging, "R"));
+        assert!(splits(&bandaging, "M"));
+        assert!(!splits(&bandaging, "U"));
+        assert!(!splits(&bandaging, "F'"));
+        assert!(!splits(&bandaging, "L"));
+
+        // fusing a cubie onto a block adds it to the block, and other sizes aren't bandaged
+        bandaging.fuse(size, &[IVec3::new(0, 2, 2), IVec3::new(0, 2, 0)]);
+        assert_eq!(bandaging.block_homes(&size).count(), 1);
+        assert!(splits(&bandaging, "S"));
+        assert_eq!(bandaging.block_homes(&CubeSize::cube(4)).count(), 0);
+
+        bandaging.unfuse(IVec3::new(2, 2, 2));
+        assert!(!splits(&bandaging, "R"));
+    }
+}
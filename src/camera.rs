@@ -0,0 +1,332 @@
+use bevy::prelude::*;
+
+use crate::{
+    PlayMode,
+    cube::CubeSize,
+    mouse::MousePressed,
+    rotation::{Rotation, Rotations},
+};
+
+/// How quickly the camera stops spinning after it's let go, per second.
+const SPIN_DAMPING: f32 = 4.0;
+
+/// Spins slower than this, in radians per second, stop altogether.
+const MIN_SPIN: f32 = 0.01;
+
+/// How long the camera takes to move to a new view, in seconds.
+const TRANSITION_SECONDS: f32 = 0.6;
+
+/// How close and how far the camera can zoom, compared to where it started.
+const MIN_ZOOM: f32 = 0.35;
+const MAX_ZOOM: f32 = 3.0;
+
+/// How far off a face the camera can be, as the cosine of the angle, before the face is
+/// treated as hidden, and how far off it the camera moves to when following turns.
+const FACE_HIDDEN_COS: f32 = 0.2;
+const FACE_SHOWN_COS: f32 = 0.5;
+
+/// A view of the puzzle that can be picked with a button or a number key.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum CameraPreset {
+    Front,
+    Back,
+    Top,
+    /// Looking straight at a corner, in the direction of its signs along each axis.
+    Corner(IVec3),
+}
+
+impl CameraPreset {
+    /// Every view, with the top corners before the bottom ones. Each group of corners goes
+    /// around from the front right.
+    pub fn all() -> [Self; 11] {
+        let corner = |x, y, z| Self::Corner(IVec3::new(x, y, z));
+        [
+            Self::Front,
+            Self::Back,
+            Self::Top,
+            corner(1, 1, 1),
+            corner(-1, 1, 1),
+            corner(-1, 1, -1),
+            corner(1, 1, -1),
+            corner(1, -1, 1),
+            corner(-1, -1, 1),
+            corner(-1, -1, -1),
+            corner(1, -1, -1),
+        ]
+    }
+
+    /// The view's name, with corners named by their faces, e.g. `UFR`.
+    pub fn name(&self) -> String {
+        match self {
+            Self::Front => "Front".to_string(),
+            Self::Back => "Back".to_string(),
+            Self::Top => "Top".to_string(),
+            Self::Corner(corner) => [
+                if corner.y > 0 { 'U' } else { 'D' },
+                if corner.z > 0 { 'F' } else { 'B' },
+                if corner.x > 0 { 'R' } else { 'L' },
+            ]
+            .iter()
+            .collect(),
+        }
+    }
+
+    /// Where the camera goes for this view, `distance` from the middle.
+    pub fn view(&self, distance: f32) -> Transform {
+        let (direction, up) = match self {
+            Self::Front => (Vec3::Z, Vec3::Y),
+            // the front is at the bottom when looking down from the top
+            Self::Top => (Vec3::Y, Vec3::NEG_Z),
+            Self::Back => (Vec3::NEG_Z, Vec3::Y),
+            Self::Corner(corner) => (corner.as_vec3().normalize(), Vec3::Y),
+        };
+        Transform::from_translation(direction * distance).looking_at(Vec3::ZERO, up)
+    }
+}
+
+/// Whether the camera moves round to show faces being turned that it can't see.
+#[derive(Debug, Default, PartialEq, Resource)]
+pub struct FollowTurns(pub bool);
+
+/// A camera that orbits the middle of the puzzle, always looking at it.
+#[derive(Component)]
+pub struct OrbitCamera {
+    /// Which way the camera faces. The camera sits along its own z axis from the middle.
+    rotation: Quat,
+    distance: f32,
+    /// How far away the camera started, which the zoom limits are measured from.
+    start_distance: f32,
+    /// How fast the camera is spinning around its own axes, as an axis scaled by radians per
+    /// second.
+    spin: Vec3,
+    transition: Option<Transition>,
+}
+
+/// An animated move from one view to another.
+struct Transition {
+    from: (Quat, f32),
+    to: (Quat, f32),
+    elapsed: f32,
+}
+
+impl OrbitCamera {
+    /// A camera at `start`, which should be looking at the middle.
+    pub fn new(start: Transform) -> Self {
+        let distance = start.translation.length();
+        Self {
+            rotation: start.rotation,
+            distance,
+            start_distance: distance,
+            spin: Vec3::ZERO,
+            transition: None,
+        }
+    }
+
+    pub fn transform(&self) -> Transform {
+        Transform::from_translation(self.rotation * Vec3::Z * self.distance)
+            .with_rotation(self.rotation)
+    }
+
+    /// Jump straight to `start`, which is also where zooming is measured from.
+    pub fn set(&mut self, start: Transform) {
+        *self = Self::new(start);
+    }
+
+    /// Move smoothly to `start`, which is also where zooming is measured from.
+    pub fn move_to(&mut self, start: Transform) {
+        let target = Self::new(start);
+        self.transition = Some(Transition {
+            from: (self.rotation, self.distance),
+            to: (target.rotation, target.distance),
+            elapsed: 0.0,
+        });
+        self.start_distance = target.start_distance;
+        self.spin = Vec3::ZERO;
+    }
+
+    /// Orbit smoothly to face the way `rotation` does, staying as far away.
+    fn orbit_to(&mut self, rotation: Quat) {
+        self.transition = Some(Transition {
+            from: (self.rotation, self.distance),
+            to: (rotation, self.distance),
+            elapsed: 0.0,
+        });
+        self.spin = Vec3::ZERO;
+    }
+
+    /// Orbit the shortest way round until the face with `normal` can be clearly seen, if it's
+    /// hidden.
+    pub fn show_face(&mut self, normal: Vec3) {
+        // aim from wherever the camera is heading to, if it's already moving
+        let rotation = self
+            .transition
+            .as_ref()
+            .map_or(self.rotation, |transition| transition.to.0);
+        let from = rotation * Vec3::Z;
+        if from.dot(normal) >= FACE_HIDDEN_COS {
+            return;
+        }
+
+        // tip towards the face, keeping the same side of it in view
+        let across = (from - normal * from.dot(normal))
+            .try_normalize()
+            .unwrap_or(rotation * Vec3::Y);
+        let to = normal * FACE_SHOWN_COS + across * (1.0 - FACE_SHOWN_COS.powi(2)).sqrt();
+        self.orbit_to(Quat::from_rotation_arc(from, to) * rotation);
+    }
+
+    /// Turn the camera around the middle by `rotation` in its own frame, as it's dragged around
+    /// over `delta` seconds. It keeps spinning at the same speed once it's let go.
+    pub fn drag(&mut self, rotation: Quat, delta: f32) {
+        self.transition = None;
+        self.rotation = (self.rotation * rotation).normalize();
+        // smooth out the speed, since the mouse doesn't move the same amount every frame
+        let spin = rotation.to_scaled_axis() / delta.max(0.001);
+        self.spin = self.spin.lerp(spin, 0.5);
+    }
+
+    /// Stop the camera spinning, as it's held still.
+    pub fn hold(&mut self) {
+        self.spin = Vec3::ZERO;
+    }
+
+    /// Move the camera `factor` times as far away, within the zoom limits.
+    pub fn zoom(&mut self, factor: f32) {
+        let distance = self.distance * factor;
+        self.distance = distance.clamp(
+            self.start_distance * MIN_ZOOM,
+            self.start_distance * MAX_ZOOM,
+        );
+        if let Some(transition) = &mut self.transition {
+            transition.to.1 = (transition.to.1 * factor).clamp(
+                self.start_distance * MIN_ZOOM,
+                self.start_distance * MAX_ZOOM,
+            );
+        }
+    }
+
+    /// Move the camera on by `delta` seconds, spinning down or carrying on with a transition.
+    fn advance(&mut self, delta: f32, held: bool) {
+        if let Some(transition) = &mut self.transition {
+            transition.elapsed += delta;
+            let t = (transition.elapsed / TRANSITION_SECONDS).min(1.0);
+            let t = t * t * (3.0 - 2.0 * t);
+            self.rotation = transition.from.0.slerp(transition.to.0, t);
+            self.distance = transition.from.1.lerp(transition.to.1, t);
+            if t >= 1.0 {
+                self.transition = None;
+            }
+        } else if !held && self.spin != Vec3::ZERO {
+            self.rotation = (self.rotation * Quat::from_scaled_axis(self.spin * delta)).normalize();
+            self.spin *= (-SPIN_DAMPING * delta).exp();
+            if self.spin.length() < MIN_SPIN {
+                self.spin = Vec3::ZERO;
+            }
+        }
+    }
+}
+
+/// The face turned by `rotation` that should be in view, which is the face it's named after,
+/// or the opposite face if it only turns layers from the far side. Slices in the middle have no
+/// face.
+fn turned_face_normal(rotation: &Rotation, cube_size: &CubeSize) -> Option<Vec3> {
+    let normal = rotation.face().normal();
+    let last = cube_size.layers(normal.as_ivec3()) - 1;
+    if *rotation.layers().start() == 0 {
+        Some(normal)
+    } else if *rotation.layers().end() == last {
+        Some(-normal)
+    } else {
+        None
+    }
+}
+
+/// Move the camera round to show the face being turned next, when following turns. Shuffles
+/// move too quickly to follow.
+pub fn follow_turns(
+    follow_turns: Res<FollowTurns>,
+    play_mode: Res<PlayMode>,
+    cube_size: Res<CubeSize>,
+    rotations: Res<Rotations>,
+    mut camera: Single<&mut OrbitCamera>,
+    mut followed: Local<Option<Rotation>>,
+) {
+    if !follow_turns.0 || matches!(*play_mode, PlayMode::Shuffle) {
+        *followed = None;
+        return;
+    }
+
+    let next = rotations.current().or(rotations.next());
+    if next == followed.as_ref() {
+        return;
+    }
+    if let Some(normal) = next.and_then(|rotation| turned_face_normal(rotation, &cube_size)) {
+        camera.show_face(normal);
+    }
+    *followed = next.cloned();
+}
+
+/// Keep the camera spinning after it's let go, animate it back when it's reset, and move it to
+/// where it should be.
+pub fn update_orbit_camera(
+    time: Res<Time>,
+    mouse_pressed: Res<MousePressed>,
+    camera: Single<(&mut OrbitCamera, &mut Transform)>,
+) {
+    let (mut camera, mut transform) = camera.into_inner();
+    camera.advance(time.delta_secs(), mouse_pressed.0);
+    transform.set_if_neq(camera.transform());
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_orbit_camera_spins_down_and_resets() {
+        let start = Transform::from_xyz(5.0, 5.0, 10.0).looking_at(Vec3::ZERO, Vec3::Y);
+        let mut camera = OrbitCamera::new(start);
+        assert!(camera.transform().translation.distance(start.translation) < 1e-4);
+
+        // let go while spinning, and it slows down to a stop while still looking at the middle
+        camera.drag(Quat::from_rotation_y(0.1), 0.1);
+        for _ in 0..300 {
+            camera.advance(1.0 / 60.0, false);
+        }
+        assert_eq!(camera.spin, Vec3::ZERO);
+        let transform = camera.transform();
+        assert!((transform.forward().dot(-transform.translation.normalize()) - 1.0).abs() < 1e-4);
+
+        camera.zoom(100.0);
+        assert_eq!(camera.distance, camera.start_distance * MAX_ZOOM);
+
+        // resetting gets back to the start after the transition, not straight away
+        camera.move_to(start);
+        camera.advance(TRANSITION_SECONDS / 2.0, false);
+        assert!(camera.transform().translation.distance(start.translation) > 0.1);
+        camera.advance(TRANSITION_SECONDS, false);
+        assert!(camera.transform().translation.distance(start.translation) < 1e-3);
+    }
+
+    #[test]
+    fn test_camera_shows_hidden_faces() {
+        let mut camera = OrbitCamera::new(CameraPreset::Corner(IVec3::ONE).view(10.0));
+        let sees = |camera: &OrbitCamera, normal: Vec3| {
+            camera.transform().translation.normalize().dot(normal) >= FACE_HIDDEN_COS
+        };
+
+        // the right face is already in view
+        camera.show_face(Vec3::X);
+        assert!(camera.transition.is_none());
+
+        // the left face isn't, so the camera orbits round to it without zooming
+        assert!(!sees(&camera, Vec3::NEG_X));
+        camera.show_face(Vec3::NEG_X);
+        camera.advance(TRANSITION_SECONDS, false);
+        assert!(sees(&camera, Vec3::NEG_X) && sees(&camera, Vec3::Y));
+        assert!((camera.transform().translation.length() - 10.0).abs() < 1e-3);
+
+        let names: Vec<_> = CameraPreset::all().iter().map(CameraPreset::name).collect();
+        assert_eq!(names[3..7], ["UFR", "UFL", "UBL", "UBR"]);
+    }
+}
@@ -1,12 +1,10 @@
 use bevy::prelude::*;
 use rand::distr::{Distribution, StandardUniform};
+use serde::{Deserialize, Serialize};
 
-use crate::{
-    cubie::{CUBIE_FACE_OFFSET, CubieFace},
-    rotation::RotationTimer,
-};
+use crate::{cubie::FaceColor, history::Timeline, shape::ShapeMod, state::SolvedCheck};
 
-#[derive(Clone, Debug, Component)]
+#[derive(Clone, Debug, PartialEq, Eq, Hash, Component)]
 pub enum Face {
     Top,
     Bottom,
@@ -14,22 +12,11 @@ pub enum Face {
     Right,
     Front,
     Back,
-    HorizontalCentre, // the centre slice that is horizontal
-    VerticalCentre,   // the centre slice that is vertical
 }
 
 impl Face {
-    pub fn is_center(&self) -> bool {
-        match self {
-            Self::HorizontalCentre | Self::VerticalCentre => true,
-            _ => false,
-        }
-    }
-
-    /// A flat cube face is a face that lies on one plane.
-    /// Essentially it is all the faces except the centre ones.
-    pub fn flat_faces() -> Vec<Self> {
-        vec![
+    pub fn variants() -> [Self; 6] {
+        [
             Self::Top,
             Self::Bottom,
             Self::Left,
@@ -39,6 +26,18 @@ impl Face {
         ]
     }
 
+    /// Where the face comes in `variants`.
+    pub fn index(&self) -> usize {
+        match self {
+            Self::Top => 0,
+            Self::Bottom => 1,
+            Self::Left => 2,
+            Self::Right => 3,
+            Self::Front => 4,
+            Self::Back => 5,
+        }
+    }
+
     /// The unit vector representing the normal for a cube face.
     pub fn normal(&self) -> Vec3 {
         match self {
@@ -48,11 +47,10 @@ impl Face {
             Self::Right => Vec3::X,
             Self::Front => Vec3::Z,
             Self::Back => -Vec3::Z,
-            Self::HorizontalCentre => Vec3::Y,
-            Self::VerticalCentre => Vec3::X,
         }
     }
 
+    #[allow(clippy::inherent_to_string)]
     pub fn to_string(&self) -> String {
         let s = match self {
             Self::Top => "Top",
@@ -61,85 +59,155 @@ impl Face {
             Self::Right => "Right",
             Self::Front => "Front",
             Self::Back => "Back",
-            Self::HorizontalCentre => "Horizontal Center",
-            Self::VerticalCentre => "Vertical Center",
         };
 
         s.to_string()
     }
+
+    /// The face on the other side of the cube.
+    pub fn opposite(&self) -> Self {
+        match self {
+            Self::Top => Self::Bottom,
+            Self::Bottom => Self::Top,
+            Self::Left => Self::Right,
+            Self::Right => Self::Left,
+            Self::Front => Self::Back,
+            Self::Back => Self::Front,
+        }
+    }
+
+    /// Returns the starting color for a cube face in the solved state
+    /// For example, in a solved cube with white on top and red facing the user, blue is on the right and green is on the left, etc.
+    pub fn start_color(&self) -> FaceColor {
+        match self {
+            Self::Top => FaceColor::White,
+            Self::Bottom => FaceColor::Yellow,
+            Self::Left => FaceColor::Green,
+            Self::Right => FaceColor::Blue,
+            Self::Front => FaceColor::Red,
+            Self::Back => FaceColor::Orange,
+        }
+    }
 }
 
 impl Distribution<Face> for StandardUniform {
     /// Get a random `Face`
     fn sample<R: rand::Rng + ?Sized>(&self, rng: &mut R) -> Face {
-        match rng.random_range(0..8) {
+        match rng.random_range(0..6) {
             1 => Face::Top,
             2 => Face::Bottom,
             3 => Face::Left,
             4 => Face::Right,
             5 => Face::Front,
-            6 => Face::Back,
-            7 => Face::HorizontalCentre,
-            _ => Face::VerticalCentre,
+            _ => Face::Back,
         }
     }
 }
 
-#[derive(Resource)]
-pub struct IsCubeSolved(pub bool);
+/// The number of layers along the x, y and z axes, e.g. 3 each for a 3x3x3. Cuboids have a
+/// different number of layers along some axes, so a 2x2x3 is 2 wide, 3 tall and 2 deep.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Resource, Serialize, Deserialize)]
+pub struct CubeSize([usize; 3]);
+
+impl CubeSize {
+    pub const MIN: usize = 2;
+    pub const MAX: usize = 9;
+
+    /// The cuboids that can be picked, as their width, height and depth.
+    pub const CUBOIDS: [Self; 3] = [
+        Self::cuboid(2, 3, 2),
+        Self::cuboid(3, 2, 3),
+        Self::cuboid(3, 4, 3),
+    ];
+
+    /// A cube with `layers` layers along every axis.
+    pub const fn cube(layers: usize) -> Self {
+        Self([layers; 3])
+    }
 
-/// Check whether the cube is in a solved state and update the `IsCubeSolved` resource.
-pub fn check_cube_solved(
-    mut is_cube_solved: ResMut<IsCubeSolved>,
-    rotation_timer: Res<RotationTimer>,
-    cubie_faces: Query<(&GlobalTransform, &MeshMaterial3d<StandardMaterial>), With<CubieFace>>,
-    materials: Res<Assets<StandardMaterial>>,
-) {
-    if rotation_timer.0.finished() {
-        // check if all cubie faces on a given cube face have the same color
-        let mut cube_solved = true;
-        let cubie_faces = cubie_faces.iter().collect::<Vec<_>>();
-        for face in Face::flat_faces() {
-            if !are_all_colors_on_face_same(&face, &cubie_faces, &materials) {
-                cube_solved = false;
-                break;
-            }
-        }
+    pub const fn cuboid(width: usize, height: usize, depth: usize) -> Self {
+        Self([width, height, depth])
+    }
 
-        is_cube_solved.0 = cube_solved;
+    /// The number of layers along `axis`, which can point either way along it.
+    pub fn layers(&self, axis: IVec3) -> usize {
+        let [x, y, z] = self.0;
+        let axis = axis.abs();
+        x * axis.x as usize + y * axis.y as usize + z * axis.z as usize
+    }
+
+    /// The middle layer along `axis`, counted from either face, if there's an odd number of
+    /// layers along it. This is the layer turned by the `M`, `E` and `S` slice moves.
+    pub fn middle_layer(&self, axis: IVec3) -> Option<usize> {
+        let layers = self.layers(axis);
+        (layers % 2 == 1 && layers > 1).then_some(layers / 2)
+    }
+
+    /// The most layers along any axis, which is the number along every axis of a cube.
+    pub fn largest(&self) -> usize {
+        self.0.into_iter().max().unwrap_or_default()
+    }
+
+    pub fn is_cube(&self) -> bool {
+        self.0.iter().all(|layers| *layers == self.0[0])
+    }
+
+    /// How far the centre of the outermost cubies is from the centre of the cube along each axis.
+    pub fn half_extent(&self) -> Vec3 {
+        Vec3::from_array(self.0.map(|layers| (layers as f32 - 1.0) / 2.0))
+    }
+
+    /// Where the outermost cubies are along each axis, in half cubies. See `Cubie`.
+    pub fn outer(&self) -> IVec3 {
+        IVec3::from_array(self.0.map(|layers| layers as i32 - 1))
+    }
+
+    /// Whether `face` can make quarter turns. A quarter turn of a face that isn't square would
+    /// leave it sticking out of the cuboid, so those faces only make half turns.
+    pub fn allows_quarter_turns(&self, face: &Face) -> bool {
+        let axis = face.normal().as_ivec3();
+        let across = [IVec3::X, IVec3::Y, IVec3::Z]
+            .into_iter()
+            .filter(|other| other.dot(axis) == 0)
+            .map(|other| self.layers(other))
+            .collect::<Vec<_>>();
+        across[0] == across[1]
     }
 }
 
-/// Check whether a single face is in the solved state.
-fn are_all_colors_on_face_same(
-    face: &Face,
-    cubie_faces: &Vec<(&GlobalTransform, &MeshMaterial3d<StandardMaterial>)>,
-    materials: &Res<Assets<StandardMaterial>>,
-) -> bool {
-    let normal = face.normal();
-
-    // a color chosen at random from the face being checked
-    // this is used to assert that all other colors on this cube face are the same
-    let mut sample_color: Option<Color> = None;
-
-    for (face_transform, material_3d) in cubie_faces {
-        if normal.dot(face_transform.translation()) == 1. + CUBIE_FACE_OFFSET {
-            if let Some(material) = materials.get(&material_3d.0) {
-                let color = material.base_color;
-
-                // a sample color has already been picked, just compare
-                if let Some(sample_color) = sample_color {
-                    if sample_color != color {
-                        return false;
-                    }
-                }
-                // sample color needs to be picked
-                else {
-                    sample_color = Some(color);
-                }
-            }
-        }
+impl Default for CubeSize {
+    fn default() -> Self {
+        Self::cube(3)
     }
+}
+
+#[derive(Resource)]
+pub struct IsCubeSolved(pub bool);
+
+/// Whether the cube is played as a supercube, where the middle centre of each face shows which
+/// way it's turned and has to be turned back for the cube to be solved.
+#[derive(Debug, Default, PartialEq, Resource)]
+pub struct Supercube(pub bool);
 
-    true
+/// Whether the cube is played as a void cube, which has no centres but can still turn its
+/// middle layers. Without centres to show which way round the cube is, a middle layer turn is
+/// just another move, and two edges can end up swapped with the corners solved.
+#[derive(Debug, Default, PartialEq, Resource)]
+pub struct VoidCube(pub bool);
+
+/// Check whether the cube is in a solved state and update the `IsCubeSolved` resource. The
+/// solved indicator uses the same `SolvedCheck` as the solvers and reconstructions, on the moves
+/// in the timeline.
+pub fn check_cube_solved(
+    mut is_cube_solved: ResMut<IsCubeSolved>,
+    supercube: Res<Supercube>,
+    void_cube: Res<VoidCube>,
+    shape_mod: Res<ShapeMod>,
+    timeline: Res<Timeline>,
+) {
+    let check_changed = supercube.is_changed() || void_cube.is_changed() || shape_mod.is_changed();
+    if check_changed || timeline.is_changed() {
+        let check = SolvedCheck::new(&supercube, &void_cube, &shape_mod);
+        is_cube_solved.0 = check.is_solved(&timeline.state());
+    }
 }
@@ -1,96 +1,200 @@
 use bevy::prelude::*;
 
-#[derive(Clone, Component)]
-pub struct Cubie;
+use crate::{
+    cube::{CubeSize, Face, Supercube, VoidCube},
+    model::CubieAssets,
+    puzzle::Puzzle,
+    rotation::Rotations,
+    shape::{ShapeMod, box_face_mesh},
+};
 
-pub const CUBIE_FACE_OFFSET: f32 = 0.49;
+/// A cubie, along with where it sits in the solved cube.
+/// Positions are measured in half cubies so that cubes with an even number of layers, whose
+/// cubies sit half way between whole numbers, still have whole number positions.
+#[derive(Clone, Component)]
+pub struct Cubie {
+    pub home: IVec3,
+}
 
 pub fn spawn_cubies(
     commands: &mut Commands,
     meshes: &mut ResMut<Assets<Mesh>>,
-    materials: &mut ResMut<Assets<StandardMaterial>>,
+    cubie_assets: &CubieAssets,
+    cube_size: &CubeSize,
+    void_cube: &VoidCube,
+    shape_mod: &ShapeMod,
 ) {
-    for x in -1..=1 {
-        for y in -1..=1 {
-            for z in -1..=1 {
-                if let Some(kind) = Kind::from_coordinates(x, y, z) {
-                    // spawn parent cubie to anchor faces
-                    let cubie =
-                        CubieBundle::new(kind, Transform::from_xyz(x as f32, y as f32, z as f32));
-
-                    // spawn cubie faces
-                    commands.spawn(cubie.clone()).with_children(|parent| {
-                        let cubie_face_half_size = Vec2::new(CUBIE_FACE_OFFSET, CUBIE_FACE_OFFSET);
-                        for face in Face::variants() {
-                            let normal = face.normal();
-                            let transform = Transform::from_translation(normal * CUBIE_FACE_OFFSET);
-                            parent.spawn((
-                                CubieFace,
-                                Mesh3d(
-                                    meshes.add(Plane3d::new(normal, cubie_face_half_size.clone())),
-                                ),
-                                MeshMaterial3d(materials.add(face.start_color().color())),
-                                transform,
-                                GlobalTransform::IDENTITY,
-                            ));
-                        }
-                    });
+    for position in grid_positions(cube_size) {
+        if let Some(kind) = Kind::from_position(position, cube_size)
+            && !(void_cube.0 && kind == Kind::Centre)
+        {
+            // spawn parent cubie to anchor faces
+            let cubie = CubieBundle::new(kind, position);
+
+            // spawn cubie faces
+            commands.spawn(cubie.clone()).with_children(|parent| {
+                // how far the outside of a fixed centre is from its middle
+                let mut surface = cubie_assets.sticker_offset;
+                if let Some(grid) = shape_mod.0 {
+                    // shape mods have a box for each cubie, all the same color
+                    // every cubie is a different shape, so each needs its own meshes
+                    let (min, max) = grid.cubie_bounds(position, cube_size);
+                    for face in Face::variants() {
+                        let (mesh, centre) = box_face_mesh(&face, min, max);
+                        parent.spawn((
+                            CubieFace,
+                            Mesh3d(meshes.add(mesh)),
+                            MeshMaterial3d(cubie_assets.shape_mod_material.clone()),
+                            Transform::from_translation(centre),
+                            GlobalTransform::IDENTITY,
+                        ));
+                    }
+                    let normal = position.signum().as_vec3();
+                    surface = normal.dot(if normal.max_element() > 0.0 { max } else { min });
+                } else {
+                    parent.spawn((
+                        CubieBody,
+                        Mesh3d(cubie_assets.body.clone()),
+                        MeshMaterial3d(cubie_assets.body_material.clone()),
+                    ));
+                    for face in Face::variants() {
+                        let normal = face.normal();
+                        let transform =
+                            Transform::from_translation(normal * cubie_assets.sticker_offset)
+                                .with_rotation(Quat::from_rotation_arc(Vec3::Z, normal));
+                        parent.spawn((
+                            CubieFace,
+                            face.clone(),
+                            Mesh3d(cubie_assets.sticker.clone()),
+                            MeshMaterial3d(cubie_assets.sticker_material(&face).clone()),
+                            transform,
+                            GlobalTransform::IDENTITY,
+                        ));
+                    }
+                }
+                if is_fixed_centre(position) {
+                    parent.spawn(centre_marker(position, surface, cubie_assets));
                 }
-            }
+            });
         }
     }
 }
 
+/// A dot near one edge of a fixed centre's sticker, so a supercube shows which way the centre is
+/// turned. It's only visible while playing a supercube.
 #[derive(Component)]
-pub struct CubieFace;
+pub struct CentreMarker;
+
+pub const CENTRE_MARKER_HALF_SIZE: f32 = 0.08;
+
+pub const CENTRE_MARKER_COLOR: Color = Color::srgb(0.16, 0.16, 0.16);
+
+fn centre_marker(position: IVec3, surface: f32, cubie_assets: &CubieAssets) -> impl Bundle {
+    let normal = position.signum().as_vec3();
+    // towards the top edge of side faces, and the edge nearest the back or front on the top and
+    // bottom, as they're laid out in a net
+    let up = if normal.y == 0.0 {
+        Vec3::Y
+    } else {
+        -Vec3::Z * normal.y
+    };
+    let translation = normal * (surface + 0.005) + up * 0.3;
 
-#[derive(Clone, Debug)]
-pub enum Face {
-    Top,
-    Bottom,
-    Left,
-    Right,
-    Front,
-    Back,
+    (
+        CentreMarker,
+        Mesh3d(cubie_assets.marker.clone()),
+        MeshMaterial3d(cubie_assets.marker_material.clone()),
+        Transform::from_translation(translation)
+            .with_rotation(Quat::from_rotation_arc(Vec3::Z, normal)),
+        Visibility::Hidden,
+        Pickable::IGNORE,
+    )
 }
 
-impl Face {
-    pub fn variants() -> [Face; 6] {
-        [
-            Self::Top,
-            Self::Bottom,
-            Self::Left,
-            Self::Right,
-            Self::Front,
-            Self::Back,
-        ]
+/// Show the centre markers while playing a supercube.
+pub fn show_centre_markers(
+    supercube: Res<Supercube>,
+    mut markers: Query<&mut Visibility, With<CentreMarker>>,
+) {
+    let visibility = if supercube.0 {
+        Visibility::Inherited
+    } else {
+        Visibility::Hidden
+    };
+    for mut marker in &mut markers {
+        marker.set_if_neq(visibility);
     }
+}
 
-    pub fn normal(&self) -> Vec3 {
-        match self {
-            Self::Top => Vec3::Y,
-            Self::Bottom => -Vec3::Y,
-            Self::Left => -Vec3::X,
-            Self::Right => Vec3::X,
-            Self::Front => Vec3::Z,
-            Self::Back => -Vec3::Z,
-        }
+/// Replace every cubie with a solved cube of the new size whenever the size changes, the cube
+/// becomes or stops being a void cube or its shape mod changes, or remove them while another
+/// puzzle is picked.
+#[allow(clippy::too_many_arguments)]
+pub fn respawn_cubies(
+    mut commands: Commands,
+    mut meshes: ResMut<Assets<Mesh>>,
+    cubie_assets: Res<CubieAssets>,
+    cube_size: Res<CubeSize>,
+    void_cube: Res<VoidCube>,
+    shape_mod: Res<ShapeMod>,
+    puzzle: Res<Puzzle>,
+    mut rotations: ResMut<Rotations>,
+    cubies: Query<Entity, With<Cubie>>,
+) {
+    let cube_changed = cube_size.is_changed() || void_cube.is_changed() || shape_mod.is_changed();
+    if !(cube_changed || puzzle.is_changed()) || cube_size.is_added() {
+        return;
     }
 
-    /// Returns the starting color for a cube face in the solved state
-    /// For example, in a solved cube with white on top and red facing the user, blue is on the right and green is on the left, etc.
-    pub fn start_color(&self) -> FaceColor {
-        match self {
-            Self::Top => FaceColor::White,
-            Self::Bottom => FaceColor::Yellow,
-            Self::Left => FaceColor::Green,
-            Self::Right => FaceColor::Blue,
-            Self::Front => FaceColor::Red,
-            Self::Back => FaceColor::Orange,
-        }
+    rotations.clear();
+    for cubie in &cubies {
+        commands.entity(cubie).despawn();
+    }
+    if *puzzle == Puzzle::Cube {
+        spawn_cubies(
+            &mut commands,
+            &mut meshes,
+            &cubie_assets,
+            &cube_size,
+            &void_cube,
+            &shape_mod,
+        );
     }
 }
 
+/// Every position in a cube of the given size, in half cubies. See `Cubie`.
+pub fn grid_positions(cube_size: &CubeSize) -> impl Iterator<Item = IVec3> {
+    let outer = cube_size.outer();
+    let coordinates = move |outer: i32| (-outer..=outer).step_by(2);
+
+    coordinates(outer.x).flat_map(move |x| {
+        coordinates(outer.y)
+            .flat_map(move |y| coordinates(outer.z).map(move |z| IVec3::new(x, y, z)))
+    })
+}
+
+/// Convert a position in half cubies to a translation.
+pub fn translation(position: IVec3) -> Vec3 {
+    position.as_vec3() / 2.0
+}
+
+/// Convert a translation to the nearest position in half cubies.
+pub fn position(translation: Vec3) -> IVec3 {
+    (translation * 2.0).round().as_ivec3()
+}
+
+/// A sticker. Stickers on a cube with stickers also have the `Face` they started on, which
+/// stands for their color whatever the theme.
+#[derive(Component)]
+pub struct CubieFace;
+
+/// The rounded plastic under a cubie's stickers, which shows in the gaps between them. Themes
+/// with a stickerless body hide it.
+#[derive(Component)]
+pub struct CubieBody;
+
+pub const CUBIE_BODY_COLOR: Color = Color::srgb(0.03, 0.03, 0.03);
+
 #[derive(Component)]
 pub enum FaceColor {
     Orange,
@@ -114,27 +218,35 @@ impl FaceColor {
     }
 }
 
-#[derive(Clone, Component)]
+#[derive(Clone, Debug, PartialEq, Eq, Component)]
 pub enum Kind {
     Centre,
     Corner,
     Edge,
 }
 
+/// Whether the piece with this home is the middle centre of a face, which only turns in place
+/// and doesn't move relative to the other middle centres.
+pub fn is_fixed_centre(home: IVec3) -> bool {
+    home.cmpeq(IVec3::ZERO).bitmask().count_ones() == 2
+}
+
 impl Kind {
-    /// Gets the `CubieKind` given a set of `x`, `y`, and `z` coordinates.
-    pub fn from_coordinates(x: i8, y: i8, z: i8) -> Option<Self> {
-        let (x_abs, y_abs, z_abs) = (x.abs(), y.abs(), z.abs());
-        let abs_sum = x_abs + y_abs + z_abs;
-        if x == 0 && y == 0 && z == 0 {
-            return None;
-        } else if abs_sum == 1 {
-            return Some(Self::Centre);
-        } else if x.abs() == y.abs() && x.abs() == z.abs() {
-            return Some(Self::Corner);
-        }
+    /// Gets the `Kind` of the cubie at a position, in half cubies, in a cube of the given size.
+    /// Cubies hidden inside the cube have no kind.
+    pub fn from_position(position: IVec3, cube_size: &CubeSize) -> Option<Self> {
+        let outer_coordinates = position
+            .abs()
+            .cmpeq(cube_size.outer())
+            .bitmask()
+            .count_ones();
 
-        return Some(Kind::Edge);
+        match outer_coordinates {
+            0 => None,
+            1 => Some(Self::Centre),
+            2 => Some(Self::Edge),
+            _ => Some(Self::Corner),
+        }
     }
 }
 
@@ -147,12 +259,12 @@ pub struct CubieBundle {
 }
 
 impl CubieBundle {
-    pub fn new(kind: Kind, transform: Transform) -> Self {
+    pub fn new(kind: Kind, position: IVec3) -> Self {
         Self {
             visibility: Visibility::default(),
-            cubie: Cubie,
+            cubie: Cubie { home: position },
             kind,
-            transform,
+            transform: Transform::from_translation(translation(position)),
         }
     }
 }
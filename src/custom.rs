@@ -0,0 +1,423 @@
+use std::{fmt, sync::Arc};
+
+use bevy::{
+    asset::{AssetLoader, LoadContext, io::Reader},
+    prelude::*,
+};
+use rand::Rng;
+use serde::Deserialize;
+
+use crate::{
+    algorithm::NotationError,
+    camera::OrbitCamera,
+    cube::CubeSize,
+    puzzle::{AxisDefinition, FaceDefinition, Puzzle, PuzzleDefinition, Twist, misfit_axis, solid},
+    puzzle_camera_start_position,
+};
+
+/// The puzzle picked with the "Custom" button, in the assets folder. It's loaded when the app
+/// starts, and spawned in place of the cube if it asks to be.
+pub const CUSTOM_PUZZLE_PATH: &str = "puzzles/custom.puzzle.ron";
+
+/// A puzzle written in RON, so new puzzles can be tried out without changing any code. See
+/// `assets/puzzles/custom.puzzle.ron` for an example.
+#[derive(Clone, Debug, PartialEq, Asset, TypePath, Deserialize)]
+pub struct PuzzleFile {
+    pub name: String,
+    pub shape: Shape,
+    pub axes: Vec<AxisFile>,
+    #[serde(default = "default_scramble_length")]
+    pub scramble_length: usize,
+    /// Whether the app starts with this puzzle rather than a cube.
+    #[serde(default)]
+    pub startup: bool,
+}
+
+fn default_scramble_length() -> usize {
+    25
+}
+
+/// The solid a puzzle is cut from, centred on the origin.
+#[derive(Clone, Debug, PartialEq, Deserialize)]
+pub enum Shape {
+    /// A convex solid with a face `distance` from the centre along each face's normal.
+    Solid {
+        distance: f32,
+        faces: Vec<SolidFace>,
+    },
+    /// Every face of the solid, as its corners in order around it.
+    Polygons(Vec<PolygonFace>),
+}
+
+#[derive(Clone, Debug, PartialEq, Deserialize)]
+pub struct SolidFace {
+    pub normal: [f32; 3],
+    pub color: [u8; 3],
+}
+
+#[derive(Clone, Debug, PartialEq, Deserialize)]
+pub struct PolygonFace {
+    pub vertices: Vec<[f32; 3]>,
+    pub color: [u8; 3],
+}
+
+/// An axis the puzzle turns around. See `AxisDefinition`.
+#[derive(Clone, Debug, PartialEq, Deserialize)]
+pub struct AxisFile {
+    /// What moves around this axis are called in notation.
+    pub name: String,
+    pub normal: [f32; 3],
+    pub cuts: Vec<f32>,
+    pub order: u32,
+}
+
+fn color([r, g, b]: [u8; 3]) -> Color {
+    Color::srgb_u8(r, g, b)
+}
+
+impl PuzzleFile {
+    /// Check the puzzle can be built and turned, since a bad file would otherwise only show up
+    /// as NaNs or a panic once it's picked.
+    pub fn check(&self) -> Result<(), PuzzleFileError> {
+        if let Some(axis) = self.axes.iter().find(|axis| axis.order < 2) {
+            return Err(PuzzleFileError::NoTurns(axis.name.clone()));
+        }
+        let is_zero = |normal: &[f32; 3]| Vec3::from(*normal).length_squared() < f32::EPSILON;
+        if let Some(axis) = self.axes.iter().find(|axis| is_zero(&axis.normal)) {
+            return Err(PuzzleFileError::ZeroNormal(format!("axis {}", axis.name)));
+        }
+        let solid_faces = match &self.shape {
+            Shape::Solid { faces, .. } => faces.as_slice(),
+            Shape::Polygons(_) => &[],
+        };
+        if let Some(face) = solid_faces.iter().position(|face| is_zero(&face.normal)) {
+            return Err(PuzzleFileError::ZeroNormal(format!("face {face}")));
+        }
+
+        let definition = self.definition();
+        if let Some(face) = definition
+            .faces
+            .iter()
+            .position(|face| face.vertices.len() < 3 || face.area() < f32::EPSILON)
+        {
+            return Err(PuzzleFileError::DegenerateFace(face));
+        }
+        match misfit_axis(&definition) {
+            Some(axis) => Err(PuzzleFileError::MisfitTurn(self.axes[axis].name.clone())),
+            None => Ok(()),
+        }
+    }
+
+    pub fn definition(&self) -> PuzzleDefinition {
+        let faces = match &self.shape {
+            Shape::Solid { distance, faces } => {
+                let normals = faces
+                    .iter()
+                    .map(|face| Vec3::from(face.normal).normalize())
+                    .collect::<Vec<_>>();
+                let colors = faces
+                    .iter()
+                    .map(|face| color(face.color))
+                    .collect::<Vec<_>>();
+                solid(&normals, *distance, &colors)
+            }
+            Shape::Polygons(faces) => faces
+                .iter()
+                .map(|face| {
+                    let vertices = face.vertices.iter().copied().map(Vec3::from).collect();
+                    FaceDefinition::new(vertices, color(face.color))
+                })
+                .collect(),
+        };
+
+        let axes = self
+            .axes
+            .iter()
+            .map(|axis| AxisDefinition {
+                normal: Vec3::from(axis.normal).normalize(),
+                cuts: axis.cuts.clone(),
+                order: axis.order,
+            })
+            .collect();
+
+        PuzzleDefinition { faces, axes }
+    }
+
+    /// Read moves with a space between each. An axis's name turns its outermost layer one step
+    /// clockwise, a number after it turns that many steps and `'` turns anticlockwise. A number
+    /// before it turns that layer instead, counting from 1 at the outside, e.g. `2UFR`.
+    pub fn parse(&self, s: &str) -> Result<Vec<Twist>, NotationError> {
+        s.split_whitespace()
+            .map(|token| self.parse_move(token))
+            .collect()
+    }
+
+    fn parse_move(&self, token: &str) -> Result<Twist, NotationError> {
+        let prefix_end = token
+            .find(|c: char| !c.is_ascii_digit())
+            .unwrap_or(token.len());
+        let (prefix, token) = token.split_at(prefix_end);
+
+        // the longest name that fits, in case one name starts with another
+        let (axis, definition) = self
+            .axes
+            .iter()
+            .enumerate()
+            .filter(|(_, axis)| token.starts_with(&axis.name))
+            .max_by_key(|(_, axis)| axis.name.len())
+            .ok_or_else(|| match token.chars().next() {
+                Some(c) if c.is_alphabetic() => NotationError::UnknownMove(c),
+                Some(c) => NotationError::UnexpectedCharacter(c),
+                None => NotationError::MissingMove,
+            })?;
+
+        let layer = if prefix.is_empty() {
+            0
+        } else {
+            let first = prefix.chars().next().unwrap_or_default();
+            match prefix.parse::<usize>() {
+                Ok(layer) if (1..=definition.cuts.len() + 1).contains(&layer) => layer - 1,
+                Ok(layer) => return Err(NotationError::NoSuchLayer(layer)),
+                Err(_) => return Err(NotationError::UnexpectedCharacter(first)),
+            }
+        };
+
+        let modifiers = &token[definition.name.len()..];
+        let digits_end = modifiers
+            .find(|c: char| !c.is_ascii_digit())
+            .unwrap_or(modifiers.len());
+        let (digits, prime) = modifiers.split_at(digits_end);
+        let steps = if digits.is_empty() {
+            1
+        } else {
+            let first = digits.chars().next().unwrap_or_default();
+            digits
+                .parse::<i32>()
+                .map_err(|_| NotationError::UnexpectedCharacter(first))?
+        };
+        let turns = match prime {
+            "" => steps,
+            "'" | "’" => -steps,
+            _ => {
+                let unexpected = prime.chars().next().unwrap_or_default();
+                return Err(NotationError::UnexpectedCharacter(unexpected));
+            }
+        };
+
+        Ok(Twist::new(axis, layer..=layer, turns, definition.order))
+    }
+
+    /// A random scramble of `scramble_length` moves, never turning the same axis twice in a row.
+    /// Any layer but the innermost can be turned, since turning that is the same as turning the
+    /// rest of the puzzle the other way.
+    pub fn scramble(&self) -> Vec<Twist> {
+        let mut rng = rand::rng();
+        let mut twists: Vec<Twist> = Vec::new();
+        if self.axes.is_empty() {
+            return twists;
+        }
+
+        while twists.len() < self.scramble_length {
+            let axis = rng.random_range(0..self.axes.len());
+            let (order, layers) = (self.axes[axis].order, self.axes[axis].cuts.len().max(1));
+            if order > 1 && twists.last().is_none_or(|last| last.axis() != axis) {
+                let turns = rng.random_range(1..order) as i32;
+                let layer = rng.random_range(0..layers);
+                twists.push(Twist::new(axis, layer..=layer, turns, order));
+            }
+        }
+
+        twists
+    }
+}
+
+/// The custom puzzle file, which starts loading when the app starts.
+#[derive(Resource)]
+pub struct CustomPuzzleHandle(pub Handle<PuzzleFile>);
+
+/// Spawn the custom puzzle in place of the cube once it has loaded, if it asks to be the puzzle
+/// the app starts with.
+pub fn spawn_startup_puzzle(
+    mut events: EventReader<AssetEvent<PuzzleFile>>,
+    handle: Res<CustomPuzzleHandle>,
+    files: Res<Assets<PuzzleFile>>,
+    cube_size: Res<CubeSize>,
+    mut puzzle: ResMut<Puzzle>,
+    mut camera: Single<&mut OrbitCamera>,
+) {
+    for event in events.read() {
+        if !event.is_loaded_with_dependencies(&handle.0) {
+            continue;
+        }
+        if let Some(file) = files.get(&handle.0).filter(|file| file.startup) {
+            *puzzle = Puzzle::Custom(CustomPuzzle(Arc::new(file.clone())));
+            camera.set(puzzle_camera_start_position(&puzzle, &cube_size));
+        }
+    }
+}
+
+/// A loaded custom puzzle, shared rather than copied each time the puzzle is cloned.
+#[derive(Clone, Debug, PartialEq)]
+pub struct CustomPuzzle(pub Arc<PuzzleFile>);
+
+/// Why a puzzle file could not be loaded.
+#[derive(Debug)]
+pub enum PuzzleFileError {
+    Io(std::io::Error),
+    Ron(ron::error::SpannedError),
+    /// An axis, by name, that takes fewer than two steps to go all the way round.
+    NoTurns(String),
+    /// An axis or a face of a solid with a normal that doesn't point anywhere.
+    ZeroNormal(String),
+    /// A face, by its place in the list of faces, with no area.
+    DegenerateFace(usize),
+    /// An axis, by name, with an order that turns stickers to places where there are none.
+    MisfitTurn(String),
+}
+
+impl fmt::Display for PuzzleFileError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Io(error) => write!(f, "couldn't read puzzle file: {error}"),
+            Self::Ron(error) => write!(f, "couldn't parse puzzle file: {error}"),
+            Self::NoTurns(axis) => write!(f, "axis {axis} needs an order of at least 2"),
+            Self::ZeroNormal(name) => write!(f, "{name} has a zero normal"),
+            Self::DegenerateFace(face) => write!(f, "face {face} has no area"),
+            Self::MisfitTurn(axis) => {
+                write!(
+                    f,
+                    "turns around axis {axis} don't move stickers onto stickers"
+                )
+            }
+        }
+    }
+}
+
+impl std::error::Error for PuzzleFileError {}
+
+/// Loads `.puzzle.ron` files as `PuzzleFile`s.
+#[derive(Default)]
+pub struct PuzzleFileLoader;
+
+impl AssetLoader for PuzzleFileLoader {
+    type Asset = PuzzleFile;
+    type Settings = ();
+    type Error = PuzzleFileError;
+
+    async fn load(
+        &self,
+        reader: &mut dyn Reader,
+        _settings: &(),
+        _load_context: &mut LoadContext<'_>,
+    ) -> Result<PuzzleFile, PuzzleFileError> {
+        let mut bytes = Vec::new();
+        reader
+            .read_to_end(&mut bytes)
+            .await
+            .map_err(PuzzleFileError::Io)?;
+        let puzzle: PuzzleFile = ron::de::from_bytes(&bytes).map_err(PuzzleFileError::Ron)?;
+        puzzle.check()?;
+        Ok(puzzle)
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["puzzle.ron"]
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::puzzle::{PuzzleState, pieces};
+
+    fn example() -> PuzzleFile {
+        ron::from_str(include_str!("../assets/puzzles/custom.puzzle.ron")).unwrap()
+    }
+
+    #[test]
+    fn test_example_puzzle() {
+        let puzzle = example();
+        assert_eq!(puzzle.name, "Dino cube");
+
+        // every piece is an edge, with a sticker on each face either side of it
+        let pieces = pieces(&puzzle.definition());
+        assert_eq!(pieces.len(), 12);
+        assert!(pieces.iter().all(|piece| piece.len() == 2));
+    }
+
+    #[test]
+    fn test_broken_puzzles_are_rejected() {
+        assert!(example().check().is_ok());
+
+        let mut puzzle = example();
+        puzzle.axes[2].order = 0;
+        assert!(matches!(puzzle.check(), Err(PuzzleFileError::NoTurns(axis)) if axis == "UBR"));
+
+        let mut puzzle = example();
+        puzzle.axes[0].normal = [0.0; 3];
+        assert!(matches!(
+            puzzle.check(),
+            Err(PuzzleFileError::ZeroNormal(_))
+        ));
+
+        // a fifth of a turn around a corner of a cube moves its stickers off the cube
+        let mut puzzle = example();
+        puzzle.axes[0].order = 5;
+        assert!(matches!(puzzle.check(), Err(PuzzleFileError::MisfitTurn(axis)) if axis == "UFR"));
+
+        // without a bottom the solid isn't closed, so the faces around it have no bottom corners
+        let mut puzzle = example();
+        if let Shape::Solid { faces, .. } = &mut puzzle.shape {
+            faces.remove(1);
+        }
+        assert!(matches!(
+            puzzle.check(),
+            Err(PuzzleFileError::DegenerateFace(_))
+        ));
+
+        let mut puzzle = example();
+        puzzle.shape = Shape::Polygons(vec![PolygonFace {
+            vertices: vec![[0.0; 3], [1.0, 0.0, 0.0], [2.0, 0.0, 0.0]],
+            color: [0; 3],
+        }]);
+        assert!(matches!(
+            puzzle.check(),
+            Err(PuzzleFileError::DegenerateFace(0))
+        ));
+    }
+
+    #[test]
+    fn test_notation() {
+        let puzzle = example();
+        assert_eq!(
+            puzzle.parse("UFR DBL2' UBL'").unwrap(),
+            [
+                Twist::new(0, 0..=0, 1, 3),
+                Twist::new(7, 0..=0, -2, 3),
+                Twist::new(3, 0..=0, -1, 3),
+            ]
+        );
+        assert_eq!(puzzle.parse("R"), Err(NotationError::UnknownMove('R')));
+        assert_eq!(
+            puzzle.parse("UFR+"),
+            Err(NotationError::UnexpectedCharacter('+'))
+        );
+        assert_eq!(
+            puzzle.parse("2UFR'").unwrap(),
+            [Twist::new(0, 1..=1, -1, 3)]
+        );
+        assert_eq!(puzzle.parse("3UFR"), Err(NotationError::NoSuchLayer(3)));
+
+        let mut state = PuzzleState::solved(&puzzle.definition());
+        let scramble = puzzle.scramble();
+        assert_eq!(scramble.len(), 25);
+        for twist in &scramble {
+            state.apply(twist);
+        }
+        assert!(!state.is_solved());
+        for twist in scramble.iter().rev() {
+            state.apply(&twist.inverse());
+        }
+        assert!(state.is_solved());
+    }
+}
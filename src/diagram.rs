@@ -0,0 +1,398 @@
+use std::io::Cursor;
+
+use bevy::{
+    asset::RenderAssetUsages,
+    image::ImageFormat,
+    prelude::*,
+    render::render_resource::{Extent3d, TextureDimension, TextureFormat},
+};
+
+use crate::{
+    cube::{CubeSize, Face},
+    net::{StickerId, face_origin, net_axes, net_stickers},
+    state::CubeState,
+    theme::ColorScheme,
+};
+
+/// How big the space around a diagram is, and how far stickers are inset from their cell, in
+/// stickers.
+const MARGIN: f32 = 0.2;
+const STICKER_INSET: f32 = 0.06;
+
+/// How many pixels across each sticker is in an exported PNG.
+const PIXELS_PER_STICKER: f32 = 40.0;
+
+const DIAGRAM_BODY_COLOR: Color = Color::srgb(0.05, 0.05, 0.05);
+const MASKED_STICKER_COLOR: Color = Color::srgb(0.45, 0.45, 0.45);
+
+/// How the cube is drawn.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum DiagramView {
+    /// Unfolded flat, showing every face.
+    Net,
+    /// Seen from above the front right corner, showing the top, front and right faces.
+    Isometric,
+}
+
+/// Which stickers are grayed out, so a diagram only shows what matters for a step of a solve.
+/// Stickers are picked by the piece they're on and the face they started on, so a mask follows
+/// the pieces however the cube is turned.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Resource)]
+pub enum DiagramMask {
+    #[default]
+    None,
+    /// Only the bottom layers' pieces, for the first two layers.
+    F2l,
+    /// Only stickers of the top face's color, for orienting the last layer.
+    Oll,
+    /// Only the top layer's pieces, for permuting the last layer.
+    Pll,
+}
+
+impl DiagramMask {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::None => "none",
+            Self::F2l => "F2L",
+            Self::Oll => "OLL",
+            Self::Pll => "PLL",
+        }
+    }
+
+    pub fn next(&self) -> Self {
+        match self {
+            Self::None => Self::F2l,
+            Self::F2l => Self::Oll,
+            Self::Oll => Self::Pll,
+            Self::Pll => Self::None,
+        }
+    }
+
+    /// Whether the sticker keeps its color rather than being grayed out.
+    fn shows(&self, sticker: &StickerId, size: &CubeSize) -> bool {
+        let in_top_layer = sticker.home.y == size.outer().y;
+        match self {
+            Self::None => true,
+            Self::F2l => !in_top_layer,
+            Self::Oll => sticker.face == Face::Top,
+            Self::Pll => in_top_layer,
+        }
+    }
+}
+
+/// A flat picture of the cube, made of filled polygons drawn in order.
+#[derive(Clone, Debug)]
+pub struct Diagram {
+    polygons: Vec<(Vec<Vec2>, Color)>,
+    size: Vec2,
+}
+
+impl Diagram {
+    /// Draw the cube in `state` with its stickers colored by `scheme`, graying out those the
+    /// mask hides. Positions are in stickers, with y going down.
+    pub fn new(
+        state: &CubeState,
+        view: DiagramView,
+        scheme: &ColorScheme,
+        mask: DiagramMask,
+    ) -> Self {
+        let size = state.size();
+        let color = |sticker: &StickerId| {
+            if mask.shows(sticker, &size) {
+                scheme.color(&sticker.face)
+            } else {
+                MASKED_STICKER_COLOR
+            }
+        };
+
+        let polygons = match view {
+            DiagramView::Net => {
+                let mut polygons: Vec<_> = Face::variants()
+                    .iter()
+                    .map(|face| {
+                        let (right, down) = net_axes(face);
+                        let min = face_origin(face, &size).as_vec2();
+                        let max =
+                            min + Vec2::new(size.layers(right) as f32, size.layers(down) as f32);
+                        (rectangle(min, max), DIAGRAM_BODY_COLOR)
+                    })
+                    .collect();
+                polygons.extend(net_stickers(state).iter().map(|(cell, sticker)| {
+                    let min = cell.as_vec2() + STICKER_INSET;
+                    let max = cell.as_vec2() + 1.0 - STICKER_INSET;
+                    (rectangle(min, max), color(sticker))
+                }));
+                polygons
+            }
+            DiagramView::Isometric => isometric_polygons(state, color),
+        };
+
+        // move everything so it starts just inside the margin
+        let min = polygons
+            .iter()
+            .flat_map(|(points, _)| points)
+            .fold(Vec2::INFINITY, |min, point| min.min(*point));
+        let max = polygons
+            .iter()
+            .flat_map(|(points, _)| points)
+            .fold(Vec2::NEG_INFINITY, |max, point| max.max(*point));
+        let polygons = polygons
+            .into_iter()
+            .map(|(points, color)| {
+                let points = points.iter().map(|point| *point - min + MARGIN).collect();
+                (points, color)
+            })
+            .collect();
+
+        Self {
+            polygons,
+            size: max - min + 2.0 * MARGIN,
+        }
+    }
+
+    /// The diagram as an SVG document, scaled so each sticker is `scale` units across.
+    pub fn to_svg(&self, scale: f32) -> String {
+        let size = self.size * scale;
+        let mut svg = format!(
+            r#"<svg xmlns="http://www.w3.org/2000/svg" width="{}" height="{}" viewBox="0 0 {} {}">"#,
+            size.x, size.y, size.x, size.y
+        );
+        svg.push('\n');
+        for (points, color) in &self.polygons {
+            let points: Vec<_> = points
+                .iter()
+                .map(|point| format!("{},{}", point.x * scale, point.y * scale))
+                .collect();
+            svg.push_str(&format!(
+                "  <polygon points=\"{}\" fill=\"{}\"/>\n",
+                points.join(" "),
+                color.to_srgba().to_hex()
+            ));
+        }
+        svg.push_str("</svg>\n");
+        svg
+    }
+
+    /// The diagram drawn into an image with a transparent background, scaled so each sticker is
+    /// `scale` pixels across. Edges are smoothed by sampling each pixel four times.
+    pub fn to_image(&self, scale: f32) -> Image {
+        let width = (self.size.x * scale).ceil() as usize;
+        let height = (self.size.y * scale).ceil() as usize;
+        let mut pixels = vec![Vec4::ZERO; width * height];
+        let samples = [0.25, 0.75].map(|y| [0.25, 0.75].map(|x| Vec2::new(x, y)));
+
+        for (points, color) in &self.polygons {
+            let points: Vec<_> = points.iter().map(|point| *point * scale).collect();
+            let color = Vec4::from_array(color.to_srgba().to_f32_array());
+            let min = points
+                .iter()
+                .fold(Vec2::INFINITY, |min, point| min.min(*point));
+            let max = points
+                .iter()
+                .fold(Vec2::NEG_INFINITY, |max, point| max.max(*point));
+            let [min_x, min_y] = min.floor().max(Vec2::ZERO).as_uvec2().to_array();
+            let max_x = (max.x.ceil() as usize).min(width);
+            let max_y = (max.y.ceil() as usize).min(height);
+
+            for y in min_y as usize..max_y {
+                for x in min_x as usize..max_x {
+                    let pixel = Vec2::new(x as f32, y as f32);
+                    let inside = samples
+                        .as_flattened()
+                        .iter()
+                        .filter(|sample| is_inside(&points, pixel + **sample))
+                        .count();
+                    let coverage = inside as f32 / 4.0;
+                    let pixel = &mut pixels[y * width + x];
+                    *pixel = color * coverage + *pixel * (1.0 - coverage);
+                }
+            }
+        }
+
+        let data = pixels
+            .iter()
+            .flat_map(|pixel| {
+                pixel
+                    .to_array()
+                    .map(|channel| (channel * 255.0).round() as u8)
+            })
+            .collect();
+        Image::new(
+            Extent3d {
+                width: width as u32,
+                height: height as u32,
+                depth_or_array_layers: 1,
+            },
+            TextureDimension::D2,
+            data,
+            TextureFormat::Rgba8UnormSrgb,
+            RenderAssetUsages::default(),
+        )
+    }
+
+    /// The diagram encoded as a PNG, scaled like `to_image`.
+    pub fn to_png(&self, scale: f32) -> Result<Vec<u8>, String> {
+        let format = ImageFormat::Png
+            .as_image_crate_format()
+            .ok_or("PNG images aren't supported")?;
+        let mut png = Vec::new();
+        self.to_image(scale)
+            .try_into_dynamic()
+            .map_err(|error| error.to_string())?
+            .write_to(&mut Cursor::new(&mut png), format)
+            .map_err(|error| error.to_string())?;
+        Ok(png)
+    }
+}
+
+/// Diagrams of the cube in `state` both unfolded and from the corner, as SVGs and PNGs, each
+/// with a file name such as `net.svg`. They're left to the caller to save, since the web build
+/// has no files to write to.
+pub fn export_diagrams(
+    state: &CubeState,
+    scheme: &ColorScheme,
+    mask: DiagramMask,
+) -> Result<Vec<(String, Vec<u8>)>, String> {
+    let mut files = Vec::new();
+    for (view, name) in [
+        (DiagramView::Net, "net"),
+        (DiagramView::Isometric, "isometric"),
+    ] {
+        let diagram = Diagram::new(state, view, scheme, mask);
+        files.push((
+            format!("{name}.svg"),
+            diagram.to_svg(PIXELS_PER_STICKER).into_bytes(),
+        ));
+        files.push((format!("{name}.png"), diagram.to_png(PIXELS_PER_STICKER)?));
+    }
+    Ok(files)
+}
+
+/// The corners of a rectangle, going around it.
+fn rectangle(min: Vec2, max: Vec2) -> Vec<Vec2> {
+    vec![min, Vec2::new(max.x, min.y), max, Vec2::new(min.x, max.y)]
+}
+
+/// Whether `point` is inside the convex polygon with the given corners, going around it either
+/// way.
+fn is_inside(corners: &[Vec2], point: Vec2) -> bool {
+    let sides = corners.iter().zip(corners.iter().cycle().skip(1));
+    let turns: Vec<_> = sides
+        .map(|(start, end)| (*end - *start).perp_dot(point - *start))
+        .collect();
+    turns.iter().all(|turn| *turn >= 0.0) || turns.iter().all(|turn| *turn <= 0.0)
+}
+
+/// The top, front and right faces of the cube seen from above its front right corner, each
+/// face's body followed by its stickers.
+fn isometric_polygons(
+    state: &CubeState,
+    color: impl Fn(&StickerId) -> Color,
+) -> Vec<(Vec<Vec2>, Color)> {
+    // looking down the diagonal from the front right top corner, with y going down the screen
+    let right = Vec3::new(1.0, 0.0, -1.0).normalize();
+    let up = Vec3::new(-1.0, 2.0, -1.0).normalize();
+    let project = |point: Vec3| Vec2::new(point.dot(right), -point.dot(up));
+
+    let size = state.size();
+    let half_size = size.half_extent() + 0.5;
+    let square = |centre: Vec3, normal: Vec3, half: Vec3| {
+        let across = normal.yzx();
+        let along = normal.cross(across);
+        let (across, along) = (
+            across * half.dot(across.abs()),
+            along * half.dot(along.abs()),
+        );
+        [
+            -across - along,
+            across - along,
+            across + along,
+            along - across,
+        ]
+        .map(|corner| project(centre + corner))
+        .to_vec()
+    };
+
+    let mut polygons = Vec::new();
+    for face in [Face::Top, Face::Front, Face::Right] {
+        let normal = face.normal();
+        polygons.push((
+            square(normal * half_size, normal, half_size),
+            DIAGRAM_BODY_COLOR,
+        ));
+
+        for piece in state.pieces() {
+            for home_normal in piece.sticker_normals() {
+                if piece.orientation().apply(*home_normal) != normal.as_ivec3() {
+                    continue;
+                }
+                let Some(start) = Face::variants()
+                    .into_iter()
+                    .find(|start| start.normal().as_ivec3() == *home_normal)
+                else {
+                    continue;
+                };
+                let sticker = StickerId {
+                    home: piece.home(),
+                    face: start,
+                };
+                let centre = piece.position().as_vec3() / 2.0 + normal * 0.5;
+                polygons.push((
+                    square(centre, normal, Vec3::splat(0.5 - STICKER_INSET)),
+                    color(&sticker),
+                ));
+            }
+        }
+    }
+    polygons
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_diagrams_show_masked_stickers() {
+        let mut state = CubeState::default();
+        state.apply_algorithm(&"R U R' U'".parse().unwrap());
+        let scheme = ColorScheme::Western;
+        let gray = MASKED_STICKER_COLOR.to_srgba().to_hex();
+
+        let net = Diagram::new(&state, DiagramView::Net, &scheme, DiagramMask::None).to_svg(10.0);
+        assert_eq!(net.matches("<polygon").count(), 6 + 54);
+        assert!(!net.contains(&gray));
+
+        // only the 9 stickers of the top color keep their color
+        let oll = Diagram::new(&state, DiagramView::Net, &scheme, DiagramMask::Oll).to_svg(10.0);
+        assert_eq!(oll.matches(&gray).count(), 45);
+
+        let isometric = Diagram::new(&state, DiagramView::Isometric, &scheme, DiagramMask::None);
+        assert_eq!(isometric.polygons.len(), 3 + 27);
+
+        // the middle of the solved front centre is drawn in the front's color
+        let solved = Diagram::new(
+            &CubeState::default(),
+            DiagramView::Net,
+            &scheme,
+            DiagramMask::None,
+        );
+        let image = solved.to_image(10.0);
+        let centre =
+            (face_origin(&Face::Front, &CubeSize::default()).as_vec2() + 1.5 + MARGIN) * 10.0;
+        let color = image
+            .get_color_at(centre.x as u32, centre.y as u32)
+            .unwrap();
+        assert_eq!(
+            color.to_srgba().to_hex(),
+            scheme.color(&Face::Front).to_srgba().to_hex()
+        );
+
+        let files = export_diagrams(&state, &scheme, DiagramMask::None).unwrap();
+        let names: Vec<_> = files.iter().map(|(name, _)| name.as_str()).collect();
+        assert_eq!(
+            names,
+            ["net.svg", "net.png", "isometric.svg", "isometric.png"]
+        );
+        assert!(files[1].1.starts_with(b"\x89PNG"));
+    }
+}
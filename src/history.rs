@@ -0,0 +1,244 @@
+use bevy::prelude::*;
+
+use crate::{
+    PlayMode,
+    cube::{CubeSize, VoidCube},
+    cubie::{Cubie, translation},
+    puzzle::Puzzle,
+    rotation::{Rotation, RotationCompleted, Rotations},
+    shape::ShapeMod,
+    state::CubeState,
+};
+
+/// Every move applied to the cube since it was solved, along with where in those moves the cube
+/// currently is. Moving the cube from part way along the timeline starts a new branch so the
+/// moves after that point are kept.
+#[derive(Debug, Resource)]
+pub struct Timeline {
+    size: CubeSize,
+    /// Each branch holds all of its moves from the solved cube, including any it shares with the
+    /// branch it came from.
+    branches: Vec<Vec<Rotation>>,
+    /// When each move in each branch was made, in seconds since the app started.
+    times: Vec<Vec<f64>>,
+    branch: usize,
+    /// The number of moves in the current branch that have been applied to the cube.
+    position: usize,
+    /// The position the current attempt started from. Moves before this are the scramble.
+    attempt_start: usize,
+}
+
+impl Timeline {
+    /// An empty timeline for a solved cube of the given size.
+    pub fn new(size: CubeSize) -> Self {
+        Self {
+            size,
+            branches: vec![Vec::new()],
+            times: vec![Vec::new()],
+            branch: 0,
+            position: 0,
+            attempt_start: 0,
+        }
+    }
+
+    pub fn size(&self) -> CubeSize {
+        self.size
+    }
+
+    pub fn moves(&self) -> &[Rotation] {
+        &self.branches[self.branch]
+    }
+
+    /// The moves that have been applied to get to the cube's current state.
+    pub fn applied(&self) -> &[Rotation] {
+        &self.moves()[..self.position]
+    }
+
+    pub fn position(&self) -> usize {
+        self.position
+    }
+
+    pub fn branch(&self) -> usize {
+        self.branch
+    }
+
+    pub fn branch_count(&self) -> usize {
+        self.branches.len()
+    }
+
+    /// When each move in the current branch was made.
+    pub fn times(&self) -> &[f64] {
+        &self.times[self.branch]
+    }
+
+    /// Record a rotation that has just been applied to the cube at `time`.
+    pub fn record(&mut self, rotation: Rotation, time: f64) {
+        let position = self.position;
+        let branch = &mut self.branches[self.branch];
+        let times = &mut self.times[self.branch];
+
+        if branch.get(position) == Some(&rotation) {
+            // the cube is replaying this branch, no need to change anything
+        } else if position == branch.len() {
+            branch.push(rotation);
+            times.push(time);
+        } else {
+            let mut moves = branch[..position].to_vec();
+            moves.push(rotation);
+            let mut move_times = times[..position].to_vec();
+            move_times.push(time);
+
+            self.branches.push(moves);
+            self.times.push(move_times);
+            self.branch = self.branches.len() - 1;
+        }
+
+        self.position += 1;
+    }
+
+    /// Start a new attempt from the current position, treating every move so far as the scramble.
+    pub fn start_attempt(&mut self) {
+        self.attempt_start = self.position;
+    }
+
+    /// The moves that scrambled the cube for the current attempt.
+    pub fn scramble(&self) -> &[Rotation] {
+        &self.moves()[..self.attempt_start.min(self.position)]
+    }
+
+    /// The moves applied since the current attempt started, along with when they were made.
+    pub fn attempt(&self) -> (&[Rotation], &[f64]) {
+        let start = self.attempt_start.min(self.position);
+        (
+            &self.moves()[start..self.position],
+            &self.times()[start..self.position],
+        )
+    }
+
+    /// Move to a point along the current branch, clamped to its length.
+    pub fn jump(&mut self, position: usize) {
+        self.position = position.min(self.moves().len());
+    }
+
+    /// Switch to another branch, keeping the same position along it where possible.
+    pub fn switch_branch(&mut self, branch: usize) {
+        self.branch = branch.min(self.branches.len() - 1);
+        self.jump(self.position);
+    }
+
+    /// The state of the cube at the current position.
+    pub fn state(&self) -> CubeState {
+        let mut state = CubeState::solved(self.size);
+        for rotation in self.applied() {
+            state.apply(rotation);
+        }
+        state
+    }
+}
+
+impl Default for Timeline {
+    fn default() -> Self {
+        Self::new(CubeSize::default())
+    }
+}
+
+/// Sent when the timeline jumps somewhere and the cube needs to be updated to match.
+#[derive(Event)]
+pub struct TimelineJumped;
+
+/// Add each completed rotation to the timeline.
+pub fn record_rotations(
+    time: Res<Time>,
+    mut completed: EventReader<RotationCompleted>,
+    mut timeline: ResMut<Timeline>,
+) {
+    for RotationCompleted(rotation) in completed.read() {
+        timeline.record(rotation.clone(), time.elapsed_secs_f64());
+    }
+}
+
+/// Start a new timeline whenever the cube is replaced with one of a different size, a void cube
+/// or a cube with centres, a different shape mod, or with another puzzle.
+pub fn reset_timeline(
+    cube_size: Res<CubeSize>,
+    void_cube: Res<VoidCube>,
+    shape_mod: Res<ShapeMod>,
+    puzzle: Res<Puzzle>,
+    mut timeline: ResMut<Timeline>,
+) {
+    let cube_changed = cube_size.is_changed() || void_cube.is_changed() || shape_mod.is_changed();
+    if (cube_changed || puzzle.is_changed()) && !cube_size.is_added() {
+        *timeline = Timeline::new(*cube_size);
+    }
+}
+
+/// Start a new attempt once shuffling has stopped and the last shuffle move has finished.
+pub fn start_attempt_after_shuffle(
+    play_mode: Res<PlayMode>,
+    rotations: Res<Rotations>,
+    mut timeline: ResMut<Timeline>,
+    mut was_shuffling: Local<bool>,
+) {
+    if matches!(*play_mode, PlayMode::Shuffle) {
+        *was_shuffling = true;
+    } else if *was_shuffling && rotations.is_idle() {
+        timeline.start_attempt();
+        *was_shuffling = false;
+    }
+}
+
+/// Instantly put every cubie where it is at the timeline's current position.
+pub fn sync_cubies_to_timeline(
+    mut jumped: EventReader<TimelineJumped>,
+    timeline: Res<Timeline>,
+    mut rotations: ResMut<Rotations>,
+    mut cubies: Query<(&Cubie, &mut Transform)>,
+) {
+    if jumped.read().last().is_none() {
+        return;
+    }
+
+    // anything still animating would be applied on top of the new state
+    rotations.clear();
+
+    let state = timeline.state();
+    for (cubie, mut transform) in &mut cubies {
+        if let Some(piece) = state.piece(cubie.home) {
+            transform.translation = translation(piece.position());
+            transform.rotation = piece.orientation().to_quat();
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::algorithm::Algorithm;
+
+    #[test]
+    fn test_moving_mid_timeline_branches() {
+        let mut timeline = Timeline::default();
+        let moves = "R U F".parse::<Algorithm>().unwrap();
+        for rotation in moves.rotations() {
+            timeline.record(rotation.clone(), 0.0);
+        }
+
+        // replaying the same move keeps the branch
+        timeline.jump(1);
+        timeline.record(moves.rotations()[1].clone(), 1.0);
+        assert_eq!((timeline.branch_count(), timeline.position()), (1, 2));
+
+        // a different move starts a new branch with the shared start
+        timeline.jump(1);
+        timeline.record(moves.rotations()[2].clone(), 2.0);
+        assert_eq!((timeline.branch_count(), timeline.branch()), (2, 1));
+        assert_eq!(
+            timeline.moves(),
+            &[moves.rotations()[0].clone(), moves.rotations()[2].clone()]
+        );
+
+        timeline.switch_branch(0);
+        assert_eq!(timeline.moves(), moves.rotations());
+        assert_eq!(timeline.position(), 2);
+    }
+}
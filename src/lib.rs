@@ -0,0 +1,416 @@
+//! A Rubik's cube simulator. The app itself is started with `run`, and diagrams of a cube can
+//! be drawn without it, see `diagram`.
+
+// #![allow(dead_code)]
+
+use std::{collections::VecDeque, sync::Arc};
+
+use bevy::{
+    prelude::*,
+    tasks::{AsyncComputeTaskPool, Task, block_on, futures_lite::future},
+};
+
+use crate::{
+    accessibility::{ColorVision, StickerSymbols, SymbolMeshes, update_sticker_symbols},
+    bandage::{BandageEditor, Bandaging, draw_bandaging, draw_gizmos_on_top, pick_bandaged_cubie},
+    camera::{FollowTurns, OrbitCamera, follow_turns, update_orbit_camera},
+    cube::{IsCubeSolved, Supercube, VoidCube, check_cube_solved},
+    cubie::{respawn_cubies, show_centre_markers, spawn_cubies},
+    custom::{
+        CUSTOM_PUZZLE_PATH, CustomPuzzleHandle, PuzzleFile, PuzzleFileLoader, spawn_startup_puzzle,
+    },
+    diagram::DiagramMask,
+    history::{
+        Timeline, TimelineJumped, record_rotations, reset_timeline, start_attempt_after_shuffle,
+        sync_cubies_to_timeline,
+    },
+    mirror::{HiddenFaceMirrors, update_mirrors},
+    model::{CubieAssets, CubieModel},
+    mouse::{MousePressed, handle_mouse_drag, handle_zoom},
+    net::HighlightedSticker,
+    pocket::{POCKET_SIZE, PocketSolver},
+    puzzle::{Puzzle, PuzzleState, Twist, apply_twists, record_twists, respawn_puzzle},
+    reduction::ReductionSolver,
+    rotation::{Rotation, RotationCompleted, RotationTimer, Rotations, apply_rotations},
+    shape::ShapeMod,
+    state::SolvedCheck,
+    theme::{StickerTexture, Themes, apply_theme, sticker_texture},
+    ui::{AlgorithmInput, SharedReconstruction, setup_ui, update_cube_solved_indicator},
+};
+
+mod accessibility;
+mod algorithm;
+mod analysis;
+mod bandage;
+mod camera;
+mod cube;
+mod cubie;
+mod custom;
+pub mod diagram;
+mod history;
+mod megaminx;
+mod mirror;
+mod model;
+mod mouse;
+mod net;
+mod pocket;
+mod puzzle;
+mod pyraminx;
+mod reconstruction;
+mod reduction;
+mod rotation;
+mod shape;
+mod skewb;
+mod state;
+mod theme;
+mod ui;
+
+pub use crate::{algorithm::Algorithm, cube::CubeSize, state::CubeState, theme::ColorScheme};
+
+/// Open the simulator in a window, or in the page's canvas on the web.
+pub fn run() {
+    App::new()
+        .add_plugins((
+            DefaultPlugins
+                .set(AssetPlugin {
+                    file_path: "assets".into(),
+                    meta_check: bevy::asset::AssetMetaCheck::Never,
+                    ..default()
+                })
+                .set(WindowPlugin {
+                    primary_window: Some(Window {
+                        canvas: Some("#bevy".into()), // use the <canvas id="bevy">
+                        fit_canvas_to_parent: true,   // fill parent size
+                        ..default()
+                    }),
+                    ..default()
+                }),
+            MeshPickingPlugin,
+        ))
+        .init_asset::<PuzzleFile>()
+        .init_asset_loader::<PuzzleFileLoader>()
+        .init_resource::<SymbolMeshes>()
+        .add_event::<RotationCompleted>()
+        .add_event::<RotationCompleted<Twist>>()
+        .add_event::<TimelineJumped>()
+        .add_systems(Startup, (setup, draw_gizmos_on_top))
+        .add_observer(pick_bandaged_cubie)
+        .add_systems(
+            Update,
+            (
+                ui::scene_button_system,
+                ui::cube_control_button_system,
+                (
+                    handle_mouse_drag,
+                    handle_zoom,
+                    ui::camera_preset_keyboard_system,
+                    follow_turns,
+                    update_orbit_camera,
+                )
+                    .chain(),
+                apply_rotations.run_if(resource_equals(Puzzle::Cube)),
+                (
+                    check_cube_solved
+                        .run_if(resource_equals(Puzzle::Cube))
+                        .after(record_rotations),
+                    show_centre_markers,
+                    apply_theme,
+                    update_sticker_symbols,
+                    update_mirrors.after(apply_rotations),
+                ),
+                update_cube_solved_indicator,
+                (
+                    handle_play_mode
+                        .after(record_rotations)
+                        .after(record_twists),
+                    ui::update_play_mode_buttons.after(handle_play_mode),
+                ),
+                ui::algorithm_input_focus_system,
+                ui::algorithm_input_keyboard_system,
+                ui::update_algorithm_panel,
+                record_rotations.after(apply_rotations),
+                sync_cubies_to_timeline,
+                ui::timeline_slider_system,
+                ui::update_timeline_panel,
+                start_attempt_after_shuffle,
+                ui::update_reconstruction_panel,
+                ui::update_rotation_rejection_text,
+                draw_bandaging.run_if(resource_equals(Puzzle::Cube)),
+                (
+                    respawn_cubies,
+                    ui::respawn_cube_controls,
+                    reset_timeline,
+                    net::respawn_net_panel,
+                    net::update_net,
+                    net::net_sticker_button_system,
+                    net::highlight_sticker,
+                ),
+                (
+                    spawn_startup_puzzle,
+                    respawn_puzzle.after(spawn_startup_puzzle),
+                    apply_twists.run_if(not(resource_equals(Puzzle::Cube))),
+                    record_twists.after(apply_twists),
+                ),
+            ),
+        )
+        .run();
+}
+
+/// Setup the scene
+fn setup(
+    mut commands: Commands,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+    mut images: ResMut<Assets<Image>>,
+    asset_server: Res<AssetServer>,
+) {
+    // spawn cube
+    let cube_size = CubeSize::default();
+    let void_cube = VoidCube::default();
+    let shape_mod = ShapeMod::default();
+    let cubie_model = CubieModel::load();
+    let cubie_assets = CubieAssets::new(&cubie_model, &mut meshes, &mut materials);
+    spawn_cubies(
+        &mut commands,
+        &mut meshes,
+        &cubie_assets,
+        &cube_size,
+        &void_cube,
+        &shape_mod,
+    );
+
+    // spawn lights
+    spawn_lights(&mut commands);
+
+    // spawn camera
+    let start = camera_start_position(&cube_size);
+    commands.spawn((Camera3d::default(), start, OrbitCamera::new(start)));
+
+    // insert resources
+    commands.insert_resource(IsCubeSolved(true));
+    commands.insert_resource(MousePressed(false));
+    commands.insert_resource(RotationTimer::new());
+    commands.insert_resource(Rotations::<Rotation>::new(None, VecDeque::new()));
+    commands.insert_resource(Rotations::<Twist>::new(None, VecDeque::new()));
+    commands.insert_resource(PlayMode::default());
+    commands.insert_resource(AlgorithmInput::default());
+    commands.insert_resource(cube_size);
+    commands.insert_resource(Timeline::new(cube_size));
+    commands.insert_resource(SharedReconstruction::default());
+    commands.insert_resource(Solvers::default());
+    commands.insert_resource(PendingMoves::default());
+    commands.insert_resource(Puzzle::default());
+    commands.insert_resource(PuzzleState::default());
+    commands.insert_resource(Bandaging::load());
+    commands.insert_resource(BandageEditor::default());
+    commands.insert_resource(Supercube::default());
+    commands.insert_resource(void_cube);
+    commands.insert_resource(shape_mod);
+    commands.insert_resource(cubie_model);
+    commands.insert_resource(cubie_assets);
+    commands.insert_resource(HighlightedSticker::default());
+    commands.insert_resource(DiagramMask::default());
+    commands.insert_resource(HiddenFaceMirrors::default());
+    commands.insert_resource(FollowTurns::default());
+    commands.insert_resource(Themes::default());
+    commands.insert_resource(StickerSymbols::default());
+    commands.insert_resource(ColorVision::default());
+    commands.insert_resource(StickerTexture(images.add(sticker_texture())));
+    commands.insert_resource(CustomPuzzleHandle(asset_server.load(CUSTOM_PUZZLE_PATH)));
+
+    // setup UI
+    setup_ui(commands, &asset_server, &cube_size);
+}
+
+/// Where the camera starts, far enough away that the whole cube fits in view.
+pub fn camera_start_position(cube_size: &CubeSize) -> Transform {
+    let distance = cube_size.largest() as f32 / 3.0;
+    Transform::from_xyz(10.0 * distance, 10.0 * distance, 20.0 * distance)
+        .looking_at(Vec3::ZERO, Vec3::Y)
+}
+
+/// Where the camera starts for the picked puzzle. Puzzles other than the cube are about as big
+/// as a 3x3x3.
+pub fn puzzle_camera_start_position(puzzle: &Puzzle, cube_size: &CubeSize) -> Transform {
+    match puzzle {
+        Puzzle::Cube => camera_start_position(cube_size),
+        _ => camera_start_position(&CubeSize::default()),
+    }
+}
+
+fn spawn_lights(commands: &mut Commands) {
+    // spawn lights
+    let light_distance = 5.;
+    commands.spawn((
+        PointLight {
+            intensity: 5_000_000.0,
+            shadows_enabled: true,
+            ..default()
+        },
+        Transform::from_xyz(light_distance, light_distance, light_distance),
+    ));
+    commands.spawn((
+        PointLight {
+            intensity: 5_000_000.0,
+            shadows_enabled: true,
+            ..default()
+        },
+        Transform::from_xyz(-light_distance, -light_distance, -light_distance),
+    ));
+}
+
+#[derive(Debug, Default, Resource)]
+pub enum PlayMode {
+    #[default]
+    None,
+    Shuffle,
+    Solve,
+}
+
+/// The cube solvers, shared with the background tasks that shuffle and solve the cube.
+#[derive(Clone, Resource)]
+pub struct Solvers {
+    pocket: Arc<PocketSolver>,
+    reduction: Arc<ReductionSolver>,
+}
+
+impl Default for Solvers {
+    fn default() -> Self {
+        Self {
+            pocket: Arc::new(PocketSolver::new()),
+            reduction: Arc::new(ReductionSolver::default()),
+        }
+    }
+}
+
+/// Moves for the cube being worked out in the background. The solvers build their tables the
+/// first time they're used, which takes too long to wait for between frames.
+#[derive(Default, Resource)]
+pub struct PendingMoves(Option<Task<Vec<Rotation>>>);
+
+impl PendingMoves {
+    pub fn is_pending(&self) -> bool {
+        self.0.is_some()
+    }
+}
+
+/// Shuffle or solve the cube. Pocket cubes are shuffled into random states and solved
+/// optimally, one whole scramble or solution at a time. Bigger cubes are solved by reduction.
+/// This runs once the last rotation has been recorded, so the solvers see the cube as it is.
+/// Scrambles and solutions are worked out in the background and queued once they're ready,
+/// unless the cube is turned or the play mode changes first. A solution is only worked out once
+/// for each state, so a cube the solver can't solve isn't tried again until it's turned.
+/// Supercubes also have their fixed centres turned back, and void cubes are solved whichever way
+/// round they end up.
+/// Other puzzles are shuffled and solved a whole scramble or solution at a time too, if they have
+/// a solver.
+#[allow(clippy::too_many_arguments)]
+fn handle_play_mode(
+    play_mode: Res<PlayMode>,
+    puzzle: Res<Puzzle>,
+    puzzle_state: Res<PuzzleState>,
+    cube_size: Res<CubeSize>,
+    supercube: Res<Supercube>,
+    void_cube: Res<VoidCube>,
+    shape_mod: Res<ShapeMod>,
+    timeline: Res<Timeline>,
+    bandaging: Res<Bandaging>,
+    solvers: Res<Solvers>,
+    mut pending: ResMut<PendingMoves>,
+    mut rotations: ResMut<Rotations>,
+    mut twists: ResMut<Rotations<Twist>>,
+    mut solve_tried: Local<bool>,
+) {
+    let cube_changed = timeline.is_changed()
+        || puzzle.is_changed()
+        || puzzle_state.is_changed()
+        || cube_size.is_changed()
+        || bandaging.is_changed()
+        || supercube.is_changed()
+        || void_cube.is_changed()
+        || shape_mod.is_changed();
+    if cube_changed || play_mode.is_changed() {
+        *solve_tried = false;
+    }
+
+    if *puzzle != Puzzle::Cube {
+        if !twists.is_idle() {
+            return;
+        }
+
+        let moves = match *play_mode {
+            PlayMode::Shuffle => puzzle.scramble(),
+            PlayMode::Solve if !*solve_tried => {
+                *solve_tried = true;
+                if puzzle_state.is_solved() {
+                    Vec::new()
+                } else {
+                    puzzle.solve(&puzzle_state).unwrap_or_default()
+                }
+            }
+            PlayMode::Solve | PlayMode::None => Vec::new(),
+        };
+        for twist in moves {
+            twists.enqueue(twist);
+        }
+        return;
+    }
+
+    // moves worked out for a cube that's since changed, or for another play mode, are dropped
+    if pending.is_pending() && (play_mode.is_changed() || cube_changed) {
+        pending.0 = None;
+    }
+    if let Some(task) = &mut pending.0 {
+        if let Some(moves) = block_on(future::poll_once(task)) {
+            pending.0 = None;
+            for rotation in moves {
+                rotations.enqueue(rotation);
+            }
+        }
+        return;
+    }
+
+    // the solvers would split bandaged blocks, so bandaged cubes are left as they are
+    let solving = matches!(*play_mode, PlayMode::Solve);
+    if !rotations.is_idle() || (solving && (*solve_tried || bandaging.is_bandaged(&cube_size))) {
+        return;
+    }
+
+    let solvers = solvers.clone();
+    let task_pool = AsyncComputeTaskPool::get();
+    let task = match (&*play_mode, *cube_size == POCKET_SIZE) {
+        (PlayMode::Shuffle, true) => {
+            task_pool.spawn(async move { solvers.pocket.random_scramble().rotations().to_vec() })
+        }
+        (PlayMode::Shuffle, false) => {
+            rotations.enqueue(Rotation::random(&cube_size));
+            return;
+        }
+        (PlayMode::Solve, true) => {
+            *solve_tried = true;
+            let state = timeline.state();
+            task_pool.spawn(async move { solvers.pocket.solve(&state).rotations().to_vec() })
+        }
+        (PlayMode::Solve, false) => {
+            *solve_tried = true;
+            let state = timeline.state();
+            let check = SolvedCheck::new(&supercube, &void_cube, &shape_mod);
+            task_pool.spawn(async move {
+                let solver = &solvers.reduction;
+                if check.is_solved(&state) {
+                    return Vec::new();
+                }
+                let solution = if check.void_cube {
+                    solver.solve_void(&state)
+                } else if check.supercube {
+                    solver.solve_supercube(&state)
+                } else {
+                    solver.solve(&state)
+                };
+                solution.map_or_else(Vec::new, |solution| solution.rotations().to_vec())
+            })
+        }
+        (PlayMode::None, _) => return,
+    };
+    pending.0 = Some(task);
+}
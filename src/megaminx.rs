@@ -0,0 +1,208 @@
+use bevy::prelude::*;
+use rand::Rng;
+
+use crate::{
+    algorithm::NotationError,
+    puzzle::{AxisDefinition, PuzzleDefinition, Twist, solid},
+};
+
+/// How far each face is from the centre, which makes the megaminx about as big as a 3x3x3.
+const INRADIUS: f32 = 2.0;
+
+/// Every face of the megaminx turns in fifths of a whole turn.
+const ORDER: u32 = 5;
+
+/// The names of the faces. The top face is surrounded by a ring of five, starting at the front
+/// and going round to the right, and the bottom face is surrounded by a ring of five sitting
+/// between them, starting below the front and right faces.
+const FACES: [&str; 12] = [
+    "U", "F", "R", "BR", "BL", "L", "DR", "DBR", "B", "DBL", "DL", "D",
+];
+
+/// WCA scrambles are 7 lines of moves, each with 10 turns alternating between `R` and `D`
+/// followed by a turn of `U`.
+const SCRAMBLE_LINES: usize = 7;
+const SCRAMBLE_LINE_LENGTH: usize = 10;
+
+/// The direction out of each face, in the same order as `FACES`.
+fn normals() -> Vec<Vec3> {
+    // neighbouring faces are this far apart, seen from the centre
+    let ring_angle = (1.0 / 5.0_f32.sqrt()).acos();
+    let ring = |azimuth: f32, up: f32| {
+        let azimuth = azimuth.to_radians();
+        let (sin, cos) = ring_angle.sin_cos();
+        Vec3::new(sin * azimuth.sin(), cos * up, sin * azimuth.cos())
+    };
+
+    let upper = (0..5).map(|i| ring(72.0 * i as f32, 1.0));
+    let lower = (0..5).map(|i| ring(36.0 + 72.0 * i as f32, -1.0));
+    std::iter::once(Vec3::Y)
+        .chain(upper)
+        .chain(lower)
+        .chain(std::iter::once(-Vec3::Y))
+        .collect()
+}
+
+fn colors() -> [Color; 12] {
+    [
+        Color::srgb_u8(255, 255, 255),
+        Color::srgb_u8(0, 160, 0),
+        Color::srgb_u8(220, 0, 0),
+        Color::srgb_u8(0, 0, 230),
+        Color::srgb_u8(255, 213, 0),
+        Color::srgb_u8(128, 0, 160),
+        Color::srgb_u8(255, 240, 180),
+        Color::srgb_u8(160, 230, 0),
+        Color::srgb_u8(255, 120, 190),
+        Color::srgb_u8(255, 120, 0),
+        Color::srgb_u8(100, 190, 255),
+        Color::srgb_u8(128, 128, 128),
+    ]
+}
+
+/// A dodecahedron with a cut below every face, turning in fifths. The cuts split every edge
+/// into thirds, so the corners and edges are about the same size along the edges.
+pub fn definition() -> PuzzleDefinition {
+    let normals = normals();
+    let faces = solid(&normals, INRADIUS, &colors());
+
+    // a cut through a face, a third of the way along its edges from a neighbour, is this far in
+    // from the neighbour's face
+    let edge = faces[0].vertices[0].distance(faces[0].vertices[1]);
+    let from_edge = edge / 3.0 * 108.0_f32.to_radians().sin();
+    let depth = INRADIUS - from_edge * normals[0].angle_between(normals[1]).sin();
+
+    // each axis is cut both below its own face and below the opposite one, so that a turn can
+    // take everything but the opposite face along with it
+    let axes = normals
+        .iter()
+        .map(|normal| AxisDefinition {
+            normal: *normal,
+            cuts: vec![depth, -depth],
+            order: ORDER,
+        })
+        .collect();
+
+    PuzzleDefinition { faces, axes }
+}
+
+/// Read megaminx notation, with a space between each move. A face name turns that face a fifth
+/// of a turn clockwise, `2` turns it two fifths and `'` turns it anticlockwise. As in WCA
+/// scrambles, `R++` and `D--` turn everything but the opposite face two fifths of a turn.
+pub fn parse(s: &str) -> Result<Vec<Twist>, NotationError> {
+    s.split_whitespace().map(parse_move).collect()
+}
+
+fn parse_move(token: &str) -> Result<Twist, NotationError> {
+    let name_end = token
+        .find(|c: char| !c.is_ascii_uppercase())
+        .unwrap_or(token.len());
+    let (name, modifiers) = token.split_at(name_end);
+
+    let axis = FACES.iter().position(|face| *face == name).ok_or_else(|| {
+        match name.chars().next().or(modifiers.chars().next()) {
+            Some(c) if c.is_alphabetic() => NotationError::UnknownMove(c),
+            Some(c) => NotationError::UnexpectedCharacter(c),
+            None => NotationError::MissingMove,
+        }
+    })?;
+
+    let (layers, turns) = match modifiers {
+        "" => (0..=0, 1),
+        "'" | "’" => (0..=0, -1),
+        "2" => (0..=0, 2),
+        "2'" | "2’" => (0..=0, -2),
+        "++" => (0..=1, 2),
+        "--" => (0..=1, -2),
+        _ => {
+            let unexpected = modifiers.chars().next().unwrap_or_default();
+            return Err(NotationError::UnexpectedCharacter(unexpected));
+        }
+    };
+
+    Ok(Twist::new(axis, layers, turns, ORDER))
+}
+
+/// A random scramble in the WCA's Pochmann style, turning `R` and `D` in either direction and
+/// finishing each line with a turn of `U`.
+pub fn scramble() -> Vec<Twist> {
+    let mut rng = rand::rng();
+    let mut moves = Vec::new();
+    for _ in 0..SCRAMBLE_LINES {
+        for i in 0..SCRAMBLE_LINE_LENGTH {
+            let face = if i % 2 == 0 { "R" } else { "D" };
+            let direction = if rng.random() { "++" } else { "--" };
+            moves.push(format!("{face}{direction}"));
+        }
+        moves.push(if rng.random() { "U" } else { "U'" }.to_string());
+    }
+
+    parse(&moves.join(" ")).expect("scrambles are written in valid notation")
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::puzzle::{PuzzleState, pieces};
+
+    #[test]
+    fn test_pieces() {
+        let pieces = pieces(&definition());
+        let sticker_counts = [1, 2, 3].map(|stickers| {
+            pieces
+                .iter()
+                .filter(|piece| piece.len() == stickers)
+                .count()
+        });
+
+        // centres, edges and corners
+        assert_eq!(sticker_counts, [12, 30, 20]);
+        assert_eq!(pieces.len(), 62);
+    }
+
+    #[test]
+    fn test_notation() {
+        assert_eq!(
+            parse("R++ D-- U' F2").unwrap(),
+            [
+                Twist::new(2, 0..=1, 2, ORDER),
+                Twist::new(11, 0..=1, -2, ORDER),
+                Twist::new(0, 0..=0, -1, ORDER),
+                Twist::new(1, 0..=0, 2, ORDER),
+            ]
+        );
+        assert_eq!(parse("DBL DX"), Err(NotationError::UnknownMove('D')));
+        assert_eq!(parse("R+"), Err(NotationError::UnexpectedCharacter('+')));
+    }
+
+    #[test]
+    fn test_solved_check() {
+        let mut state = PuzzleState::solved(&definition());
+        for i in 1..=5 {
+            state.apply(&parse("U").unwrap()[0]);
+            assert_eq!(state.is_solved(), i == 5);
+        }
+
+        // everything but the opposite face turns, so the puzzle isn't solved until it lines up
+        state.apply(&parse("R++").unwrap()[0]);
+        assert!(!state.is_solved());
+        for _ in 0..4 {
+            state.apply(&parse("R++").unwrap()[0]);
+        }
+        assert!(state.is_solved());
+
+        // four more turns of each twist undo it
+        let scramble = scramble();
+        assert_eq!(scramble.len(), 77);
+        for twist in &scramble {
+            state.apply(twist);
+        }
+        assert!(!state.is_solved());
+        for twist in scramble.iter().rev() {
+            for _ in 0..4 {
+                state.apply(twist);
+            }
+        }
+        assert!(state.is_solved());
+    }
+}
@@ -0,0 +1,140 @@
+use bevy::prelude::*;
+
+use crate::{
+    cube::{CubeSize, Face},
+    cubie::{Cubie, CubieFace},
+    model::CubieAssets,
+};
+
+/// Whether the faces the camera can't see are shown reflected in mirrors behind the cube.
+#[derive(Debug, Default, PartialEq, Resource)]
+pub struct HiddenFaceMirrors(pub bool);
+
+/// The reflection of a sticker, showing it in whichever mirror it faces.
+#[derive(Component)]
+pub struct StickerMirror(Entity);
+
+/// The directions of the mirrors from the middle of the cube, behind the faces hidden from the
+/// camera: the left, bottom and back.
+const MIRROR_DIRECTIONS: [Vec3; 3] = [Vec3::NEG_X, Vec3::NEG_Y, Vec3::NEG_Z];
+
+/// How far a mirror is from the middle of the cube along `direction`, in cubies.
+fn mirror_distance(direction: Vec3, cube_size: &CubeSize) -> f32 {
+    let half_size = cube_size.half_extent().dot(direction.abs()) + 0.5;
+    half_size + 1.0 + cube_size.largest() as f32 / 2.0
+}
+
+/// Where a sticker at `transform` is seen in the mirror it faces, or `None` if it's facing none
+/// of them or isn't on the outside of the cube.
+fn reflection(transform: &Transform, cube_size: &CubeSize) -> Option<Transform> {
+    let normal = transform.rotation * Vec3::Z;
+    let direction = MIRROR_DIRECTIONS
+        .into_iter()
+        .max_by(|a, b| normal.dot(*a).total_cmp(&normal.dot(*b)))?;
+    // stickers inside the cube are no further out than the middle of the outer cubies
+    let outside =
+        transform.translation.dot(direction) > cube_size.half_extent().dot(direction.abs()) + 0.25;
+    if normal.dot(direction) < 0.5 || !outside {
+        return None;
+    }
+
+    let reflect = |v: Vec3| v - 2.0 * v.dot(direction) * direction;
+    let distance = mirror_distance(direction, cube_size);
+    let translation =
+        transform.translation + 2.0 * (distance - transform.translation.dot(direction)) * direction;
+    // a reflection turns the sticker inside out, so flip it across its own x axis too, which
+    // stickers look the same after
+    let rotation = Quat::from_mat3(&Mat3::from_cols(
+        -reflect(transform.rotation * Vec3::X),
+        reflect(transform.rotation * Vec3::Y),
+        reflect(normal),
+    ));
+
+    Some(Transform {
+        translation,
+        rotation,
+        scale: transform.scale,
+    })
+}
+
+/// Give each new sticker on the outside of the cube a reflection, and move every reflection to
+/// where its sticker is seen in the mirrors, following the stickers as the cube turns.
+#[allow(clippy::type_complexity, clippy::too_many_arguments)]
+pub fn update_mirrors(
+    mut commands: Commands,
+    mirrors_shown: Res<HiddenFaceMirrors>,
+    cube_size: Res<CubeSize>,
+    cubie_assets: Res<CubieAssets>,
+    new_stickers: Query<
+        (Entity, &Face, &MeshMaterial3d<StandardMaterial>, &ChildOf),
+        Added<CubieFace>,
+    >,
+    stickers: Query<(&Transform, &ChildOf), (With<CubieFace>, Without<StickerMirror>)>,
+    cubies: Query<(&Cubie, &Transform), Without<StickerMirror>>,
+    mut mirrors: Query<(Entity, &StickerMirror, &mut Transform, &mut Visibility)>,
+) {
+    for (sticker, face, material, parent) in &new_stickers {
+        let normal = face.normal().as_ivec3();
+        let outside = cubies
+            .get(parent.parent())
+            .is_ok_and(|(cubie, _)| cubie.home.dot(normal) == cube_size.outer().dot(normal.abs()));
+        if outside {
+            commands.spawn((
+                StickerMirror(sticker),
+                Mesh3d(cubie_assets.sticker.clone()),
+                material.clone(),
+                Transform::default(),
+                Visibility::Hidden,
+                Pickable::IGNORE,
+            ));
+        }
+    }
+
+    for (entity, mirror, mut transform, mut visibility) in &mut mirrors {
+        // the sticker went when the cube was respawned
+        let Ok((sticker, parent)) = stickers.get(mirror.0) else {
+            commands.entity(entity).despawn();
+            continue;
+        };
+
+        let reflected = mirrors_shown
+            .0
+            .then(|| cubies.get(parent.parent()).ok())
+            .flatten()
+            .and_then(|(_, cubie)| reflection(&cubie.mul_transform(*sticker), &cube_size));
+        match reflected {
+            Some(reflected) => {
+                *transform = reflected;
+                visibility.set_if_neq(Visibility::Inherited);
+            }
+            None => {
+                visibility.set_if_neq(Visibility::Hidden);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_reflections_face_the_cube() {
+        let cube_size = CubeSize::default();
+        let on_left = Transform::from_xyz(-1.5, 1.0, 0.0)
+            .with_rotation(Quat::from_rotation_arc(Vec3::Z, Vec3::NEG_X));
+        let reflected = reflection(&on_left, &cube_size).unwrap();
+
+        // behind the left face, and facing back towards it
+        assert!(reflected.translation.x < -1.5);
+        assert_eq!(reflected.translation.yz(), Vec2::new(1.0, 0.0));
+        assert!((reflected.rotation * Vec3::Z).abs_diff_eq(Vec3::X, 1e-5));
+
+        // stickers the camera can already see, and ones inside the cube, aren't reflected
+        let on_right = Transform::from_xyz(1.5, 0.0, 0.0)
+            .with_rotation(Quat::from_rotation_arc(Vec3::Z, Vec3::X));
+        assert!(reflection(&on_right, &cube_size).is_none());
+        let inside = on_left.with_translation(Vec3::new(-0.5, 0.0, 0.0));
+        assert!(reflection(&inside, &cube_size).is_none());
+    }
+}
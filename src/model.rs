@@ -0,0 +1,272 @@
+use std::f32::consts::FRAC_PI_2;
+
+use bevy::{
+    asset::RenderAssetUsages,
+    prelude::*,
+    render::mesh::{Indices, PrimitiveTopology},
+};
+use serde::Deserialize;
+
+use crate::{
+    cube::Face,
+    cubie::{CENTRE_MARKER_COLOR, CENTRE_MARKER_HALF_SIZE, CUBIE_BODY_COLOR},
+    shape::SHAPE_MOD_COLOR,
+};
+
+/// Where the shape of the cubies can be changed, e.g. `(gap: 0.04, bevel: 0.07)`.
+const CUBIE_MODEL_PATH: &str = "cubie_model.ron";
+
+/// How many triangles go around each rounded edge or corner.
+const ROUNDING_SEGMENTS: usize = 4;
+
+/// The shape of every cubie on a cube, in cubies.
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq, Resource)]
+#[serde(default)]
+pub struct CubieModel {
+    /// How much space there is between neighbouring cubies.
+    pub gap: f32,
+    /// How far back from each edge a cubie starts to round off.
+    pub bevel: f32,
+}
+
+impl Default for CubieModel {
+    fn default() -> Self {
+        Self {
+            gap: 0.04,
+            bevel: 0.07,
+        }
+    }
+}
+
+impl CubieModel {
+    /// The model saved in `cubie_model.ron`, or the default one if there isn't one.
+    pub fn load() -> Self {
+        std::fs::read_to_string(CUBIE_MODEL_PATH)
+            .ok()
+            .and_then(|text| ron::from_str(&text).ok())
+            .unwrap_or_default()
+    }
+
+    /// Half the length of a side of a cubie's body.
+    fn body_half_size(&self) -> f32 {
+        0.5 - self.gap.clamp(0.0, 0.5) / 2.0
+    }
+
+    /// How far a cubie's stickers are from its middle, just above its body.
+    pub fn sticker_offset(&self) -> f32 {
+        self.body_half_size() + 0.002
+    }
+}
+
+/// The color of the ring around a highlighted sticker, which isn't the color of any sticker.
+const HIGHLIGHT_COLOR: Color = Color::srgb(1.0, 0.0, 1.0);
+
+/// The meshes and materials every cubie on a cube is made from, shared by all of them so each
+/// is only made once however big the cube is.
+#[derive(Resource)]
+pub struct CubieAssets {
+    pub body: Handle<Mesh>,
+    /// A sticker facing along the z axis, turned to face each side of a cubie.
+    pub sticker: Handle<Mesh>,
+    /// A centre marker facing along the z axis.
+    pub marker: Handle<Mesh>,
+    pub body_material: Handle<StandardMaterial>,
+    /// The material for each color of sticker, in the order of `Face::variants`.
+    sticker_materials: [Handle<StandardMaterial>; 6],
+    pub marker_material: Handle<StandardMaterial>,
+    pub shape_mod_material: Handle<StandardMaterial>,
+    /// A ring around a sticker facing along the z axis, for stickers picked on the net.
+    pub highlight: Handle<Mesh>,
+    pub highlight_material: Handle<StandardMaterial>,
+    pub sticker_offset: f32,
+}
+
+impl CubieAssets {
+    pub fn new(
+        model: &CubieModel,
+        meshes: &mut Assets<Mesh>,
+        materials: &mut Assets<StandardMaterial>,
+    ) -> Self {
+        let half_size = model.body_half_size();
+        let bevel = model.bevel.clamp(0.0, half_size);
+        let body = rounded_box(Vec3::splat(-half_size), Vec3::splat(half_size), bevel);
+        // stickers cover the flat part of each side and reach a little onto the rounded edges
+        let sticker = rounded_square(Vec3::Z, half_size - bevel / 2.0, bevel);
+        let marker = Plane3d::new(Vec3::Z, Vec2::splat(CENTRE_MARKER_HALF_SIZE));
+
+        Self {
+            body: meshes.add(body),
+            sticker: meshes.add(sticker),
+            marker: meshes.add(marker),
+            body_material: materials.add(CUBIE_BODY_COLOR),
+            sticker_materials: Face::variants()
+                .map(|face| materials.add(face.start_color().color())),
+            marker_material: materials.add(CENTRE_MARKER_COLOR),
+            shape_mod_material: materials.add(SHAPE_MOD_COLOR),
+            highlight: meshes.add(Annulus::new(half_size - bevel, half_size)),
+            highlight_material: materials.add(StandardMaterial {
+                base_color: HIGHLIGHT_COLOR,
+                emissive: HIGHLIGHT_COLOR.into(),
+                unlit: true,
+                ..default()
+            }),
+            sticker_offset: model.sticker_offset(),
+        }
+    }
+
+    /// The material for stickers that started on `face`, which themes restyle.
+    pub fn sticker_material(&self, face: &Face) -> &Handle<StandardMaterial> {
+        &self.sticker_materials[face.index()]
+    }
+}
+
+/// Two directions along the side facing `normal`, anticlockwise around it.
+fn side_axes(normal: Vec3) -> (Vec3, Vec3) {
+    let across = normal.yzx();
+    (across, normal.cross(across))
+}
+
+/// A box from `min` to `max` with its edges and corners rounded off by `radius`.
+pub fn rounded_box(min: Vec3, max: Vec3, radius: f32) -> Mesh {
+    let centre = (min + max) / 2.0;
+    let half_size = (max - min) / 2.0;
+    let radius = radius.clamp(0.0, half_size.min_element());
+    let inner = half_size - radius;
+
+    // where the rows of vertices go along a side of half length `half`, bunched up on the
+    // rounded edges so they're smooth
+    let coordinates = |half: f32| {
+        let flat = half - radius;
+        let arc = (0..=ROUNDING_SEGMENTS).map(move |i| {
+            let angle = i as f32 / ROUNDING_SEGMENTS as f32 * FRAC_PI_2;
+            flat + radius * angle.sin()
+        });
+        arc.clone().rev().map(|x| -x).chain(arc).collect::<Vec<_>>()
+    };
+
+    let mut positions = Vec::new();
+    let mut normals = Vec::new();
+    let mut uvs = Vec::new();
+    let mut indices = Vec::new();
+    for face in Face::variants() {
+        let normal = face.normal();
+        let (across, up) = side_axes(normal);
+        let columns = coordinates(half_size.dot(across.abs()));
+        let rows = coordinates(half_size.dot(up.abs()));
+        let start = positions.len() as u32;
+
+        for (j, y) in rows.iter().enumerate() {
+            for (i, x) in columns.iter().enumerate() {
+                // push each point on the side of the box out onto the rounded box
+                let point = normal * half_size.dot(normal.abs()) + across * *x + up * *y;
+                let nearest = point.clamp(-inner, inner);
+                let outward = (point - nearest).normalize_or(normal);
+                positions.push(centre + nearest + outward * radius);
+                normals.push(outward);
+                uvs.push(Vec2::new(
+                    i as f32 / (columns.len() - 1) as f32,
+                    j as f32 / (rows.len() - 1) as f32,
+                ));
+            }
+        }
+
+        let width = columns.len() as u32;
+        for j in 0..rows.len() as u32 - 1 {
+            for i in 0..width - 1 {
+                let corner = start + j * width + i;
+                indices.extend([corner, corner + 1, corner + width + 1]);
+                indices.extend([corner, corner + width + 1, corner + width]);
+            }
+        }
+    }
+
+    Mesh::new(
+        PrimitiveTopology::TriangleList,
+        RenderAssetUsages::default(),
+    )
+    .with_inserted_attribute(Mesh::ATTRIBUTE_POSITION, positions)
+    .with_inserted_attribute(Mesh::ATTRIBUTE_NORMAL, normals)
+    .with_inserted_attribute(Mesh::ATTRIBUTE_UV_0, uvs)
+    .with_inserted_indices(Indices::U32(indices))
+}
+
+/// A flat square facing `normal`, centred on the origin, with its corners rounded off by
+/// `radius`.
+pub fn rounded_square(normal: Vec3, half_size: f32, radius: f32) -> Mesh {
+    let (across, up) = side_axes(normal);
+    let radius = radius.clamp(0.0, half_size);
+    let inner = half_size - radius;
+
+    // the middle, then around the outside anticlockwise one corner at a time
+    let mut points = vec![Vec2::ZERO];
+    for corner in 0..4 {
+        let middle = Vec2::from_angle(corner as f32 * FRAC_PI_2).rotate(Vec2::splat(inner));
+        points.extend((0..=ROUNDING_SEGMENTS).map(|i| {
+            let angle = (corner as f32 + i as f32 / ROUNDING_SEGMENTS as f32) * FRAC_PI_2;
+            middle + Vec2::from_angle(angle) * radius
+        }));
+    }
+
+    let positions: Vec<_> = points
+        .iter()
+        .map(|point| across * point.x + up * point.y)
+        .collect();
+    let uvs: Vec<_> = points
+        .iter()
+        .map(|point| Vec2::new(point.x, -point.y) / (half_size * 2.0) + 0.5)
+        .collect();
+    let outline = points.len() as u32 - 1;
+    let indices = (1..=outline)
+        .flat_map(|i| [0, i, i % outline + 1])
+        .collect();
+
+    Mesh::new(
+        PrimitiveTopology::TriangleList,
+        RenderAssetUsages::default(),
+    )
+    .with_inserted_attribute(Mesh::ATTRIBUTE_NORMAL, vec![normal; positions.len()])
+    .with_inserted_attribute(Mesh::ATTRIBUTE_POSITION, positions)
+    .with_inserted_attribute(Mesh::ATTRIBUTE_UV_0, uvs)
+    .with_inserted_indices(Indices::U32(indices))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use bevy::render::mesh::VertexAttributeValues;
+
+    fn positions(mesh: &Mesh) -> Vec<Vec3> {
+        match mesh.attribute(Mesh::ATTRIBUTE_POSITION) {
+            Some(VertexAttributeValues::Float32x3(positions)) => {
+                positions.iter().map(|&position| position.into()).collect()
+            }
+            _ => panic!("mesh has no positions"),
+        }
+    }
+
+    #[test]
+    fn test_rounded_meshes_fit_inside_their_bounds() {
+        let half_size = Vec3::new(0.5, 0.4, 0.3);
+        let radius = 0.1;
+        let body = positions(&rounded_box(-half_size, half_size, radius));
+        assert!(
+            body.iter()
+                .all(|position| position.abs().cmple(half_size + 0.0001).all())
+        );
+        // the corners are cut off, but the middle of each side isn't
+        assert!(body.iter().all(|position| {
+            let past_corner = (position.abs() - (half_size - radius)).max(Vec3::ZERO);
+            past_corner.length() <= radius + 0.0001
+        }));
+        assert!(body.iter().any(|position| position.z == half_size.z));
+
+        let sticker = positions(&rounded_square(Vec3::NEG_X, 0.4, radius));
+        assert!(sticker.iter().all(|position| position.x == 0.0));
+        assert!(
+            sticker
+                .iter()
+                .all(|position| position.abs().max_element() <= 0.4 + 0.0001)
+        );
+        assert!(!sticker.contains(&Vec3::new(0.0, 0.4, 0.4)));
+    }
+}
@@ -1,8 +1,13 @@
 use bevy::{
-    input::mouse::{AccumulatedMouseMotion, MouseButtonInput},
+    input::{
+        gestures::PinchGesture,
+        mouse::{AccumulatedMouseScroll, MouseButtonInput, MouseScrollUnit},
+    },
     prelude::*,
+    window::PrimaryWindow,
 };
 
+use crate::camera::OrbitCamera;
 #[derive(Resource)]
 pub struct MousePressed(pub bool);
 
@@ -14,12 +19,36 @@ impl MousePressed {
     }
 }
 
-/// Pan the screen, effectively rotating the cube when the mouse is dragged
+/// How much one line of scrolling zooms by.
+const ZOOM_PER_LINE: f32 = 0.1;
+
+/// How many pixels of smooth scrolling make a line.
+const PIXELS_PER_LINE: f32 = 100.0;
+
+/// Where the cursor at `cursor` is on a ball filling the middle of the window, facing the
+/// camera, with y going up. Outside the ball, it's on the ball's edge.
+fn arcball_point(cursor: Vec2, window_size: Vec2) -> Vec3 {
+    let point = Vec2::new(
+        2.0 * cursor.x - window_size.x,
+        window_size.y - 2.0 * cursor.y,
+    ) / window_size.min_element();
+    let length_squared = point.length_squared();
+    if length_squared <= 1.0 {
+        point.extend((1.0 - length_squared).sqrt())
+    } else {
+        (point / length_squared.sqrt()).extend(0.0)
+    }
+}
+
+/// Turn the cube around as if it were a ball being dragged by the mouse, by orbiting the camera
+/// the other way.
 pub fn handle_mouse_drag(
+    time: Res<Time>,
     mut mouse_pressed: ResMut<MousePressed>,
     mut button_events: EventReader<MouseButtonInput>,
-    motion_events: Res<AccumulatedMouseMotion>,
-    mut camera_transform: Single<&mut Transform, With<Camera>>,
+    window: Single<&Window, With<PrimaryWindow>>,
+    mut camera: Single<&mut OrbitCamera>,
+    mut grabbed: Local<Option<Vec3>>,
 ) {
     // store whether left mouse button is pressed or not
     for button_event in button_events.read() {
@@ -28,19 +57,54 @@ pub fn handle_mouse_drag(
         }
     }
 
-    // if mouse is pressed, handle motion events
-    if mouse_pressed.0 {
-        if motion_events.delta != Vec2::ZERO {
-            let x_displacement = motion_events.delta.x;
-            let y_displacement = motion_events.delta.y;
-
-            if x_displacement.abs() > y_displacement.abs() {
-                let y_rotation = Quat::from_rotation_y(-x_displacement / 75.);
-                camera_transform.rotate_around(Vec3::ZERO, y_rotation);
-            } else {
-                let x_rotation = Quat::from_rotation_x(-y_displacement / 75.);
-                camera_transform.rotate_around(Vec3::ZERO, x_rotation);
-            }
+    let point = window
+        .cursor_position()
+        .map(|cursor| arcball_point(cursor, window.size()));
+    let (true, Some(point)) = (mouse_pressed.0, point) else {
+        *grabbed = None;
+        return;
+    };
+
+    match *grabbed {
+        Some(previous) if previous != point => {
+            camera.drag(Quat::from_rotation_arc(point, previous), time.delta_secs());
         }
+        Some(_) => camera.hold(),
+        None => {}
+    }
+    *grabbed = Some(point);
+}
+
+/// Zoom in and out with the scroll wheel, or by pinching a trackpad or touch screen.
+pub fn handle_zoom(
+    scroll: Res<AccumulatedMouseScroll>,
+    mut pinches: EventReader<PinchGesture>,
+    touches: Res<Touches>,
+    mut camera: Single<&mut OrbitCamera>,
+    mut touch_spread: Local<Option<f32>>,
+) {
+    let lines = match scroll.unit {
+        MouseScrollUnit::Line => scroll.delta.y,
+        MouseScrollUnit::Pixel => scroll.delta.y / PIXELS_PER_LINE,
+    };
+    if lines != 0.0 {
+        camera.zoom((-lines * ZOOM_PER_LINE).exp());
+    }
+
+    for PinchGesture(magnification) in pinches.read() {
+        camera.zoom(1.0 / (1.0 + magnification.max(-0.9)));
+    }
+
+    // two fingers moving apart zoom in, and together zoom out
+    let fingers: Vec<_> = touches.iter().map(|touch| touch.position()).collect();
+    let spread = match fingers[..] {
+        [first, second] => Some(first.distance(second)),
+        _ => None,
+    };
+    if let (Some(spread), Some(previous)) = (spread, *touch_spread)
+        && spread > 0.0
+    {
+        camera.zoom(previous / spread);
     }
+    *touch_spread = spread;
 }
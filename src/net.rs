@@ -0,0 +1,315 @@
+use bevy::{ecs::spawn::SpawnIter, prelude::*};
+
+use crate::{
+    accessibility::ColorVision,
+    cube::{CubeSize, Face, VoidCube},
+    cubie::{Cubie, CubieFace, Kind},
+    history::Timeline,
+    model::CubieAssets,
+    puzzle::Puzzle,
+    state::CubeState,
+    theme::Themes,
+};
+
+/// A sticker on the cube, known by the piece it's on and the face it started on, which stays the
+/// same however the cube is turned.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct StickerId {
+    pub home: IVec3,
+    pub face: Face,
+}
+
+/// The sticker picked on the net, which is highlighted on the cube.
+#[derive(Debug, Default, Resource)]
+pub struct HighlightedSticker(pub Option<StickerId>);
+
+/// Which way is right and which way is down on a face laid out in the net, as seen from outside
+/// the cube. The sides go around the middle row with the top above the front and the bottom
+/// below it.
+pub fn net_axes(face: &Face) -> (IVec3, IVec3) {
+    match face {
+        Face::Top => (IVec3::X, IVec3::Z),
+        Face::Bottom => (IVec3::X, IVec3::NEG_Z),
+        Face::Left => (IVec3::Z, IVec3::NEG_Y),
+        Face::Front => (IVec3::X, IVec3::NEG_Y),
+        Face::Right => (IVec3::NEG_Z, IVec3::NEG_Y),
+        Face::Back => (IVec3::NEG_X, IVec3::NEG_Y),
+    }
+}
+
+/// The column and row of the top left sticker of `face` in the net.
+pub fn face_origin(face: &Face, size: &CubeSize) -> IVec2 {
+    let [width, height, depth] = [IVec3::X, IVec3::Y, IVec3::Z].map(|axis| size.layers(axis));
+    let (width, height, depth) = (width as i32, height as i32, depth as i32);
+    match face {
+        Face::Top => IVec2::new(depth, 0),
+        Face::Left => IVec2::new(0, depth),
+        Face::Front => IVec2::new(depth, depth),
+        Face::Right => IVec2::new(depth + width, depth),
+        Face::Back => IVec2::new(2 * depth + width, depth),
+        Face::Bottom => IVec2::new(depth, depth + height),
+    }
+}
+
+/// How many columns and rows the net of a cube of the given size takes up.
+fn net_size(size: &CubeSize) -> UVec2 {
+    let [width, height, depth] = [IVec3::X, IVec3::Y, IVec3::Z].map(|axis| size.layers(axis));
+    UVec2::new((2 * (width + depth)) as u32, (height + 2 * depth) as u32)
+}
+
+/// Where in the net the sticker facing `face` on the piece at `position` goes.
+fn net_cell(face: &Face, position: IVec3, size: &CubeSize) -> IVec2 {
+    let (right, down) = net_axes(face);
+    let outer = size.outer();
+    // positions are in half cubies, from -outer to outer
+    let column = (position.dot(right) + outer.dot(right.abs())) / 2;
+    let row = (position.dot(down) + outer.dot(down.abs())) / 2;
+    face_origin(face, size) + IVec2::new(column, row)
+}
+
+/// Every sticker on the cube, along with where it is in the net.
+pub fn net_stickers(state: &CubeState) -> Vec<(IVec2, StickerId)> {
+    let size = state.size();
+    let faces = Face::variants();
+    let face = |normal: IVec3| faces.iter().find(|face| face.normal().as_ivec3() == normal);
+
+    let mut stickers = Vec::new();
+    for piece in state.pieces() {
+        for normal in piece.sticker_normals() {
+            let (Some(facing), Some(start)) =
+                (face(piece.orientation().apply(*normal)), face(*normal))
+            else {
+                continue;
+            };
+            stickers.push((
+                net_cell(facing, piece.position(), &size),
+                StickerId {
+                    home: piece.home(),
+                    face: start.clone(),
+                },
+            ));
+        }
+    }
+    stickers
+}
+
+/// The panel showing the cube unfolded flat, so every face can be seen at once. It's replaced
+/// whenever the cube changes size.
+#[derive(Component)]
+pub struct NetPanel;
+
+/// A sticker in the net, along with the sticker on the cube it's showing.
+#[derive(Component)]
+pub struct NetSticker {
+    cell: IVec2,
+    showing: Option<StickerId>,
+}
+
+/// The color of net stickers showing nothing, like the centres of a void cube.
+const EMPTY_NET_STICKER_COLOR: Color = Color::srgb(0.2, 0.2, 0.2);
+
+pub fn net_panel(cube_size: &CubeSize) -> impl Bundle {
+    let size = net_size(cube_size);
+    let stickers = Face::variants().into_iter().flat_map(|face| {
+        let (right, down) = net_axes(&face);
+        let origin = face_origin(&face, cube_size);
+        let (columns, rows) = (cube_size.layers(right), cube_size.layers(down));
+        (0..rows).flat_map(move |row| {
+            (0..columns).map(move |column| {
+                let cell = origin + IVec2::new(column as i32, row as i32);
+                (
+                    Button,
+                    NetSticker {
+                        cell,
+                        showing: None,
+                    },
+                    Node {
+                        grid_column: GridPlacement::start(cell.x as i16 + 1),
+                        grid_row: GridPlacement::start(cell.y as i16 + 1),
+                        border: UiRect::all(Val::Px(1.0)),
+                        ..default()
+                    },
+                    BackgroundColor(EMPTY_NET_STICKER_COLOR),
+                    BorderColor(Color::BLACK),
+                )
+            })
+        })
+    });
+
+    (
+        NetPanel,
+        Node {
+            position_type: PositionType::Absolute,
+            right: Val::Percent(2.0),
+            top: Val::Percent(50.0),
+            width: Val::Vw(20.0),
+            aspect_ratio: Some(size.x as f32 / size.y as f32),
+            display: Display::Grid,
+            grid_template_columns: vec![RepeatedGridTrack::fr(size.x as u16, 1.)],
+            grid_template_rows: vec![RepeatedGridTrack::fr(size.y as u16, 1.)],
+            ..default()
+        },
+        Children::spawn(SpawnIter(stickers.collect::<Vec<_>>().into_iter())),
+    )
+}
+
+/// Replace the net whenever the cube changes size, and hide it while another puzzle is picked.
+pub fn respawn_net_panel(
+    mut commands: Commands,
+    cube_size: Res<CubeSize>,
+    puzzle: Res<Puzzle>,
+    mut highlighted: ResMut<HighlightedSticker>,
+    panels: Query<Entity, With<NetPanel>>,
+) {
+    if !(cube_size.is_changed() || puzzle.is_changed()) || cube_size.is_added() {
+        return;
+    }
+
+    highlighted.0 = None;
+    for entity in &panels {
+        commands.entity(entity).despawn();
+    }
+    if *puzzle == Puzzle::Cube {
+        commands.spawn(net_panel(&cube_size));
+    }
+}
+
+/// Color the net to match the cube whenever the cube is turned or the timeline jumps, and
+/// outline the highlighted sticker.
+pub fn update_net(
+    timeline: Res<Timeline>,
+    themes: Res<Themes>,
+    vision: Res<ColorVision>,
+    void_cube: Res<VoidCube>,
+    highlighted: Res<HighlightedSticker>,
+    mut stickers: Query<(&mut NetSticker, &mut BackgroundColor, &mut BorderColor)>,
+    added: Query<(), Added<NetSticker>>,
+) {
+    let changed = timeline.is_changed() || themes.is_changed() || vision.is_changed();
+    if !changed && !void_cube.is_changed() && !highlighted.is_changed() && added.is_empty() {
+        return;
+    }
+
+    let state = timeline.state();
+    let size = state.size();
+    let net = net_stickers(&state);
+    let scheme = &themes.current().scheme;
+    for (mut sticker, mut background, mut border) in &mut stickers {
+        let showing = net
+            .iter()
+            .find(|(cell, _)| *cell == sticker.cell)
+            .map(|(_, id)| id)
+            .filter(|id| {
+                !(void_cube.0 && Kind::from_position(id.home, &size) == Some(Kind::Centre))
+            });
+
+        background.0 = showing.map_or(EMPTY_NET_STICKER_COLOR, |id| {
+            vision.simulate(scheme.color(&id.face))
+        });
+        border.0 = if showing.is_some() && showing == highlighted.0.as_ref() {
+            Color::WHITE
+        } else {
+            Color::BLACK
+        };
+        sticker.showing = showing.cloned();
+    }
+}
+
+/// Highlight the sticker clicked on the net, or stop highlighting it when it's clicked again.
+pub fn net_sticker_button_system(
+    stickers: Query<(&Interaction, &NetSticker), Changed<Interaction>>,
+    mut highlighted: ResMut<HighlightedSticker>,
+) {
+    for (interaction, sticker) in &stickers {
+        if *interaction != Interaction::Pressed || sticker.showing.is_none() {
+            continue;
+        }
+        highlighted.0 = if highlighted.0 == sticker.showing {
+            None
+        } else {
+            sticker.showing.clone()
+        };
+    }
+}
+
+/// A ring around the sticker on the cube that's highlighted on the net.
+#[derive(Component)]
+pub struct StickerHighlight;
+
+/// Put a ring around the highlighted sticker. The ring is a child of the sticker, so it follows
+/// the sticker as the cube is turned.
+pub fn highlight_sticker(
+    mut commands: Commands,
+    highlighted: Res<HighlightedSticker>,
+    cubie_assets: Res<CubieAssets>,
+    stickers: Query<(Entity, &Face, &ChildOf), With<CubieFace>>,
+    added: Query<(), Added<CubieFace>>,
+    cubies: Query<&Cubie>,
+    highlights: Query<Entity, With<StickerHighlight>>,
+) {
+    if !highlighted.is_changed() && added.is_empty() {
+        return;
+    }
+
+    for highlight in &highlights {
+        commands.entity(highlight).despawn();
+    }
+    let Some(id) = &highlighted.0 else {
+        return;
+    };
+    let sticker = stickers.iter().find(|(_, face, parent)| {
+        **face == id.face
+            && cubies
+                .get(parent.parent())
+                .is_ok_and(|cubie| cubie.home == id.home)
+    });
+    if let Some((sticker, _, _)) = sticker {
+        // stickers face along their z axis
+        commands.entity(sticker).with_child((
+            StickerHighlight,
+            Mesh3d(cubie_assets.highlight.clone()),
+            MeshMaterial3d(cubie_assets.highlight_material.clone()),
+            Transform::from_xyz(0.0, 0.0, 0.004),
+            Pickable::IGNORE,
+        ));
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_net_shows_every_sticker() {
+        let size = CubeSize::default();
+        let mut state = CubeState::solved(size);
+        let net = net_stickers(&state);
+        assert_eq!(net.len(), 54);
+        // each face of a solved cube is all one color
+        for face in Face::variants() {
+            let origin = face_origin(&face, &size);
+            let mut on_face = net.iter().filter(|(cell, _)| {
+                (*cell - origin).cmpge(IVec2::ZERO).all()
+                    && (*cell - origin).cmplt(IVec2::splat(3)).all()
+            });
+            assert!(on_face.clone().count() == 9 && on_face.all(|(_, id)| id.face == face));
+        }
+
+        // turning the right face moves the front's right column onto the top
+        state.apply_algorithm(&"R".parse().unwrap());
+        let net = net_stickers(&state);
+        let top = face_origin(&Face::Top, &size);
+        for row in 0..3 {
+            let cell = top + IVec2::new(2, row);
+            let (_, id) = net.iter().find(|(other, _)| *other == cell).unwrap();
+            assert_eq!(id.face, Face::Front);
+        }
+        let cells: Vec<_> = net.iter().map(|(cell, _)| *cell).collect();
+        assert!(
+            cells
+                .iter()
+                .enumerate()
+                .all(|(i, cell)| !cells[i + 1..].contains(cell))
+        );
+    }
+}
@@ -0,0 +1,331 @@
+use std::{collections::VecDeque, sync::OnceLock};
+
+use bevy::prelude::*;
+use rand::{Rng, seq::SliceRandom};
+
+use crate::{
+    algorithm::Algorithm,
+    cube::{CubeSize, Face},
+    cubie::grid_positions,
+    rotation::{Direction, Rotation},
+    state::{CubeState, Orientation},
+};
+
+pub const POCKET_SIZE: CubeSize = CubeSize::cube(2);
+
+/// The pocket cube has no centres to say which way is up, so states are compared with the
+/// down-back-left corner kept still. Turning the whole cube is ignored.
+const FIXED_CORNER: IVec3 = IVec3::new(-1, -1, -1);
+
+/// The faces that can be turned without moving `FIXED_CORNER`.
+const FACES: [Face; 3] = [Face::Top, Face::Right, Face::Front];
+
+/// Each face can be turned clockwise, twice, or anticlockwise.
+const MOVE_COUNT: usize = 9;
+
+/// The number of ways to arrange the 7 corners that move.
+const PERMUTATIONS: usize = 5040;
+
+/// The number of ways to twist the 7 corners that move. The last twist always follows from
+/// the other 6, as the twists add up to a multiple of 3.
+const TWISTS: usize = 729;
+
+/// The positions of the 7 corners that move, which are numbered in this order.
+fn slots() -> Vec<IVec3> {
+    grid_positions(&POCKET_SIZE)
+        .filter(|position| *position != FIXED_CORNER)
+        .collect()
+}
+
+/// Which corner is in each slot, and how it is twisted.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+struct Corners {
+    pieces: [u8; 7],
+    twists: [u8; 7],
+}
+
+impl Corners {
+    const SOLVED: Self = Self {
+        pieces: [0, 1, 2, 3, 4, 5, 6],
+        twists: [0; 7],
+    };
+
+    /// Read the corners from a cube, returning how the whole cube has been turned along with them.
+    fn from_state(state: &CubeState, slots: &[IVec3]) -> (Self, Orientation) {
+        let frame = state
+            .piece(FIXED_CORNER)
+            .expect("pocket cubes have every corner")
+            .orientation();
+        let unturn = frame.inverse();
+
+        let slot = |position: IVec3| slots.iter().position(|slot| *slot == position).unwrap();
+        let mut corners = Self::SOLVED;
+        for piece in state.pieces() {
+            if piece.home() == FIXED_CORNER {
+                continue;
+            }
+
+            let position = unturn.apply(piece.position());
+            let up_down = IVec3::Y * piece.home().y.signum();
+            let facing = unturn.compose(&piece.orientation()).apply(up_down);
+            corners.pieces[slot(position)] = slot(piece.home()) as u8;
+            corners.twists[slot(position)] = twist(position, facing);
+        }
+
+        (corners, frame)
+    }
+
+    /// Apply a move, given as the corners of a solved cube after that move.
+    fn turn(&self, turn: &Self) -> Self {
+        let mut corners = *self;
+        for slot in 0..7 {
+            let from = turn.pieces[slot] as usize;
+            corners.pieces[slot] = self.pieces[from];
+            corners.twists[slot] = (self.twists[from] + turn.twists[slot]) % 3;
+        }
+        corners
+    }
+
+    /// Number the arrangement of the corners from 0 to `PERMUTATIONS`, with 0 for solved.
+    fn permutation_index(&self) -> usize {
+        (0..7).fold(0, |index, i| {
+            let smaller_after = self.pieces[i + 1..]
+                .iter()
+                .filter(|piece| **piece < self.pieces[i])
+                .count();
+            index * (7 - i) + smaller_after
+        })
+    }
+
+    /// Number the twists of the corners from 0 to `TWISTS`, with 0 for solved.
+    fn twist_index(&self) -> usize {
+        self.twists[..6]
+            .iter()
+            .fold(0, |index, twist| index * 3 + *twist as usize)
+    }
+
+    fn from_indices(mut permutation: usize, mut twist: usize) -> Self {
+        let mut corners = Self::SOLVED;
+
+        let mut smaller_after = [0; 7];
+        for (i, smaller) in smaller_after.iter_mut().enumerate().rev() {
+            *smaller = permutation % (7 - i);
+            permutation /= 7 - i;
+        }
+        let mut unused = (0..7).collect::<Vec<u8>>();
+        for (piece, smaller) in corners.pieces.iter_mut().zip(smaller_after) {
+            *piece = unused.remove(smaller);
+        }
+
+        for i in (0..6).rev() {
+            corners.twists[i] = (twist % 3) as u8;
+            twist /= 3;
+        }
+        let total = corners.twists[..6].iter().sum::<u8>();
+        corners.twists[6] = (3 - total % 3) % 3;
+
+        corners
+    }
+
+    fn index(&self) -> usize {
+        self.permutation_index() * TWISTS + self.twist_index()
+    }
+}
+
+/// How far a corner at `position` is twisted, given where its up or down sticker is `facing`.
+/// 0 when that sticker is on the up or down face, otherwise 1 or 2 for each way of twisting.
+fn twist(position: IVec3, facing: IVec3) -> u8 {
+    let up_down = IVec3::Y * position.y.signum();
+    if facing == up_down {
+        0
+    } else if up_down.cross(facing).dot(position) < 0 {
+        1
+    } else {
+        2
+    }
+}
+
+/// Finds the shortest solutions for the pocket cube, counting half turns as one move.
+/// Every state's distance from solved is worked out with a breadth first search the first time
+/// it is needed, after which solving is just stepping to a neighbour one move closer.
+/// States are numbered by `Corners::index`.
+pub struct PocketSolver {
+    slots: Vec<IVec3>,
+    /// Where each move takes each permutation index. Each face is turned clockwise, twice, then
+    /// anticlockwise.
+    permutation_moves: Vec<[u16; MOVE_COUNT]>,
+    /// Where each move takes each twist index.
+    twist_moves: Vec<[u16; MOVE_COUNT]>,
+    distances: OnceLock<Vec<u8>>,
+}
+
+impl PocketSolver {
+    pub fn new() -> Self {
+        let slots = slots();
+        let mut moves = Vec::new();
+        for face in &FACES {
+            let mut state = CubeState::solved(POCKET_SIZE);
+            for _ in 0..3 {
+                state.apply(&Rotation::new(face.clone(), 0..=0, Direction::Backward));
+                moves.push(Corners::from_state(&state, &slots).0);
+            }
+        }
+
+        let move_table =
+            |count: usize, corners: fn(usize) -> Corners, index: fn(&Corners) -> usize| {
+                (0..count)
+                    .map(|i| {
+                        let corners = corners(i);
+                        std::array::from_fn(|m| index(&corners.turn(&moves[m])) as u16)
+                    })
+                    .collect()
+            };
+
+        Self {
+            slots,
+            permutation_moves: move_table(
+                PERMUTATIONS,
+                |i| Corners::from_indices(i, 0),
+                Corners::permutation_index,
+            ),
+            twist_moves: move_table(
+                TWISTS,
+                |i| Corners::from_indices(0, i),
+                Corners::twist_index,
+            ),
+            distances: OnceLock::new(),
+        }
+    }
+
+    /// The state each move takes the state `index` to.
+    fn neighbours(&self, index: usize) -> impl Iterator<Item = usize> {
+        let permutation_moves = &self.permutation_moves[index / TWISTS];
+        let twist_moves = &self.twist_moves[index % TWISTS];
+        (0..MOVE_COUNT).map(|m| permutation_moves[m] as usize * TWISTS + twist_moves[m] as usize)
+    }
+
+    /// How many moves away from solved every state is.
+    fn distances(&self) -> &[u8] {
+        self.distances.get_or_init(|| {
+            let mut distances = vec![u8::MAX; PERMUTATIONS * TWISTS];
+            distances[0] = 0;
+            let mut queue = VecDeque::from([0]);
+            while let Some(index) = queue.pop_front() {
+                let distance = distances[index];
+                for next in self.neighbours(index) {
+                    if distances[next] == u8::MAX {
+                        distances[next] = distance + 1;
+                        queue.push_back(next);
+                    }
+                }
+            }
+            distances
+        })
+    }
+
+    /// The moves, as indices into the move tables, that solve `corners` in as few moves as
+    /// possible.
+    fn solve_corners(&self, corners: Corners) -> Vec<usize> {
+        let distances = self.distances();
+        let mut index = corners.index();
+        let mut solution = Vec::new();
+        while distances[index] > 0 {
+            let (move_index, next) = self
+                .neighbours(index)
+                .enumerate()
+                .find(|(_, next)| distances[*next] < distances[index])
+                .expect("every state is one move closer to solved than one of its neighbours");
+            solution.push(move_index);
+            index = next;
+        }
+        solution
+    }
+
+    /// An optimal solution for a pocket cube, in terms of the faces of the cube as it is turned.
+    pub fn solve(&self, state: &CubeState) -> Algorithm {
+        let (corners, frame) = Corners::from_state(state, &self.slots);
+        algorithm(&self.solve_corners(corners), frame)
+    }
+
+    /// A scramble that leaves a solved pocket cube in a state picked uniformly at random.
+    pub fn random_scramble(&self) -> Algorithm {
+        let mut rng = rand::rng();
+        let mut pieces = [0, 1, 2, 3, 4, 5, 6];
+        pieces.shuffle(&mut rng);
+        let permutation = Corners {
+            pieces,
+            ..Corners::SOLVED
+        }
+        .permutation_index();
+        let corners = Corners::from_indices(permutation, rng.random_range(0..TWISTS));
+
+        // undoing the solution to the random state takes a solved cube to it
+        algorithm(&self.solve_corners(corners), Orientation::IDENTITY).inverse()
+    }
+}
+
+/// Turn move indices into rotations of the faces they end up on once the whole cube is turned
+/// by `frame`.
+fn algorithm(moves: &[usize], frame: Orientation) -> Algorithm {
+    let rotations = moves
+        .iter()
+        .flat_map(|move_index| {
+            let normal = frame.apply(FACES[move_index / 3].normal().as_ivec3());
+            let face = Face::variants()
+                .into_iter()
+                .find(|face| face.normal().as_ivec3() == normal)
+                .expect("a turned face normal is still a face normal");
+            let quarter_turns = move_index % 3 + 1;
+            let turn = |direction| Rotation::new(face.clone(), 0..=0, direction);
+            match quarter_turns {
+                3 => vec![turn(Direction::Forward)],
+                _ => vec![turn(Direction::Backward); quarter_turns],
+            }
+        })
+        .collect();
+
+    Algorithm::new(rotations)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_indices_round_trip() {
+        let corners = Corners {
+            pieces: [3, 0, 6, 1, 5, 2, 4],
+            twists: [1, 0, 2, 2, 1, 0, 0],
+        };
+        let index = (corners.permutation_index(), corners.twist_index());
+        assert_eq!(Corners::from_indices(index.0, index.1), corners);
+        assert_eq!(Corners::SOLVED.index(), 0);
+    }
+
+    #[test]
+    fn test_solutions_are_optimal() {
+        let solver = PocketSolver::new();
+
+        // R U2 can't be undone any quicker than U2 R'
+        let mut state = CubeState::solved(POCKET_SIZE);
+        state.apply_algorithm(&"R U2".parse().unwrap());
+        assert_eq!(solver.solve(&state).rotations().len(), 3);
+
+        // turning the fixed corner away is the same as turning the whole cube
+        let mut state = CubeState::solved(POCKET_SIZE);
+        state.apply_algorithm(&"L D' B2".parse().unwrap());
+        let solution = solver.solve(&state);
+        state.apply_algorithm(&solution);
+        assert!(state.is_solved());
+        assert_eq!(solution.rotations().len(), 4);
+
+        // every state is at most 11 moves from solved
+        let distances = solver.distances();
+        assert!(distances.iter().all(|distance| *distance <= 11));
+
+        let mut state = CubeState::solved(POCKET_SIZE);
+        state.apply_algorithm(&solver.random_scramble());
+        state.apply_algorithm(&solver.solve(&state));
+        assert!(state.is_solved());
+    }
+}
@@ -0,0 +1,614 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    f32::consts::TAU,
+    ops::RangeInclusive,
+};
+
+use bevy::{
+    asset::RenderAssetUsages,
+    prelude::*,
+    render::mesh::{Indices, PrimitiveTopology},
+};
+
+use crate::{
+    algorithm::NotationError,
+    cube::IsCubeSolved,
+    custom::CustomPuzzle,
+    megaminx, pyraminx,
+    rotation::{RotationCompleted, RotationTimer, Rotations, Turn},
+    skewb,
+};
+
+/// How close two points have to be to count as the same point.
+const EPSILON: f32 = 1e-3;
+
+/// How much of the area cut out for each sticker it covers, leaving gaps between the stickers.
+const STICKER_SCALE: f32 = 0.9;
+
+/// Which puzzle is being played with. The cube has its own cubies and moves, while every other
+/// puzzle is cut up from a `PuzzleDefinition` and turned with `Twist`s. Custom puzzles are read
+/// from a file, see `PuzzleFile`.
+#[derive(Clone, Debug, Default, PartialEq, Resource)]
+pub enum Puzzle {
+    #[default]
+    Cube,
+    Pyraminx,
+    Megaminx,
+    Skewb,
+    Custom(CustomPuzzle),
+}
+
+impl Puzzle {
+    /// The shape of the puzzle and how it's cut, or `None` for the cube.
+    pub fn definition(&self) -> Option<PuzzleDefinition> {
+        match self {
+            Self::Cube => None,
+            Self::Pyraminx => Some(pyraminx::definition()),
+            Self::Megaminx => Some(megaminx::definition()),
+            Self::Skewb => Some(skewb::definition()),
+            Self::Custom(custom) => Some(custom.0.definition()),
+        }
+    }
+
+    /// Read moves written in the puzzle's own notation. The cube's moves are read as an
+    /// `Algorithm` instead, so it has no twists.
+    pub fn parse(&self, s: &str) -> Result<Vec<Twist>, NotationError> {
+        match self {
+            Self::Cube => Ok(Vec::new()),
+            Self::Pyraminx => pyraminx::parse(s),
+            Self::Megaminx => megaminx::parse(s),
+            Self::Skewb => skewb::parse(s),
+            Self::Custom(custom) => custom.0.parse(s),
+        }
+    }
+
+    /// A random scramble for the puzzle.
+    pub fn scramble(&self) -> Vec<Twist> {
+        match self {
+            Self::Cube => Vec::new(),
+            Self::Pyraminx => pyraminx::scramble(),
+            Self::Megaminx => megaminx::scramble(),
+            Self::Skewb => skewb::scramble(),
+            Self::Custom(custom) => custom.0.scramble(),
+        }
+    }
+
+    /// The twists that solve the puzzle from `state`, or `None` if it has no solver.
+    pub fn solve(&self, state: &PuzzleState) -> Option<Vec<Twist>> {
+        match self {
+            Self::Skewb => Some(skewb::solve(state)),
+            _ => None,
+        }
+    }
+}
+
+/// A puzzle described by the solid it is made from and the planes it is cut along. Every
+/// piece is cut out by the planes, and every sticker is a part of one of the solid's faces.
+/// The solid is centred on the origin.
+#[derive(Clone, Debug, Default)]
+pub struct PuzzleDefinition {
+    pub faces: Vec<FaceDefinition>,
+    pub axes: Vec<AxisDefinition>,
+}
+
+/// A face of the solid a puzzle is made from.
+#[derive(Clone, Debug)]
+pub struct FaceDefinition {
+    /// The corners of the face, anticlockwise when looking at the face from outside.
+    pub vertices: Vec<Vec3>,
+    pub color: Color,
+}
+
+impl FaceDefinition {
+    /// A face with the given corners, in order around the face either way round.
+    pub fn new(mut vertices: Vec<Vec3>, color: Color) -> Self {
+        if normal(&vertices).dot(centre(&vertices)) < 0.0 {
+            vertices.reverse();
+        }
+        Self { vertices, color }
+    }
+
+    pub fn area(&self) -> f32 {
+        normal(&self.vertices).length() / 2.0
+    }
+}
+
+/// The faces of a convex solid with a face `distance` from the centre along each of `normals`,
+/// colored with `colors` in the same order. The corners of each face are the points where its
+/// plane meets two others without going outside the solid.
+pub fn solid(normals: &[Vec3], distance: f32, colors: &[Color]) -> Vec<FaceDefinition> {
+    let inside = |point: Vec3| {
+        normals
+            .iter()
+            .all(|normal| normal.dot(point) < distance + EPSILON)
+    };
+
+    normals
+        .iter()
+        .zip(colors)
+        .map(|(normal, color)| {
+            let mut corners: Vec<Vec3> = Vec::new();
+            for (i, a) in normals.iter().enumerate() {
+                for b in &normals[i + 1..] {
+                    let planes = Mat3::from_cols(*normal, *a, *b).transpose();
+                    if planes.determinant().abs() < EPSILON {
+                        continue;
+                    }
+                    let corner = planes.inverse() * Vec3::splat(distance);
+                    if inside(corner) && corners.iter().all(|c| c.distance(corner) > EPSILON) {
+                        corners.push(corner);
+                    }
+                }
+            }
+
+            // a solid that isn't closed in by its faces leaves some of them without corners
+            if corners.len() < 3 {
+                return FaceDefinition {
+                    vertices: corners,
+                    color: *color,
+                };
+            }
+
+            // put the corners in order around the face
+            let middle = *normal * distance;
+            let across = (corners[0] - middle).normalize();
+            let up = normal.cross(across);
+            corners.sort_by(|a, b| {
+                let angle = |corner: &Vec3| {
+                    (*corner - middle)
+                        .dot(up)
+                        .atan2((*corner - middle).dot(across))
+                };
+                angle(a).total_cmp(&angle(b))
+            });
+            FaceDefinition::new(corners, *color)
+        })
+        .collect()
+}
+
+/// An axis the puzzle turns around, along with the planes that cut it into layers.
+#[derive(Clone, Debug)]
+pub struct AxisDefinition {
+    /// A unit vector pointing out through the layers turned by moves around this axis.
+    pub normal: Vec3,
+    /// How far along `normal` each cut is, starting with the outermost. Layers are counted from
+    /// the outside starting at 0, so layer `i` is between cuts `i - 1` and `i`.
+    pub cuts: Vec<f32>,
+    /// How many turns it takes to go all the way round.
+    pub order: u32,
+}
+
+impl AxisDefinition {
+    /// The layer `point` is in.
+    pub fn layer(&self, point: Vec3) -> usize {
+        let depth = self.normal.dot(point);
+        self.cuts.iter().filter(|cut| depth < **cut).count()
+    }
+}
+
+/// A turn of some layers of a puzzle around one of its axes, counting in steps of `1 / order`
+/// of a whole turn. Positive turns are clockwise when looking at the end the axis points out of.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Twist {
+    axis: usize,
+    layers: RangeInclusive<usize>,
+    turns: i32,
+    order: u32,
+}
+
+impl Twist {
+    pub fn new(axis: usize, layers: RangeInclusive<usize>, turns: i32, order: u32) -> Self {
+        Self {
+            axis,
+            layers,
+            turns,
+            order,
+        }
+    }
+
+    pub fn axis(&self) -> usize {
+        self.axis
+    }
+
+    /// The twist that undoes this one.
+    pub fn inverse(&self) -> Self {
+        Self::new(self.axis, self.layers.clone(), -self.turns, self.order)
+    }
+}
+
+impl Turn for Twist {
+    fn angle(&self) -> f32 {
+        -(self.turns as f32) * TAU / self.order as f32
+    }
+}
+
+/// A sticker, as the part of a face it covers.
+#[derive(Clone, Debug)]
+pub struct Sticker {
+    /// The index of the face the sticker is on.
+    face: usize,
+    polygon: Vec<Vec3>,
+}
+
+impl Sticker {
+    fn centre(&self) -> Vec3 {
+        centre(&self.polygon)
+    }
+
+    /// A flat mesh covering the middle of the sticker.
+    fn mesh(&self) -> Mesh {
+        let centre = self.centre();
+        let positions = self
+            .polygon
+            .iter()
+            .map(|vertex| centre + (*vertex - centre) * STICKER_SCALE)
+            .collect::<Vec<_>>();
+        let normals = vec![normal(&self.polygon).normalize(); positions.len()];
+        let indices = (1..positions.len() as u32 - 1)
+            .flat_map(|i| [0, i, i + 1])
+            .collect();
+
+        Mesh::new(
+            PrimitiveTopology::TriangleList,
+            RenderAssetUsages::default(),
+        )
+        .with_inserted_attribute(Mesh::ATTRIBUTE_POSITION, positions)
+        .with_inserted_attribute(Mesh::ATTRIBUTE_NORMAL, normals)
+        .with_inserted_indices(Indices::U32(indices))
+    }
+}
+
+/// The average of some points.
+fn centre(points: &[Vec3]) -> Vec3 {
+    points.iter().sum::<Vec3>() / points.len() as f32
+}
+
+/// A normal of a flat polygon whose corners are anticlockwise around it, with a length of twice
+/// the polygon's area.
+fn normal(polygon: &[Vec3]) -> Vec3 {
+    polygon
+        .iter()
+        .zip(polygon.iter().cycle().skip(1))
+        .map(|(a, b)| a.cross(*b))
+        .sum()
+}
+
+/// Split a convex polygon along the plane where `axis · p = depth`, into the parts above and
+/// below it. A part is empty if none of the polygon is on that side.
+fn split(polygon: &[Vec3], axis: Vec3, depth: f32) -> [Vec<Vec3>; 2] {
+    let mut above = Vec::new();
+    let mut below = Vec::new();
+    for (a, b) in polygon.iter().zip(polygon.iter().cycle().skip(1)) {
+        let (height_a, height_b) = (axis.dot(*a) - depth, axis.dot(*b) - depth);
+        // corners on the plane belong to both parts
+        if height_a > -EPSILON {
+            above.push(*a);
+        }
+        if height_a < EPSILON {
+            below.push(*a);
+        }
+        if (height_a > EPSILON && height_b < -EPSILON)
+            || (height_a < -EPSILON && height_b > EPSILON)
+        {
+            let crossing = *a + (*b - *a) * (height_a / (height_a - height_b));
+            above.push(crossing);
+            below.push(crossing);
+        }
+    }
+
+    [above, below].map(|part| {
+        if part.len() >= 3 && normal(&part).length() > EPSILON {
+            part
+        } else {
+            Vec::new()
+        }
+    })
+}
+
+/// Cut the faces of a puzzle along every cut plane and group the stickers into pieces.
+/// Stickers are on the same piece when they are in the same layer of every axis.
+pub fn pieces(definition: &PuzzleDefinition) -> Vec<Vec<Sticker>> {
+    let mut stickers = definition
+        .faces
+        .iter()
+        .enumerate()
+        .map(|(face, face_definition)| Sticker {
+            face,
+            polygon: face_definition.vertices.clone(),
+        })
+        .collect::<Vec<_>>();
+
+    for axis in &definition.axes {
+        for cut in &axis.cuts {
+            stickers = stickers
+                .into_iter()
+                .flat_map(|sticker| {
+                    split(&sticker.polygon, axis.normal, *cut)
+                        .into_iter()
+                        .filter(|part| !part.is_empty())
+                        .map(move |polygon| Sticker {
+                            face: sticker.face,
+                            polygon,
+                        })
+                })
+                .collect();
+        }
+    }
+
+    let mut pieces: BTreeMap<Vec<usize>, Vec<Sticker>> = BTreeMap::new();
+    for sticker in stickers {
+        let layers = definition
+            .axes
+            .iter()
+            .map(|axis| axis.layer(sticker.centre()))
+            .collect();
+        pieces.entry(layers).or_default().push(sticker);
+    }
+    pieces.into_values().collect()
+}
+
+/// Where one clockwise turn of `layer` sends the sticker centred at each place, or `None` if a
+/// sticker doesn't land on another one, which happens when the axis's order doesn't match the
+/// shape.
+fn layer_turn(axis: &AxisDefinition, layer: usize, centres: &[Vec3]) -> Option<Vec<usize>> {
+    let turn = Quat::from_axis_angle(axis.normal, -TAU / axis.order as f32);
+    centres
+        .iter()
+        .enumerate()
+        .map(|(place, centre)| {
+            if axis.layer(*centre) != layer {
+                return Some(place);
+            }
+            let turned = turn * *centre;
+            centres
+                .iter()
+                .position(|other| other.distance(turned) < EPSILON)
+        })
+        .collect()
+}
+
+/// The first axis, by its place in the list of axes, with a turn that doesn't move every sticker
+/// onto another.
+pub fn misfit_axis(definition: &PuzzleDefinition) -> Option<usize> {
+    let stickers = pieces(definition).into_iter().flatten().collect::<Vec<_>>();
+    let centres = stickers.iter().map(Sticker::centre).collect::<Vec<_>>();
+
+    definition.axes.iter().position(|axis| {
+        (0..=axis.cuts.len()).any(|layer| layer_turn(axis, layer, &centres).is_none())
+    })
+}
+
+/// A logical model of a puzzle that tracks which sticker is in each place a sticker can be,
+/// independent of the rendered pieces.
+#[derive(Clone, Debug, Default, Resource)]
+pub struct PuzzleState {
+    axes: Vec<AxisDefinition>,
+    /// The face each place is on.
+    faces: Vec<usize>,
+    /// The face the sticker now in each place started on.
+    stickers: Vec<usize>,
+    /// Where one clockwise turn of a single layer sends the sticker in each place, indexed by
+    /// axis and then by layer.
+    turns: Vec<Vec<Vec<usize>>>,
+}
+
+impl PuzzleState {
+    /// The puzzle solved. Every turn must move each sticker onto another, which `misfit_axis`
+    /// checks.
+    pub fn solved(definition: &PuzzleDefinition) -> Self {
+        let stickers = pieces(definition).into_iter().flatten().collect::<Vec<_>>();
+        let centres = stickers.iter().map(Sticker::centre).collect::<Vec<_>>();
+
+        let turns = definition
+            .axes
+            .iter()
+            .map(|axis| {
+                (0..=axis.cuts.len())
+                    .map(|layer| {
+                        layer_turn(axis, layer, &centres)
+                            .expect("turns move every sticker onto another")
+                    })
+                    .collect()
+            })
+            .collect();
+
+        let faces = stickers
+            .iter()
+            .map(|sticker| sticker.face)
+            .collect::<Vec<_>>();
+        Self {
+            axes: definition.axes.clone(),
+            stickers: faces.clone(),
+            faces,
+            turns,
+        }
+    }
+
+    /// The face each place is on, which is also the face of the sticker in each place when the
+    /// puzzle is solved.
+    pub fn faces(&self) -> &[usize] {
+        &self.faces
+    }
+
+    /// The face the sticker now in each place started on.
+    pub fn stickers(&self) -> &[usize] {
+        &self.stickers
+    }
+
+    /// Where `twist` sends the sticker in each place.
+    pub fn permutation(&self, twist: &Twist) -> Vec<usize> {
+        let mut permutation = (0..self.faces.len()).collect::<Vec<_>>();
+        let Some(layer_turns) = self.turns.get(twist.axis) else {
+            return permutation;
+        };
+
+        let order = self.axes[twist.axis].order as i32;
+        for _ in 0..twist.turns.rem_euclid(order) {
+            for turn in twist
+                .layers
+                .clone()
+                .filter_map(|layer| layer_turns.get(layer))
+            {
+                for to in &mut permutation {
+                    *to = turn[*to];
+                }
+            }
+        }
+        permutation
+    }
+
+    pub fn apply(&mut self, twist: &Twist) {
+        let mut stickers = self.stickers.clone();
+        for (place, to) in self.permutation(twist).into_iter().enumerate() {
+            stickers[to] = self.stickers[place];
+        }
+        self.stickers = stickers;
+    }
+
+    /// Whether every face shows a single color.
+    pub fn is_solved(&self) -> bool {
+        // the sticker seen so far on each face
+        let mut face_stickers = HashMap::new();
+        self.faces
+            .iter()
+            .zip(&self.stickers)
+            .all(|(face, sticker)| face_stickers.entry(face).or_insert(sticker) == &sticker)
+    }
+}
+
+/// A piece of a puzzle other than the cube, along with where its centre is in the solved
+/// puzzle. Pieces turn around the centre of the puzzle, so their translation is always zero.
+#[derive(Component)]
+pub struct PuzzlePiece {
+    pub home: Vec3,
+}
+
+#[derive(Component)]
+pub struct PuzzleSticker;
+
+pub fn spawn_puzzle(
+    commands: &mut Commands,
+    meshes: &mut ResMut<Assets<Mesh>>,
+    materials: &mut ResMut<Assets<StandardMaterial>>,
+    definition: &PuzzleDefinition,
+) {
+    let face_materials = definition
+        .faces
+        .iter()
+        .map(|face| materials.add(face.color))
+        .collect::<Vec<_>>();
+
+    for stickers in pieces(definition) {
+        let home = centre(&stickers.iter().map(Sticker::centre).collect::<Vec<_>>());
+        commands
+            .spawn((
+                PuzzlePiece { home },
+                Transform::default(),
+                Visibility::default(),
+            ))
+            .with_children(|parent| {
+                for sticker in &stickers {
+                    parent.spawn((
+                        PuzzleSticker,
+                        Mesh3d(meshes.add(sticker.mesh())),
+                        MeshMaterial3d(face_materials[sticker.face].clone()),
+                    ));
+                }
+            });
+    }
+}
+
+/// Replace the pieces of the puzzle whenever a different puzzle is picked.
+pub fn respawn_puzzle(
+    mut commands: Commands,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+    puzzle: Res<Puzzle>,
+    mut twists: ResMut<Rotations<Twist>>,
+    mut is_cube_solved: ResMut<IsCubeSolved>,
+    pieces: Query<Entity, With<PuzzlePiece>>,
+) {
+    if !puzzle.is_changed() || puzzle.is_added() {
+        return;
+    }
+
+    twists.clear();
+    for piece in &pieces {
+        commands.entity(piece).despawn();
+    }
+    if let Some(definition) = puzzle.definition() {
+        spawn_puzzle(&mut commands, &mut meshes, &mut materials, &definition);
+        commands.insert_resource(PuzzleState::solved(&definition));
+        is_cube_solved.0 = true;
+    }
+}
+
+/// Animate queued twists of the puzzle, like `apply_rotations` does for the cube.
+pub fn apply_twists(
+    time: Res<Time>,
+    mut rotation_timer: ResMut<RotationTimer>,
+    mut twists: ResMut<Rotations<Twist>>,
+    state: Res<PuzzleState>,
+    mut pieces: Query<(&PuzzlePiece, &mut Transform)>,
+    mut completed: EventWriter<RotationCompleted<Twist>>,
+) {
+    if let Some(twist) = twists.current().cloned()
+        && let Some(axis) = state.axes.get(twist.axis)
+    {
+        let step = twists.step(time.delta_secs());
+        for (piece, mut transform) in &mut pieces {
+            if twist
+                .layers
+                .contains(&axis.layer(transform.rotation * piece.home))
+            {
+                transform.rotate_around(Vec3::ZERO, Quat::from_axis_angle(axis.normal, step));
+
+                // keep the rotation from drifting away from a pure rotation over many twists
+                if twists.is_current_finished() {
+                    transform.rotation = transform.rotation.normalize();
+                }
+            }
+        }
+
+        if let Some(twist) = twists.take_finished() {
+            completed.write(RotationCompleted(twist));
+        }
+    }
+
+    twists.load_next_rotation(&mut rotation_timer, time.delta());
+}
+
+/// Keep the puzzle's state in step with each completed twist, and check whether it is solved.
+pub fn record_twists(
+    mut completed: EventReader<RotationCompleted<Twist>>,
+    mut state: ResMut<PuzzleState>,
+    mut is_cube_solved: ResMut<IsCubeSolved>,
+) {
+    for RotationCompleted(twist) in completed.read() {
+        state.apply(twist);
+        is_cube_solved.0 = state.is_solved();
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_split_polygon() {
+        let square = vec![
+            Vec3::new(-1.0, -1.0, 0.0),
+            Vec3::new(1.0, -1.0, 0.0),
+            Vec3::new(1.0, 1.0, 0.0),
+            Vec3::new(-1.0, 1.0, 0.0),
+        ];
+        let [above, below] = split(&square, Vec3::X, 0.5);
+        assert_eq!(normal(&above).length(), 2.0);
+        assert_eq!(normal(&below).length(), 6.0);
+
+        // a plane along an edge leaves the whole polygon on one side
+        let [above, below] = split(&square, Vec3::X, 1.0);
+        assert!(above.is_empty());
+        assert_eq!(below, square);
+    }
+}
@@ -0,0 +1,206 @@
+use bevy::prelude::*;
+use rand::Rng;
+
+use crate::{
+    algorithm::NotationError,
+    cubie::FaceColor,
+    puzzle::{AxisDefinition, FaceDefinition, PuzzleDefinition, Twist},
+};
+
+/// How far each tip is from the centre, which makes the pyraminx about as big as a 3x3x3.
+const RADIUS: f32 = 2.5;
+
+/// Every turn of the pyraminx is a third of a whole turn.
+const ORDER: u32 = 3;
+
+/// The letter for each tip, named after where it is: up, left, right and back.
+const TIPS: [char; 4] = ['U', 'L', 'R', 'B'];
+
+/// The number of layer turns in a scramble, as in WCA scrambles, before the tips are turned.
+const SCRAMBLE_LENGTH: usize = 11;
+
+/// The corners of the tetrahedron, in the same order as `TIPS`. One face points towards the
+/// front and one points down.
+fn corners() -> [Vec3; 4] {
+    let low = -RADIUS / 3.0;
+    let out = RADIUS * 8.0_f32.sqrt() / 3.0;
+    let side = out * 3.0_f32.sqrt() / 2.0;
+    [
+        Vec3::new(0.0, RADIUS, 0.0),
+        Vec3::new(-side, low, out / 2.0),
+        Vec3::new(side, low, out / 2.0),
+        Vec3::new(0.0, low, -out),
+    ]
+}
+
+/// A tetrahedron cut twice across each corner, so that every edge is cut into thirds. The cut
+/// nearest each corner frees the tip, and the other frees the layer the tip sits on.
+pub fn definition() -> PuzzleDefinition {
+    let corners = corners();
+    // each face is opposite one of the corners, so the face opposite the up tip is on the bottom
+    let colors = [
+        FaceColor::Yellow,
+        FaceColor::Blue,
+        FaceColor::Red,
+        FaceColor::Green,
+    ];
+    let faces = colors
+        .iter()
+        .enumerate()
+        .map(|(opposite, color)| {
+            let vertices = (0..corners.len())
+                .filter(|corner| *corner != opposite)
+                .map(|corner| corners[corner])
+                .collect();
+            FaceDefinition::new(vertices, color.color())
+        })
+        .collect();
+
+    // the faces are a third as far from the centre as the corners are
+    let axes = corners
+        .iter()
+        .map(|corner| AxisDefinition {
+            normal: corner.normalize(),
+            cuts: vec![RADIUS * 5.0 / 9.0, RADIUS / 9.0],
+            order: ORDER,
+        })
+        .collect();
+
+    PuzzleDefinition { faces, axes }
+}
+
+/// Read pyraminx notation. `U`, `L`, `R` and `B` turn a tip along with the layer it sits on,
+/// lowercase letters turn just the tip, and `'` turns anticlockwise. Whitespace between moves is
+/// optional.
+pub fn parse(s: &str) -> Result<Vec<Twist>, NotationError> {
+    let mut twists = Vec::new();
+    let mut chars = s.chars().peekable();
+
+    while let Some(letter) = chars.next() {
+        if letter.is_whitespace() {
+            continue;
+        }
+
+        let axis = TIPS
+            .iter()
+            .position(|tip| *tip == letter.to_ascii_uppercase())
+            .ok_or(if letter.is_alphabetic() {
+                NotationError::UnknownMove(letter)
+            } else {
+                NotationError::UnexpectedCharacter(letter)
+            })?;
+        let layers = if letter.is_lowercase() { 0..=0 } else { 0..=1 };
+        let turns = if chars.next_if(|c| matches!(c, '\'' | '’')).is_some() {
+            -1
+        } else {
+            1
+        };
+
+        twists.push(Twist::new(axis, layers, turns, ORDER));
+    }
+
+    Ok(twists)
+}
+
+/// A random scramble: layer turns that never turn the same layer twice in a row, followed by
+/// a random turn of each tip.
+pub fn scramble() -> Vec<Twist> {
+    let mut rng = rand::rng();
+    let random_turn = |rng: &mut rand::rngs::ThreadRng| if rng.random() { 1 } else { -1 };
+
+    let mut twists: Vec<Twist> = Vec::new();
+    while twists.len() < SCRAMBLE_LENGTH {
+        let axis = rng.random_range(0..TIPS.len());
+        if twists.last().is_none_or(|last| last.axis() != axis) {
+            twists.push(Twist::new(axis, 0..=1, random_turn(&mut rng), ORDER));
+        }
+    }
+
+    for axis in 0..TIPS.len() {
+        if rng.random_ratio(2, 3) {
+            twists.push(Twist::new(axis, 0..=0, random_turn(&mut rng), ORDER));
+        }
+    }
+
+    twists
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::puzzle::{PuzzleState, pieces};
+
+    #[test]
+    fn test_pieces() {
+        let pieces = pieces(&definition());
+        let sticker_counts = [2, 3].map(|stickers| {
+            pieces
+                .iter()
+                .filter(|piece| piece.len() == stickers)
+                .count()
+        });
+
+        // 6 edges, then 4 tips and 4 centres
+        assert_eq!(sticker_counts, [6, 8]);
+        assert_eq!(pieces.len(), 14);
+    }
+
+    #[test]
+    fn test_notation() {
+        let twists = parse("U l' Rb'").unwrap();
+        assert_eq!(
+            twists,
+            [
+                Twist::new(0, 0..=1, 1, ORDER),
+                Twist::new(1, 0..=0, -1, ORDER),
+                Twist::new(2, 0..=1, 1, ORDER),
+                Twist::new(3, 0..=0, -1, ORDER),
+            ]
+        );
+        assert_eq!(parse("U D"), Err(NotationError::UnknownMove('D')));
+        assert_eq!(parse("U2"), Err(NotationError::UnexpectedCharacter('2')));
+    }
+
+    #[test]
+    fn test_solved_check() {
+        let definition = definition();
+        let mut state = PuzzleState::solved(&definition);
+        assert!(state.is_solved());
+
+        for twist in parse("U U").unwrap() {
+            state.apply(&twist);
+            assert!(!state.is_solved());
+        }
+        state.apply(&parse("U").unwrap()[0]);
+        assert!(state.is_solved());
+
+        // a tip on its own still counts
+        state.apply(&parse("r'").unwrap()[0]);
+        assert!(!state.is_solved());
+        state.apply(&parse("r").unwrap()[0]);
+
+        // two more turns of each twist undo it
+        let scramble = scramble();
+        for twist in &scramble {
+            state.apply(twist);
+        }
+        for twist in scramble.iter().rev() {
+            state.apply(twist);
+            state.apply(twist);
+        }
+        assert!(state.is_solved());
+    }
+
+    #[test]
+    fn test_sexy_move_on_the_pyraminx() {
+        // R U R' U' comes back to solved after turning it three times
+        let algorithm = parse("R U R' U'").unwrap();
+        let mut state = PuzzleState::solved(&definition());
+        for i in 1..=3 {
+            for twist in &algorithm {
+                state.apply(twist);
+            }
+            assert_eq!(state.is_solved(), i == 3);
+        }
+    }
+}
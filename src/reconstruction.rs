@@ -0,0 +1,334 @@
+use bevy::prelude::*;
+
+use crate::{
+    algorithm::Algorithm,
+    cube::CubeSize,
+    cubie::Kind,
+    rotation::Rotation,
+    state::{CubeState, SolvedCheck},
+};
+
+/// Reconstructions are shared as links to alg.cubing.net, which plays them back.
+const ALG_CUBING_URL: &str = "https://alg.cubing.net/";
+
+/// The CFOP steps a 3x3x3 solve is split into, in order.
+const CFOP_STEPS: [&str; 7] = ["Cross", "F2L 1", "F2L 2", "F2L 3", "F2L 4", "OLL", "PLL"];
+
+/// Other cubes aren't split into steps.
+const WHOLE_SOLVE: [&str; 1] = ["Solve"];
+
+/// Part of a solve, e.g. the cross.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Step {
+    pub name: String,
+    pub moves: Algorithm,
+    /// Seconds from the end of the previous step to the last move of this one.
+    pub duration: f64,
+}
+
+/// A record of a solve: how the cube was scrambled and the moves used to solve it, split into
+/// steps.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Reconstruction {
+    /// The size of the cube solved, which decides how the moves are written.
+    pub cube_size: CubeSize,
+    pub scramble: Algorithm,
+    pub steps: Vec<Step>,
+}
+
+impl Reconstruction {
+    /// Split `solution` into steps. `times` holds when each move of the solution was made, and
+    /// `solved` decides when the solve is finished.
+    pub fn new(
+        cube_size: CubeSize,
+        solved: SolvedCheck,
+        scramble: &[Rotation],
+        solution: &[Rotation],
+        times: &[f64],
+    ) -> Self {
+        let step_names: &[&str] = if cube_size == CubeSize::cube(3) {
+            &CFOP_STEPS
+        } else {
+            &WHOLE_SOLVE
+        };
+        let mut state = CubeState::solved(cube_size);
+        for rotation in scramble {
+            state.apply(rotation);
+        }
+
+        let time_after = |moves: usize| {
+            moves
+                .checked_sub(1)
+                .and_then(|i| times.get(i))
+                .or(times.first())
+                .copied()
+                .unwrap_or(0.0)
+        };
+
+        let mut steps = Vec::new();
+        let mut cross_face = None;
+        let mut reached = 0;
+        let mut step_start = 0;
+        for moves in 0..=solution.len() {
+            // several steps can finish on the same move, which leaves the later ones empty
+            let progress = if solved.is_solved(&state) {
+                step_names.len()
+            } else if step_names == WHOLE_SOLVE {
+                0
+            } else {
+                cfop_progress(&state, &mut cross_face)
+            };
+            for name in &step_names[reached..progress.max(reached)] {
+                steps.push(Step {
+                    name: name.to_string(),
+                    moves: Algorithm::new(solution[step_start..moves].to_vec()),
+                    duration: time_after(moves) - time_after(step_start),
+                });
+                step_start = moves;
+            }
+            reached = reached.max(progress);
+
+            if let Some(rotation) = solution.get(moves) {
+                state.apply(rotation);
+            }
+        }
+
+        if step_start < solution.len() {
+            let name = match step_names.get(reached) {
+                Some(name) => format!("{name} (unfinished)"),
+                None => "After solving".to_string(),
+            };
+            steps.push(Step {
+                name,
+                moves: Algorithm::new(solution[step_start..].to_vec()),
+                duration: time_after(solution.len()) - time_after(step_start),
+            });
+        }
+
+        Self {
+            cube_size,
+            scramble: Algorithm::new(scramble.to_vec()),
+            steps,
+        }
+    }
+
+    /// How many moves the solve took, in the half turn metric. See `Algorithm::move_count`.
+    pub fn move_count(&self) -> usize {
+        self.steps.iter().map(|step| step.moves.move_count()).sum()
+    }
+
+    pub fn duration(&self) -> f64 {
+        self.steps.iter().map(|step| step.duration).sum()
+    }
+
+    /// A plain text reconstruction with one line per step and a summary at the end. Moves are
+    /// counted in the half turn metric, so `U2` is one move.
+    pub fn text(&self) -> String {
+        let mut lines = vec![
+            format!("Scramble: {}", self.scramble.notation(&self.cube_size)),
+            String::new(),
+        ];
+        for step in &self.steps {
+            lines.push(format!(
+                "{} // {} ({} moves, {:.2}s)",
+                step.moves.notation(&self.cube_size),
+                step.name,
+                step.moves.move_count(),
+                step.duration
+            ));
+        }
+
+        let duration = self.duration();
+        let tps = if duration > 0.0 {
+            self.move_count() as f64 / duration
+        } else {
+            0.0
+        };
+        lines.push(String::new());
+        lines.push(format!(
+            "{} moves (HTM) in {duration:.2}s ({tps:.2} TPS)",
+            self.move_count()
+        ));
+
+        lines.join("\n")
+    }
+
+    /// A link to the reconstruction on alg.cubing.net.
+    pub fn url(&self) -> String {
+        let solution = self
+            .steps
+            .iter()
+            .map(|step| format!("{} // {}", step.moves.notation(&self.cube_size), step.name))
+            .collect::<Vec<_>>()
+            .join("\n");
+
+        format!(
+            "{ALG_CUBING_URL}?setup={}&alg={}&type=reconstruction",
+            encode(&self.scramble.notation(&self.cube_size)),
+            encode(&solution)
+        )
+    }
+}
+
+/// How many CFOP steps are done on an unsolved 3x3x3, from 0 to 6. The cross can be on any face,
+/// so until a cross is found `cross_face` is `None` and is then set to the face it was found on.
+/// Positions are in half cubies, so the face layer is 2 away from the centre.
+fn cfop_progress(state: &CubeState, cross_face: &mut Option<IVec3>) -> usize {
+    let is_solved = |home: IVec3| state.piece(home).is_some_and(|piece| piece.is_solved());
+    let cross_edges = |face: IVec3| {
+        [IVec3::X, IVec3::Y, IVec3::Z]
+            .into_iter()
+            .filter(move |axis| axis.dot(face) == 0)
+            .flat_map(move |axis| [(face + axis) * 2, (face - axis) * 2])
+    };
+
+    if cross_face.is_none() {
+        *cross_face = [IVec3::X, IVec3::Y, IVec3::Z]
+            .into_iter()
+            .flat_map(|axis| [axis, -axis])
+            .find(|face| cross_edges(*face).all(is_solved));
+    }
+    let Some(face) = *cross_face else {
+        return 0;
+    };
+    if !cross_edges(face).all(is_solved) {
+        return 0;
+    }
+
+    // each corner on the cross face pairs with the middle layer edge next to it
+    let corners = state
+        .pieces()
+        .iter()
+        .filter(|piece| piece.home().dot(face) == 2 && piece.kind() == Kind::Corner);
+    let pairs = corners
+        .filter(|corner| corner.is_solved() && is_solved(corner.home() - face * 2))
+        .count();
+    if pairs < 4 {
+        return 1 + pairs;
+    }
+
+    // orienting the last layer means every piece in it shows the last layer color on top
+    let oriented = state
+        .pieces()
+        .iter()
+        .filter(|piece| piece.home().dot(face) == -2)
+        .all(|piece| piece.orientation().apply(-face) == -face);
+
+    if oriented { 6 } else { 5 }
+}
+
+/// Encode notation for an alg.cubing.net URL, which writes spaces as `_` and primes as `-`.
+fn encode(text: &str) -> String {
+    text.chars()
+        .map(|c| match c {
+            ' ' => "_".to_string(),
+            '\'' => "-".to_string(),
+            c if c.is_ascii_alphanumeric() || c == '.' => c.to_string(),
+            c => c
+                .to_string()
+                .bytes()
+                .map(|byte| format!("%{byte:02X}"))
+                .collect(),
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn rotations(notation: &str, size: usize) -> Vec<Rotation> {
+        let algorithm = notation.parse::<Algorithm>().unwrap();
+        algorithm
+            .for_size(&CubeSize::cube(size))
+            .unwrap()
+            .rotations()
+            .to_vec()
+    }
+
+    #[test]
+    fn test_steps_are_split_at_cfop_stages() {
+        // undoing a scramble that only breaks the last layer goes straight from F2L to solved
+        let scramble = rotations("R U R' U R U2 R'", 3);
+        let solution = rotations("R U2 R' U' R U' R'", 3);
+        let times = (0..solution.len()).map(|i| i as f64).collect::<Vec<_>>();
+        let reconstruction = Reconstruction::new(
+            CubeSize::cube(3),
+            SolvedCheck::default(),
+            &scramble,
+            &solution,
+            &times,
+        );
+
+        let names = reconstruction
+            .steps
+            .iter()
+            .map(|step| step.name.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            names,
+            ["Cross", "F2L 1", "F2L 2", "F2L 3", "F2L 4", "OLL", "PLL"]
+        );
+        assert_eq!(reconstruction.steps[5].moves.rotations().len(), 8);
+        // the U2 is a single move
+        assert_eq!(reconstruction.move_count(), 7);
+        assert_eq!(reconstruction.duration(), 7.0);
+    }
+
+    #[test]
+    fn test_url() {
+        let reconstruction = Reconstruction::new(
+            CubeSize::cube(3),
+            SolvedCheck::default(),
+            &rotations("R U'", 3),
+            &rotations("U R'", 3),
+            &[0.0, 1.0],
+        );
+        let url = reconstruction.url();
+        assert!(url.starts_with("https://alg.cubing.net/?setup=R_U-&alg=U_%2F%2F_Cross%0A"));
+        assert!(url.ends_with("%0AR-_%2F%2F_PLL&type=reconstruction"));
+    }
+
+    #[test]
+    fn test_other_sizes_are_one_step() {
+        let reconstruction = Reconstruction::new(
+            CubeSize::cube(4),
+            SolvedCheck::default(),
+            &rotations("Rw U", 4),
+            &rotations("U' Rw'", 4),
+            &[0.0, 1.0],
+        );
+        assert_eq!(reconstruction.steps.len(), 1);
+        assert_eq!(reconstruction.steps[0].name, "Solve");
+    }
+
+    #[test]
+    fn test_void_cubes_are_solved_without_their_centres() {
+        // the edges and corners are put back with the centres left turned, which only matters
+        // when there are centres
+        let void_cube = SolvedCheck {
+            void_cube: true,
+            ..default()
+        };
+        let scramble = rotations("S2", 3);
+        let solution = rotations("S' D2' S U2 S' D2 S2 D2' S' U2' S D2 S'", 3);
+        let mut state = CubeState::solved(CubeSize::cube(3));
+        for rotation in scramble.iter().chain(&solution) {
+            state.apply(rotation);
+        }
+        assert!(void_cube.is_solved(&state));
+        assert!(!SolvedCheck::default().is_solved(&state));
+
+        let reconstruction =
+            Reconstruction::new(CubeSize::cube(3), void_cube, &scramble, &solution, &[0.0]);
+        assert_eq!(reconstruction.steps.len(), 7);
+        assert_eq!(reconstruction.steps[6].name, "PLL");
+        assert_eq!(reconstruction.move_count(), 13);
+        assert!(
+            reconstruction
+                .steps
+                .iter()
+                .all(|step| !step.name.contains("unfinished"))
+        );
+    }
+}
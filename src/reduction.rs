@@ -0,0 +1,1051 @@
+use std::{
+    cmp::Reverse,
+    collections::{BinaryHeap, HashMap},
+    sync::OnceLock,
+};
+
+use bevy::prelude::*;
+
+use crate::{
+    algorithm::Algorithm,
+    cube::{CubeSize, Face},
+    cubie::Kind,
+    rotation::{Direction, Rotation},
+    state::{CubeState, Orientation, Piece, sticker_normals, turn},
+};
+
+/// The largest cube that can be solved. Pieces are tracked in 128 bit masks while looking for
+/// cycles, and a 6x6x6 has too many pieces for that.
+pub const MAX_SOLVED_SIZE: usize = 5;
+
+/// Big cubes are reduced to a 3x3x3, where each corner, edge and centre stands for a block of
+/// pieces on the big cube.
+const REDUCED_SIZE: CubeSize = CubeSize::cube(3);
+
+/// How many cycles to keep for each orbit. More cycles means shorter setups, but a longer wait
+/// while they're found.
+const CYCLES_PER_ORBIT: usize = 300;
+
+/// How many cycles to try when looking for one that also turns a piece the right way.
+const ORIENTATION_TRIES: usize = 40;
+
+/// Flips one 4x4x4 edge in place, which can't be done on a 3x3x3.
+const OLL_PARITY: &str = "2R2 B2 U2 2L U2 2R' U2 2R U2 F2 2R F2 2L' B2 2R2";
+
+/// Swaps two 4x4x4 edges, which can't be done on a 3x3x3.
+const PLL_PARITY: &str = "2R2 U2 2R2 Uw2 2R2 Uw2";
+
+/// Short algorithms that turn the fixed centres of a 3x3x3 in place and put every other piece
+/// back. They turn, in order: the U centre anticlockwise and the R centre clockwise, the U centre
+/// a half turn, the F and B centres a half turn each, and the L centre clockwise and the R centre
+/// anticlockwise. Every other centre twist is one of these from another side of the cube, or
+/// several of them one after the other.
+const CENTRE_TWISTS: [&str; 4] = [
+    "U M' E M U' M' E' M",
+    "R L U2 R' L' U R L U2 R' L' U",
+    "S2 E B2 E' S2 E B2 E'",
+    "S2 R S2 R' S2 R S2 R' S2 R S2 R'",
+];
+
+/// Sent back when the last two pieces of an orbit need swapping, which 3-cycles can't do.
+#[derive(Debug)]
+struct Stuck;
+
+/// Pieces that can be moved into each other's places, e.g. the corners, are in the same orbit.
+/// Orbits are told apart by the sizes of a position's coordinates.
+fn orbit(position: IVec3) -> [i32; 3] {
+    let mut coordinates = position.abs().to_array();
+    coordinates.sort();
+    coordinates
+}
+
+fn inverse(moves: &[Rotation]) -> Vec<Rotation> {
+    moves.iter().rev().map(Rotation::inverse).collect()
+}
+
+/// Put move sequences one after another, cancelling out moves that undo each other.
+fn join(parts: &[&[Rotation]]) -> Vec<Rotation> {
+    let mut moves: Vec<Rotation> = Vec::new();
+    for rotation in parts.iter().flat_map(|part| part.iter()) {
+        if moves.last() == Some(&rotation.inverse()) {
+            moves.pop();
+        } else {
+            moves.push(rotation.clone());
+        }
+    }
+    moves
+}
+
+/// Write inner layer turns as wide turns, e.g. `2R` as `Rw R'`, since wide turns are easier to
+/// make by hand. The middle slice of an odd cube is left alone, since it has its own letter.
+fn wide_turns(moves: &[Rotation], size: &CubeSize) -> Vec<Rotation> {
+    let wide = |rotation: &Rotation, layers| {
+        if rotation.is_half_turn() {
+            Rotation::half_turn(
+                rotation.face().clone(),
+                layers,
+                rotation.direction().clone(),
+            )
+        } else {
+            Rotation::new(
+                rotation.face().clone(),
+                layers,
+                rotation.direction().clone(),
+            )
+        }
+    };
+    let moves = moves
+        .iter()
+        .flat_map(|rotation| {
+            let (first, last) = (*rotation.layers().start(), *rotation.layers().end());
+            let axis = rotation.face().normal().as_ivec3();
+            let is_slice = first == last && size.middle_layer(axis) == Some(first);
+            if first == 0 || is_slice || last + 1 == size.layers(axis) {
+                vec![rotation.clone()]
+            } else {
+                vec![
+                    wide(rotation, 0..=last),
+                    wide(rotation, 0..=first - 1).inverse(),
+                ]
+            }
+        })
+        .collect::<Vec<_>>();
+    join(&[&moves])
+}
+
+/// Where the piece at `position` ends up after `moves`.
+fn moved(moves: &[Rotation], position: IVec3, size: &CubeSize) -> IVec3 {
+    moves.iter().fold(position, |position, rotation| {
+        turn(rotation, position, size).map_or(position, |turn| turn.apply(position))
+    })
+}
+
+fn apply(state: &mut CubeState, moves: &[Rotation]) {
+    for rotation in moves {
+        state.apply(rotation);
+    }
+}
+
+/// Every turn of a single layer, as quarter turns. Each layer is turned from the face it's
+/// closest to.
+fn turns(size: &CubeSize) -> Vec<Vec<Rotation>> {
+    let mut turns = Vec::new();
+    for face in [Face::Top, Face::Right, Face::Front] {
+        for layer in 0..size.largest() {
+            let quarter = if layer < size.largest().div_ceil(2) {
+                Rotation::new(face.clone(), layer..=layer, Direction::Backward)
+            } else {
+                let layer = size.largest() - 1 - layer;
+                Rotation::new(face.opposite(), layer..=layer, Direction::Forward)
+            };
+            turns.push(vec![quarter.clone()]);
+            turns.push(vec![quarter.inverse()]);
+            turns.push(vec![quarter.clone(), quarter]);
+        }
+    }
+    turns
+}
+
+/// The axis a sequence of turns of one layer turns around.
+fn axis(turn: &[Rotation]) -> IVec3 {
+    turn[0].face().normal().as_ivec3().abs()
+}
+
+/// The third position of a 3-cycle, and the moves that do it.
+type Cycle = (IVec3, Vec<Rotation>);
+
+/// Ways of cycling three pieces of an orbit that leave every other piece alone, found by
+/// searching for commutators of sequences that only move one piece in common.
+struct Cycles {
+    size: CubeSize,
+    /// Moves that can be done before a cycle, and undone after, to change which pieces it cycles.
+    setups: Vec<Vec<Rotation>>,
+    /// Moves that take the piece at `from` to `to`, keyed by `(from, to)`. The piece at `to`
+    /// moves on to the third position given, and the piece there moves to `from`.
+    cycles: HashMap<(IVec3, IVec3), Vec<Cycle>>,
+}
+
+impl Cycles {
+    fn new(size: CubeSize) -> Self {
+        let turns = turns(&size);
+        let mut setups = vec![Vec::new()];
+        let mut candidates = turns.clone();
+        for first in &turns {
+            setups.push(first.clone());
+            for second in turns.iter().filter(|second| axis(second) != axis(first)) {
+                setups.push(join(&[first, second]));
+                candidates.push(join(&[first, second, &inverse(first)]));
+            }
+        }
+
+        // the pieces each candidate moves, as a mask of indices into `CubeState::pieces`
+        let solved = CubeState::solved(size);
+        let supports = candidates
+            .iter()
+            .map(|moves| {
+                let mut state = solved.clone();
+                apply(&mut state, moves);
+                state
+                    .pieces()
+                    .iter()
+                    .enumerate()
+                    .filter(|(_, piece)| !piece.is_solved())
+                    .fold(0u128, |mask, (i, _)| mask | 1 << i)
+            })
+            .collect::<Vec<_>>();
+
+        let mut cycles: HashMap<_, Vec<_>> = HashMap::new();
+        let mut found: HashMap<[i32; 3], usize> = HashMap::new();
+        for (i, (a, a_support)) in candidates.iter().zip(&supports).enumerate() {
+            for (b, b_support) in candidates[i + 1..].iter().zip(&supports[i + 1..]) {
+                // the commutator of two sequences with one piece in common cycles three pieces
+                let common = a_support & b_support;
+                if common.count_ones() != 1 {
+                    continue;
+                }
+                let common = solved.pieces()[common.trailing_zeros() as usize].home();
+                let found = found.entry(orbit(common)).or_default();
+                if *found >= CYCLES_PER_ORBIT {
+                    continue;
+                }
+
+                let moves = join(&[a, b, &inverse(a), &inverse(b)]);
+                let mut state = solved.clone();
+                apply(&mut state, &moves);
+                let unsolved = state
+                    .pieces()
+                    .iter()
+                    .filter(|piece| !piece.is_solved())
+                    .collect::<Vec<_>>();
+                let [first, ..] = unsolved[..] else {
+                    continue;
+                };
+                if unsolved.len() != 3
+                    || unsolved
+                        .iter()
+                        .any(|piece| piece.position() == piece.home())
+                {
+                    continue;
+                }
+                *found += 1;
+
+                let (from, to) = (first.home(), first.position());
+                let third = state.piece(to).expect("every home has a piece").position();
+                let undo = inverse(&moves);
+                for (from, to, third) in [(from, to, third), (to, third, from), (third, from, to)] {
+                    cycles
+                        .entry((from, to))
+                        .or_default()
+                        .push((third, moves.clone()));
+                    cycles
+                        .entry((from, third))
+                        .or_default()
+                        .push((to, undo.clone()));
+                }
+            }
+        }
+
+        Self {
+            size,
+            setups,
+            cycles,
+        }
+    }
+
+    /// Every way found of moving the piece at `from` to `to`, as the third position cycled along
+    /// with the setup and cycle moves.
+    fn cycles(
+        &self,
+        from: IVec3,
+        to: IVec3,
+    ) -> impl Iterator<Item = (IVec3, &[Rotation], &[Rotation])> + '_ {
+        self.setups.iter().flat_map(move |setup| {
+            let key = (moved(setup, from, &self.size), moved(setup, to, &self.size));
+            self.cycles
+                .get(&key)
+                .into_iter()
+                .flatten()
+                .map(move |(third, moves)| {
+                    let third = moved(&inverse(setup), *third, &self.size);
+                    (third, setup.as_slice(), moves.as_slice())
+                })
+        })
+    }
+
+    /// Put a piece that `belongs` in each slot, in order, with 3-cycles. If `oriented` is set
+    /// the pieces are then turned the right way too.
+    fn solve_orbit(
+        &self,
+        state: &mut CubeState,
+        solution: &mut Vec<Rotation>,
+        slots: &[IVec3],
+        belongs: impl Fn(&CubeState, IVec3, &Piece) -> bool,
+        oriented: bool,
+    ) -> Result<(), Stuck> {
+        // whether the piece at `from` belongs at `to`
+        let fits = |state: &CubeState, from: IVec3, to: IVec3| {
+            belongs(
+                state,
+                to,
+                state.piece_at(from).expect("every slot has a piece"),
+            )
+        };
+
+        let mut done: Vec<IVec3> = Vec::new();
+        for &slot in slots {
+            if !fits(state, slot, slot) {
+                let open = slots
+                    .iter()
+                    .copied()
+                    .filter(|open| *open != slot && !done.contains(open))
+                    .collect::<Vec<_>>();
+                let moves = self
+                    .place(state, slot, &open, &done, &fits, oriented)
+                    .ok_or(Stuck)?;
+                apply(state, &moves);
+                solution.extend(moves);
+            }
+            done.push(slot);
+        }
+
+        if oriented {
+            self.orient(state, solution, slots)?;
+        }
+        Ok(())
+    }
+
+    /// Moves that bring a piece that fits into `slot`, only disturbing the `open` slots.
+    fn place(
+        &self,
+        state: &CubeState,
+        slot: IVec3,
+        open: &[IVec3],
+        done: &[IVec3],
+        fits: &impl Fn(&CubeState, IVec3, IVec3) -> bool,
+        oriented: bool,
+    ) -> Option<Vec<Rotation>> {
+        let mut fallback = None;
+        for &from in open.iter().filter(|from| fits(state, **from, slot)) {
+            let cycles = self
+                .cycles(from, slot)
+                .filter(|(third, ..)| *third != from && open.contains(third))
+                .take(ORIENTATION_TRIES);
+            for (_, setup, cycle) in cycles {
+                let moves = join(&[setup, cycle, &inverse(setup)]);
+                if !oriented {
+                    return Some(moves);
+                }
+
+                // prefer moves that leave the piece turned the right way
+                let mut after = state.clone();
+                apply(&mut after, &moves);
+                if after.piece_at(slot).is_some_and(Piece::is_solved) {
+                    return Some(moves);
+                }
+                fallback.get_or_insert(moves);
+            }
+        }
+        if fallback.is_some() {
+            return fallback;
+        }
+
+        // with two slots left, a piece that fits can be borrowed from a slot that's done as long
+        // as the piece here can go in the other open slot and that one's piece can go back
+        let [other] = open[..] else {
+            return None;
+        };
+        done.iter()
+            .copied()
+            .filter(|from| {
+                fits(state, *from, slot) && fits(state, slot, other) && fits(state, other, *from)
+            })
+            .find_map(|from| {
+                self.cycles(from, slot)
+                    .find(|(third, ..)| *third == other)
+                    .map(|(_, setup, cycle)| join(&[setup, cycle, &inverse(setup)]))
+            })
+    }
+
+    /// Turn pieces that are in place the right way, two at a time. Each piece is cycled away
+    /// and back again, which can leave it turned differently.
+    fn orient(
+        &self,
+        state: &mut CubeState,
+        solution: &mut Vec<Rotation>,
+        slots: &[IVec3],
+    ) -> Result<(), Stuck> {
+        let solved_count = |state: &CubeState| {
+            slots
+                .iter()
+                .filter(|slot| state.piece_at(**slot).is_some_and(Piece::is_solved))
+                .count()
+        };
+        let cycles = |from, to, third| {
+            self.cycles(from, to)
+                .filter(move |(other, ..)| *other == third)
+                .take(ORIENTATION_TRIES)
+                .map(|(_, setup, cycle)| join(&[setup, cycle, &inverse(setup)]))
+                .collect::<Vec<_>>()
+        };
+
+        while solved_count(state) < slots.len() {
+            let mut unsolved = slots
+                .iter()
+                .copied()
+                .filter(|slot| !state.piece_at(*slot).is_some_and(Piece::is_solved));
+            let (Some(first), Some(second)) = (unsolved.next(), unsolved.next()) else {
+                return Err(Stuck);
+            };
+
+            let before = solved_count(state);
+            let moves = slots
+                .iter()
+                .copied()
+                .filter(|third| *third != first && *third != second)
+                .find_map(|third| {
+                    let away = cycles(first, second, third);
+                    let back = cycles(second, first, third);
+                    away.iter()
+                        .flat_map(|away| back.iter().map(move |back| join(&[away, back])))
+                        .find(|moves| {
+                            let mut after = state.clone();
+                            apply(&mut after, moves);
+                            solved_count(&after) > before
+                        })
+                })
+                .ok_or(Stuck)?;
+            apply(state, &moves);
+            solution.extend(moves);
+        }
+        Ok(())
+    }
+}
+
+/// Solves cubes up to `MAX_SOLVED_SIZE` by reduction: the centres are solved, then the edge
+/// pieces are paired up, and then the cube is solved like a 3x3x3 using the outer layers and
+/// the blocks of inner layers. Even cubes can reach 3x3x3 states that can't happen on a real
+/// 3x3x3, which are fixed with parity algorithms first.
+/// The cycles each stage uses are found the first time they're needed.
+#[derive(Default)]
+pub struct ReductionSolver {
+    cycles: [OnceLock<Cycles>; MAX_SOLVED_SIZE + 1],
+    centre_twists: OnceLock<Vec<CentreTwist>>,
+}
+
+impl ReductionSolver {
+    /// Whether cubes of this size can be solved. Cuboids can't, and every size this can solve
+    /// has `size.largest()` layers along each axis.
+    pub fn can_solve(size: &CubeSize) -> bool {
+        size.is_cube() && (REDUCED_SIZE.largest()..=MAX_SOLVED_SIZE).contains(&size.largest())
+    }
+
+    fn cycles(&self, size: CubeSize) -> &Cycles {
+        self.cycles[size.largest()].get_or_init(|| Cycles::new(size))
+    }
+
+    /// Solve a cube, or `None` if it's a size that can't be solved.
+    pub fn solve(&self, state: &CubeState) -> Option<Algorithm> {
+        self.solve_cube(state, false)
+    }
+
+    /// Solve a void cube, leaving its centres, which it doesn't have, wherever they end up.
+    pub fn solve_void(&self, state: &CubeState) -> Option<Algorithm> {
+        self.solve_cube(state, true)
+    }
+
+    fn solve_cube(&self, state: &CubeState, void: bool) -> Option<Algorithm> {
+        let size = state.size();
+        if !Self::can_solve(&size) {
+            return None;
+        }
+
+        let mut state = state.clone();
+        let mut solution = Vec::new();
+        if !void {
+            self.solve_fixed_centres(&mut state, &mut solution);
+        }
+        if size != REDUCED_SIZE {
+            self.reduce(&mut state, &mut solution, void).ok()?;
+        }
+        self.solve_reduced(&mut state, &mut solution, void).ok()?;
+
+        Some(Algorithm::new(wide_turns(&solution, &size)))
+    }
+
+    /// Solve a cube as a supercube, where the fixed centres have to be turned back too. They're
+    /// turned back last, since solving the rest of the cube turns them however it likes.
+    pub fn solve_supercube(&self, state: &CubeState) -> Option<Algorithm> {
+        let mut solution = self.solve(state)?.rotations().to_vec();
+        let mut state = state.clone();
+        apply(&mut state, &solution);
+        self.solve_centre_twists(&mut state, &mut solution).ok()?;
+
+        Some(Algorithm::new(wide_turns(&solution, &state.size())))
+    }
+
+    /// Turn the fixed centres of a solved odd cube back the way they started, in as few moves as
+    /// the centre twists allow.
+    fn solve_centre_twists(
+        &self,
+        state: &mut CubeState,
+        solution: &mut Vec<Rotation>,
+    ) -> Result<(), Stuck> {
+        let twists = self.centre_twists.get_or_init(CentreTwist::all);
+        let start = centre_turns(state);
+        let solved = [0; 6];
+
+        // a shortest path search through the ways the centres can be turned, of which there are
+        // only 4^6, remembering the cheapest way each one was reached
+        let mut reached = HashMap::from([(start, (0, None))]);
+        let mut queue = BinaryHeap::from([Reverse((0, start))]);
+        while let Some(Reverse((moves, turns))) = queue.pop() {
+            if turns == solved {
+                break;
+            }
+            if reached[&turns].0 < moves {
+                continue;
+            }
+            for (i, twist) in twists.iter().enumerate() {
+                let next = std::array::from_fn(|face| (turns[face] + twist.turns[face]) % 4);
+                let next_moves = moves + twist.move_count;
+                if reached
+                    .get(&next)
+                    .is_none_or(|(best, _)| next_moves < *best)
+                {
+                    reached.insert(next, (next_moves, Some((turns, i))));
+                    queue.push(Reverse((next_moves, next)));
+                }
+            }
+        }
+
+        let mut used = Vec::new();
+        let mut turns = solved;
+        while let (_, Some((before, i))) = *reached.get(&turns).ok_or(Stuck)? {
+            used.push(i);
+            turns = before;
+        }
+        let size = state.size();
+        for i in used.into_iter().rev() {
+            let moves = twists[i]
+                .moves
+                .iter()
+                .map(|rotation| unreduced(rotation, &size))
+                .collect::<Vec<_>>();
+            apply(state, &moves);
+            solution.extend(moves);
+        }
+        Ok(())
+    }
+
+    /// Turn the middle layers of an odd cube so the centres that can't move relative to each
+    /// other are back in place.
+    fn solve_fixed_centres(&self, state: &mut CubeState, solution: &mut Vec<Rotation>) {
+        let size = state.size();
+        if size.largest().is_multiple_of(2) {
+            return;
+        }
+
+        let middle = size.largest() / 2;
+        let middle_turns = turns(&size)
+            .into_iter()
+            .filter(|turn| *turn[0].layers() == (middle..=middle))
+            .collect::<Vec<_>>();
+        let fixed_centres = state
+            .pieces()
+            .iter()
+            .map(Piece::home)
+            .filter(|home| orbit(*home)[..2] == [0, 0])
+            .collect::<Vec<_>>();
+
+        // every way of turning the whole cube is at most three middle layer turns
+        let mut sequences = vec![Vec::new()];
+        let mut longest = vec![Vec::new()];
+        for _ in 0..3 {
+            longest = longest
+                .iter()
+                .flat_map(|sequence: &Vec<Rotation>| {
+                    middle_turns.iter().map(move |turn| join(&[sequence, turn]))
+                })
+                .collect();
+            sequences.extend(longest.iter().cloned());
+        }
+
+        let current = |home: IVec3| {
+            state
+                .piece(home)
+                .expect("every home has a piece")
+                .position()
+        };
+        let moves = sequences
+            .into_iter()
+            .find(|moves| {
+                fixed_centres
+                    .iter()
+                    .all(|home| moved(moves, current(*home), &size) == *home)
+            })
+            .expect("the middle layers can turn the fixed centres anywhere");
+        apply(state, &moves);
+        solution.extend(moves);
+    }
+
+    /// Solve the centres and pair up the edges, so the cube can be solved like a 3x3x3.
+    /// Void cubes have no centres to solve.
+    fn reduce(
+        &self,
+        state: &mut CubeState,
+        solution: &mut Vec<Rotation>,
+        void: bool,
+    ) -> Result<(), Stuck> {
+        let size = state.size();
+        let cycles = self.cycles(size);
+        let outer = size.largest() as i32 - 1;
+        // fixed centres and middle edges are left for the 3x3x3 stage. Edge pieces are sorted so
+        // the two on each side of an edge come one after the other.
+        let slots = |kind: Kind, moves: fn([i32; 3]) -> bool| {
+            let mut slots = state
+                .pieces()
+                .iter()
+                .map(Piece::home)
+                .filter(|home| {
+                    Kind::from_position(*home, &size) == Some(kind.clone()) && moves(orbit(*home))
+                })
+                .collect::<Vec<_>>();
+            slots.sort_by_key(|slot| {
+                let edge = slot.to_array().min(partner(*slot, outer).to_array());
+                (orbit(*slot), edge, slot.to_array())
+            });
+            slots
+        };
+        let centres = if void {
+            Vec::new()
+        } else {
+            slots(Kind::Centre, |orbit| orbit[1] != 0)
+        };
+        let wings = slots(Kind::Edge, |orbit| orbit[0] != 0);
+
+        // an odd cube's edges can end up with two pieces swapped, which a slice turn changes
+        for _ in 0..2 {
+            for orbit_slots in centres.chunk_by(|a, b| orbit(*a) == orbit(*b)) {
+                cycles.solve_orbit(state, solution, orbit_slots, same_face(&size), false)?;
+            }
+            match cycles.solve_orbit(state, solution, &wings, paired(&size), false) {
+                Err(Stuck) if size.largest() % 2 == 1 => {
+                    let slice = Rotation::new(Face::Right, 1..=1, Direction::Backward);
+                    state.apply(&slice);
+                    solution.push(slice);
+                }
+                result => return result,
+            }
+        }
+        Err(Stuck)
+    }
+
+    /// Solve a reduced cube like a 3x3x3. Odd void cubes can have two edges swapped with the
+    /// corners solved, which is fixed by turning the middle layers, since that's a move like any
+    /// other without centres to move.
+    fn solve_reduced(
+        &self,
+        state: &mut CubeState,
+        solution: &mut Vec<Rotation>,
+        void: bool,
+    ) -> Result<(), Stuck> {
+        let size = state.size();
+        let mut apply_big = |state: &mut CubeState, moves: &[Rotation]| {
+            apply(state, moves);
+            solution.extend(moves.iter().cloned());
+        };
+
+        if size.largest().is_multiple_of(2) {
+            let parity = |notation: &str| {
+                let algorithm = notation
+                    .parse::<Algorithm>()
+                    .expect("parity algorithms parse");
+                algorithm.rotations().to_vec()
+            };
+            if flips_odd(&reduced(state)) {
+                apply_big(state, &parity(OLL_PARITY));
+            }
+            let model = reduced(state);
+            if permutation_odd(&model, Kind::Corner) != permutation_odd(&model, Kind::Edge) {
+                apply_big(state, &parity(PLL_PARITY));
+            }
+        }
+        if permutation_odd(&reduced(state), Kind::Corner) {
+            apply_big(
+                state,
+                &[Rotation::new(Face::Top, 0..=0, Direction::Backward)],
+            );
+        }
+        if void && size.largest() % 2 == 1 && permutation_odd(&reduced(state), Kind::Edge) {
+            // every inner layer, so paired edge pieces stay together
+            let inner = 1..=size.largest() - 2;
+            apply_big(
+                state,
+                &[Rotation::new(Face::Right, inner, Direction::Backward)],
+            );
+        }
+
+        let mut model = reduced(state);
+        let cycles = self.cycles(REDUCED_SIZE);
+        let mut moves = Vec::new();
+        for kind in [Kind::Corner, Kind::Edge] {
+            let slots = model
+                .pieces()
+                .iter()
+                .filter(|piece| piece.kind() == kind)
+                .map(Piece::home)
+                .collect::<Vec<_>>();
+            let at_home = |_: &CubeState, slot: IVec3, piece: &Piece| piece.home() == slot;
+            cycles.solve_orbit(&mut model, &mut moves, &slots, at_home, true)?;
+        }
+
+        let moves = moves
+            .iter()
+            .map(|rotation| unreduced(rotation, &size))
+            .collect::<Vec<_>>();
+        apply_big(state, &moves);
+        Ok(())
+    }
+}
+
+/// Moves that turn some fixed centres of a 3x3x3 in place and put every other piece back. On
+/// bigger odd cubes they're made with blocks of layers, which turns the fixed centres just the
+/// same but carries the other centre pieces of each face around with them. Those have no markers
+/// and look alike, so the cube still looks solved.
+struct CentreTwist {
+    moves: Vec<Rotation>,
+    move_count: usize,
+    /// How many quarter turns forwards each fixed centre is turned, in the order of
+    /// `Face::variants`.
+    turns: [usize; 6],
+}
+
+impl CentreTwist {
+    /// Each of `CENTRE_TWISTS` from every side of the cube and undone, keeping the shortest for
+    /// each way of turning the centres.
+    fn all() -> Vec<Self> {
+        let mut views = vec![Orientation::IDENTITY];
+        while views.len() < 24 {
+            let turned = views
+                .iter()
+                .flat_map(|view| {
+                    [IVec3::X, IVec3::Y]
+                        .map(|axis| Orientation::quarter_turn(axis, true).compose(view))
+                })
+                .find(|view| !views.contains(view))
+                .expect("quarter turns around two axes reach every way of holding a cube");
+            views.push(turned);
+        }
+
+        let mut shortest: HashMap<[usize; 6], Self> = HashMap::new();
+        for twist in CENTRE_TWISTS {
+            let twist = twist
+                .parse::<Algorithm>()
+                .and_then(|twist| twist.for_size(&REDUCED_SIZE))
+                .expect("centre twists are valid notation");
+            for view in &views {
+                let moves = twist
+                    .rotations()
+                    .iter()
+                    .map(|rotation| {
+                        let normal = view.apply(rotation.face().normal().as_ivec3());
+                        let face = Face::variants()
+                            .into_iter()
+                            .find(|face| face.normal().as_ivec3() == normal)
+                            .expect("turned faces are still faces");
+                        Rotation::new(
+                            face,
+                            rotation.layers().clone(),
+                            rotation.direction().clone(),
+                        )
+                    })
+                    .collect::<Vec<_>>();
+                for moves in [inverse(&moves), moves] {
+                    let mut state = CubeState::solved(REDUCED_SIZE);
+                    apply(&mut state, &moves);
+                    let twist = Self {
+                        turns: centre_turns(&state),
+                        move_count: Algorithm::new(moves.clone()).move_count(),
+                        moves,
+                    };
+                    if shortest
+                        .get(&twist.turns)
+                        .is_none_or(|existing| twist.move_count < existing.move_count)
+                    {
+                        shortest.insert(twist.turns, twist);
+                    }
+                }
+            }
+        }
+        // in a fixed order, so the same cube is always solved the same way
+        let mut all = shortest.into_values().collect::<Vec<_>>();
+        all.sort_by_key(|twist| twist.turns);
+        all
+    }
+}
+
+/// How many quarter turns forwards each fixed centre of a cube is turned in place, in the order
+/// of `Face::variants`. Cubes without fixed centres count as not turned.
+fn centre_turns(state: &CubeState) -> [usize; 6] {
+    let outer = state.size().outer();
+    Face::variants().map(|face| {
+        let normal = face.normal().as_ivec3();
+        let Some(centre) = state.piece(normal * outer) else {
+            return 0;
+        };
+        let quarter = Orientation::quarter_turn(normal, true);
+        std::iter::successors(Some(Orientation::IDENTITY), |turned| {
+            Some(quarter.compose(turned))
+        })
+        .take(4)
+        .position(|turned| turned == centre.orientation())
+        .expect("fixed centres only turn in place")
+    })
+}
+
+/// The other edge piece that makes up an edge with the piece at `position` on a 4x4x4.
+fn partner(position: IVec3, outer: i32) -> IVec3 {
+    IVec3::from_array(position.to_array().map(|coordinate| {
+        if coordinate.abs() == outer {
+            coordinate
+        } else {
+            -coordinate
+        }
+    }))
+}
+
+/// Centres belong on the face of their color.
+fn same_face(size: &CubeSize) -> impl Fn(&CubeState, IVec3, &Piece) -> bool {
+    let size = *size;
+    move |_, slot, piece| sticker_normals(slot, &size) == piece.sticker_normals()
+}
+
+/// Edge pieces belong next to the pieces they make up an edge with. On odd cubes that's the
+/// middle edge piece, which the cycles never move. On even cubes the first piece of each edge
+/// goes in its home, or its partner's home if the partner is already there.
+fn paired(size: &CubeSize) -> impl Fn(&CubeState, IVec3, &Piece) -> bool {
+    let outer = size.largest() as i32 - 1;
+    let odd = size.largest() % 2 == 1;
+    move |state, slot, piece| {
+        let wanted = if odd {
+            let middle = IVec3::from_array(slot.to_array().map(|coordinate| {
+                if coordinate.abs() == outer {
+                    coordinate
+                } else {
+                    0
+                }
+            }));
+            let middle = state.piece_at(middle).expect("every slot has a piece");
+            middle.orientation().inverse().apply(slot)
+        } else {
+            let partner = partner(slot, outer);
+            let beside = state.piece_at(partner).expect("every slot has a piece");
+            if beside.home() == slot { partner } else { slot }
+        };
+        piece.home() == wanted
+    }
+}
+
+/// The 3x3x3 a reduced cube acts like. Corners are the same, edges follow one piece of each big
+/// edge and the centres are taken to be solved.
+fn reduced(state: &CubeState) -> CubeState {
+    let size = state.size();
+    let outer = size.largest() as i32 - 1;
+    let inner = if size.largest().is_multiple_of(2) {
+        1
+    } else {
+        0
+    };
+    let big = |coordinate: i32| match coordinate {
+        0 => inner,
+        coordinate => coordinate.signum() * outer,
+    };
+
+    CubeState::from_orientations(REDUCED_SIZE, |home| {
+        if Kind::from_position(home, &REDUCED_SIZE) == Some(Kind::Centre) {
+            return Orientation::IDENTITY;
+        }
+        let home = IVec3::from_array(home.to_array().map(big));
+        state
+            .piece(home)
+            .expect("every home has a piece")
+            .orientation()
+    })
+}
+
+/// The move on a big cube that a move of the reduced cube stands for.
+fn unreduced(rotation: &Rotation, size: &CubeSize) -> Rotation {
+    let layers = match rotation.layers().start() {
+        0 => 0..=0,
+        _ => 1..=size.largest() - 2,
+    };
+    Rotation::new(
+        rotation.face().clone(),
+        layers,
+        rotation.direction().clone(),
+    )
+}
+
+/// Whether an odd number of a reduced cube's edges are flipped. Each edge is checked by whether
+/// its up or down sticker, or front or back sticker if it has neither, is on the same axis as
+/// the one of the slot it's in.
+fn flips_odd(model: &CubeState) -> bool {
+    let main_sticker = |normals: &[IVec3]| {
+        normals
+            .iter()
+            .find(|normal| normal.y != 0)
+            .or(normals.iter().find(|normal| normal.z != 0))
+            .copied()
+            .expect("edges have a sticker facing up, down, front or back")
+    };
+
+    let flipped = model
+        .pieces()
+        .iter()
+        .filter(|piece| piece.kind() == Kind::Edge)
+        .filter(|piece| {
+            let facing = piece
+                .orientation()
+                .apply(main_sticker(piece.sticker_normals()));
+            let slot = main_sticker(&sticker_normals(piece.position(), &REDUCED_SIZE));
+            facing.abs() != slot.abs()
+        })
+        .count();
+    flipped % 2 == 1
+}
+
+/// Whether the pieces of a kind are arranged by an odd permutation.
+fn permutation_odd(model: &CubeState, kind: Kind) -> bool {
+    let pieces = model
+        .pieces()
+        .iter()
+        .filter(|piece| piece.kind() == kind)
+        .collect::<Vec<_>>();
+
+    let mut visited = vec![false; pieces.len()];
+    let mut cycles = 0;
+    for start in 0..pieces.len() {
+        if visited[start] {
+            continue;
+        }
+        cycles += 1;
+        let mut i = start;
+        while !visited[i] {
+            visited[i] = true;
+            i = pieces
+                .iter()
+                .position(|piece| piece.home() == pieces[i].position())
+                .expect("pieces of a kind fill each other's homes");
+        }
+    }
+    (pieces.len() - cycles) % 2 == 1
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::algorithm::notation;
+
+    fn solves(size: usize, scramble: &str) {
+        let solver = ReductionSolver::default();
+        let mut state = CubeState::solved(CubeSize::cube(size));
+        let scramble: Algorithm = scramble.parse().unwrap();
+        state.apply_algorithm(&scramble.for_size(&state.size()).unwrap());
+        assert!(!state.is_solved());
+
+        let solution = solver.solve(&state).unwrap();
+        state.apply_algorithm(&solution);
+        assert!(state.is_solved(), "{scramble} on {size}x{size}x{size}");
+
+        // inner layers are only turned with wide turns, apart from the middle slice
+        let middle = (size % 2 == 1).then_some(size / 2);
+        for rotation in solution.rotations() {
+            let layers = rotation.layers();
+            assert!(
+                *layers.start() == 0
+                    || (layers.start() == layers.end() && Some(*layers.start()) == middle),
+                "{} in {solution}",
+                notation(rotation, false, &state.size())
+            );
+        }
+    }
+
+    #[test]
+    fn test_solvable_sizes() {
+        assert!(!ReductionSolver::can_solve(&CubeSize::cube(2)));
+        for size in 3..=MAX_SOLVED_SIZE {
+            assert!(ReductionSolver::can_solve(&CubeSize::cube(size)));
+        }
+        for size in 6..=9 {
+            assert!(!ReductionSolver::can_solve(&CubeSize::cube(size)));
+        }
+        assert!(!ReductionSolver::can_solve(&CubeSize::cuboid(3, 3, 4)));
+
+        let state = CubeState::solved(CubeSize::cube(6));
+        assert_eq!(ReductionSolver::default().solve(&state), None);
+    }
+
+    #[test]
+    fn test_solves_3x3() {
+        solves(3, "R U2 F' L D B2 M E' S U R'");
+    }
+
+    #[test]
+    fn test_solves_4x4_with_parity() {
+        // a single inner slice turn leaves both parities to fix
+        solves(4, "2R");
+        solves(4, "Rw U2 3Fw' 2L B D' 2U Fw2 L' 2B");
+    }
+
+    #[test]
+    fn test_solves_5x5() {
+        solves(5, "2R U 3F' Lw D2 2B' Rw 3U L2 F'");
+    }
+
+    #[test]
+    fn test_solves_void_cubes() {
+        let solver = ReductionSolver::default();
+        for (size, scramble) in [(3, "M"), (3, "R U2 M' F E S2 B'"), (5, "2R U 3F' M Lw")] {
+            let mut state = CubeState::solved(CubeSize::cube(size));
+            let scramble: Algorithm = scramble.parse().unwrap();
+            state.apply_algorithm(&scramble.for_size(&state.size()).unwrap());
+            assert!(!state.is_solved_without_centres());
+
+            let solution = solver.solve_void(&state).unwrap();
+            state.apply_algorithm(&solution);
+            assert!(
+                state.is_solved_without_centres(),
+                "{scramble} on {size}x{size}x{size}"
+            );
+        }
+    }
+
+    #[test]
+    fn test_solves_supercubes() {
+        let solver = ReductionSolver::default();
+        for (size, scramble) in [
+            (3, "R U2 F' L D B2 M E' S U R'"),
+            (3, "U"),
+            (5, "2R U 3F' Lw"),
+        ] {
+            let mut state = CubeState::solved(CubeSize::cube(size));
+            let scramble: Algorithm = scramble.parse().unwrap();
+            state.apply_algorithm(&scramble.for_size(&state.size()).unwrap());
+
+            let solution = solver.solve_supercube(&state).unwrap();
+            state.apply_algorithm(&solution);
+            assert!(
+                state.is_super_solved(),
+                "{scramble} on {size}x{size}x{size}"
+            );
+        }
+
+        // a cube with only its centres turned is solved with a single short centre twist
+        let mut state = CubeState::solved(CubeSize::cube(3));
+        let twist: Algorithm = "U M' E M U' M' E' M".parse().unwrap();
+        state.apply_algorithm(&twist.for_size(&state.size()).unwrap());
+        assert!(state.is_solved() && !state.is_super_solved());
+        let solution = solver.solve_supercube(&state).unwrap();
+        assert_eq!(solution.move_count(), 8);
+    }
+}
@@ -1,4 +1,4 @@
-use std::{collections::VecDeque, f32::consts::FRAC_PI_2};
+use std::{collections::VecDeque, f32::consts::FRAC_PI_2, ops::RangeInclusive, time::Duration};
 
 use bevy::prelude::*;
 use rand::{
@@ -6,13 +6,18 @@ use rand::{
     distr::{Distribution, StandardUniform},
 };
 
-use crate::{cube::Face, cubie::Cubie};
+use crate::{
+    algorithm::notation,
+    bandage::{Bandaging, cubie_positions},
+    cube::{CubeSize, Face},
+    cubie::Cubie,
+};
 
 pub const ONE_ROTATION_RADIANS: f32 = FRAC_PI_2;
 pub const ROTATION_SPEED: f32 = 2.0;
 
 /// The direction in which a cube face should be rotated
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub enum Direction {
     Forward,
     Backward,
@@ -29,6 +34,13 @@ impl Direction {
             Self::Backward => -1.,
         }
     }
+
+    pub fn opposite(&self) -> Self {
+        match self {
+            Self::Forward => Self::Backward,
+            Self::Backward => Self::Forward,
+        }
+    }
 }
 
 impl Distribution<Direction> for StandardUniform {
@@ -40,26 +52,106 @@ impl Distribution<Direction> for StandardUniform {
     }
 }
 
-/// Describes a cube face rotation as a combination of the face to be rotated and the rotation direction
-#[derive(Clone)]
+/// Describes a cube face rotation as a combination of the face to be rotated and the rotation direction.
+/// Rotations can turn layers further into the cube than the face itself, which are counted from
+/// the face inwards starting at 0 for the face.
+/// Rotations are quarter turns, except on the faces of cuboids that only allow half turns.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
 pub struct Rotation {
     face: Face,
+    layers: RangeInclusive<usize>,
     direction: Direction,
+    half_turn: bool,
 }
 
 impl Rotation {
-    pub fn new(face: Face, direction: Direction) -> Self {
-        Self { face, direction }
+    pub fn new(face: Face, layers: RangeInclusive<usize>, direction: Direction) -> Self {
+        Self {
+            face,
+            layers,
+            direction,
+            half_turn: false,
+        }
     }
 
-    pub fn random() -> Self {
+    /// A half turn, made in one go rather than as two quarter turns. See
+    /// `CubeSize::allows_quarter_turns`.
+    pub fn half_turn(face: Face, layers: RangeInclusive<usize>, direction: Direction) -> Self {
+        Self {
+            half_turn: true,
+            ..Self::new(face, layers, direction)
+        }
+    }
+
+    /// A rotation of `layers` from `face`, as a half turn if the face can't make quarter turns on a
+    /// cube of the given size.
+    pub fn for_size(
+        face: Face,
+        layers: RangeInclusive<usize>,
+        direction: Direction,
+        cube_size: &CubeSize,
+    ) -> Self {
+        if cube_size.allows_quarter_turns(&face) {
+            Self::new(face, layers, direction)
+        } else {
+            Self::half_turn(face, layers, direction)
+        }
+    }
+
+    /// A random single layer rotation on a cube of the given size.
+    pub fn random(cube_size: &CubeSize) -> Self {
         let mut rng = rand::rng();
         let direction: Direction = rng.random();
         let face: Face = rng.random();
-        Self::new(face, direction)
+        // every layer can be reached from one of the two faces either side of it
+        let layers = cube_size.layers(face.normal().as_ivec3());
+        let layer = rng.random_range(0..layers.div_ceil(2));
+        Self::for_size(face, layer..=layer, direction, cube_size)
+    }
+
+    pub fn face(&self) -> &Face {
+        &self.face
+    }
+
+    pub fn layers(&self) -> &RangeInclusive<usize> {
+        &self.layers
+    }
+
+    pub fn direction(&self) -> &Direction {
+        &self.direction
+    }
+
+    pub fn is_half_turn(&self) -> bool {
+        self.half_turn
+    }
+
+    /// The rotation that undoes this one.
+    pub fn inverse(&self) -> Self {
+        Self {
+            direction: self.direction.opposite(),
+            ..self.clone()
+        }
+    }
+}
+
+/// A move that `Rotations` can animate, turning some pieces of a puzzle around an axis.
+pub trait Turn: Clone + Send + Sync + 'static {
+    /// How far the move turns, in radians. Positive angles follow the right-hand rule around the
+    /// axis, so clockwise turns of the face the axis points out of are negative.
+    fn angle(&self) -> f32;
+}
+
+impl Turn for Rotation {
+    fn angle(&self) -> f32 {
+        let turns = if self.half_turn { 2.0 } else { 1.0 };
+        ONE_ROTATION_RADIANS * turns * self.direction.signum()
     }
 }
 
+/// Sent when a rotation has finished animating and the cubies have been snapped to the grid.
+#[derive(Event)]
+pub struct RotationCompleted<T: Turn = Rotation>(pub T);
+
 /// Adds some time between rotations so they're not too fast.
 #[derive(Resource)]
 pub struct RotationTimer(pub Timer);
@@ -70,116 +162,188 @@ impl RotationTimer {
     }
 }
 
+/// The rotation being animated and those waiting to be. Cube rotations are the default, but any
+/// `Turn` can be queued.
 #[derive(Resource)]
-pub struct Rotations {
-    current: Option<Rotation>,
+pub struct Rotations<T: Turn = Rotation> {
+    current: Option<T>,
     current_remaining: f32,
-    queue: VecDeque<Rotation>,
+    queue: VecDeque<T>,
+    /// Why the last rotation to be refused couldn't be made, until another one is.
+    rejection: Option<String>,
 }
 
-impl Rotations {
-    pub fn new(in_progress: Option<Rotation>, queue: VecDeque<Rotation>) -> Self {
-        let current_remaining = if let Some(in_progress) = &in_progress {
-            ONE_ROTATION_RADIANS * in_progress.direction.signum()
-        } else {
-            0.0
-        };
+impl<T: Turn> Rotations<T> {
+    pub fn new(in_progress: Option<T>, queue: VecDeque<T>) -> Self {
+        let current_remaining = in_progress.as_ref().map_or(0.0, Turn::angle);
 
         Self {
             current: in_progress,
             current_remaining,
             queue,
+            rejection: None,
         }
     }
 
-    pub fn current_remaining(&self) -> f32 {
-        self.current_remaining
+    pub fn current(&self) -> Option<&T> {
+        self.current.as_ref()
     }
 
-    pub fn progress_current_rotation(&mut self, progress: f32) {
-        self.current_remaining -= progress;
+    /// How far to turn the rotation in progress after `delta_secs` seconds, which is taken off
+    /// what is left of it. Every turn goes at the same speed, however far it turns.
+    pub fn step(&mut self, delta_secs: f32) -> f32 {
+        let step = (ONE_ROTATION_RADIANS * delta_secs * ROTATION_SPEED)
+            .min(self.current_remaining.abs())
+            * self.current_remaining.signum();
+        self.current_remaining -= step;
+        step
+    }
+
+    /// Whether the rotation in progress, if any, has turned all the way.
+    pub fn is_current_finished(&self) -> bool {
+        self.current_remaining == 0.0
+    }
+
+    /// Take the rotation in progress once it has turned all the way.
+    pub fn take_finished(&mut self) -> Option<T> {
+        if self.is_current_finished() {
+            self.current.take()
+        } else {
+            None
+        }
     }
 
     pub fn is_queue_empty(&self) -> bool {
         self.queue.is_empty()
     }
 
-    pub fn enqueue(&mut self, rotation: Rotation) {
+    pub fn enqueue(&mut self, rotation: T) {
         self.queue.push_back(rotation);
     }
 
-    /// Load the next rotation from the queue into `self.current`.
-    pub fn load_next_rotation(&mut self) {
-        self.current = self.queue.pop_front();
-        if let Some(rotation) = &self.current {
-            self.current_remaining = ONE_ROTATION_RADIANS * rotation.direction.signum();
+    /// The rotation that will be made next, once the one in progress has finished.
+    pub fn next(&self) -> Option<&T> {
+        self.queue.front()
+    }
+
+    /// Drop the next rotation rather than making it, keeping `reason` to show why.
+    pub fn reject_next(&mut self, reason: String) {
+        if self.queue.pop_front().is_some() {
+            self.rejection = Some(reason);
+        }
+    }
+
+    pub fn rejection(&self) -> Option<&str> {
+        self.rejection.as_deref()
+    }
+
+    /// Whether there is no rotation in progress or waiting to be applied.
+    pub fn is_idle(&self) -> bool {
+        self.current.is_none() && self.queue.is_empty()
+    }
+
+    /// Drop the rotation in progress and every queued rotation.
+    pub fn clear(&mut self) {
+        self.current = None;
+        self.current_remaining = 0.0;
+        self.queue.clear();
+    }
+
+    /// Load the next rotation from the queue into `self.current` once the current one has
+    /// finished and `rotation_timer` says it's time.
+    pub fn load_next_rotation(&mut self, rotation_timer: &mut RotationTimer, delta: Duration) {
+        if self.is_current_finished()
+            && rotation_timer.0.tick(delta).just_finished()
+            && !self.is_queue_empty()
+        {
+            self.current = self.queue.pop_front();
+            self.current_remaining = self.current.as_ref().map_or(0.0, Turn::angle);
+            self.rejection = None;
         }
     }
 }
 
+/// Animate the rotation in progress, and start the next one when it's time. Rotations that
+/// would split a bandaged block are refused.
 pub fn apply_rotations(
     time: Res<Time>,
     mut rotation_timer: ResMut<RotationTimer>,
     mut rotations: ResMut<Rotations>,
-    mut cubie_transforms: Query<&mut Transform, With<Cubie>>,
+    cube_size: Res<CubeSize>,
+    bandaging: Res<Bandaging>,
+    mut cubie_transforms: Query<(&Cubie, &mut Transform)>,
+    mut completed: EventWriter<RotationCompleted>,
 ) {
     // progress the rotation currently in progress
-    if let Some(current_rotation) = &rotations.current {
+    if let Some(current_rotation) = rotations.current().cloned() {
         let face_normal = current_rotation.face.normal();
-        let is_center_slice = current_rotation.face.is_center();
-        let step = (ONE_ROTATION_RADIANS * time.delta_secs() * ROTATION_SPEED)
-            .min(rotations.current_remaining.abs())
-            * rotations.current_remaining.signum();
+        let step = rotations.step(time.delta_secs());
 
         // rotate eligible cubies
-        for mut cubie_transform in &mut cubie_transforms {
-            if should_rotate_cubie(&cubie_transform.translation, face_normal, is_center_slice) {
+        for (_, mut cubie_transform) in &mut cubie_transforms {
+            if should_rotate_cubie(
+                &cubie_transform.translation,
+                face_normal,
+                &current_rotation.layers,
+                &cube_size,
+            ) {
                 cubie_transform
                     .rotate_around(face_normal, Quat::from_axis_angle(face_normal, step));
 
                 // if the rotation just completed, snap the cubie to the grid
-                if rotations.current_remaining == step {
-                    snap_cubie_to_grid(&mut cubie_transform);
+                if rotations.is_current_finished() {
+                    snap_cubie_to_grid(&mut cubie_transform, &cube_size);
                 }
             }
         }
-        rotations.progress_current_rotation(step);
+
+        if let Some(rotation) = rotations.take_finished() {
+            completed.write(RotationCompleted(rotation));
+        }
     }
 
-    // check if the current rotation has completed
-    if rotations.current_remaining == 0.0
-        && rotation_timer.0.tick(time.delta()).just_finished()
-        && !rotations.is_queue_empty()
+    // refuse the next rotation before it starts if it would split a bandaged block
+    if rotations.is_current_finished()
+        && let Some(next) = rotations.next()
     {
-        rotations.load_next_rotation();
+        let positions = cubie_positions(cubie_transforms.iter());
+        if bandaging.splits_block(next, &cube_size, |home| positions.get(&home).copied()) {
+            let reason = format!(
+                "{} would split a bandaged block",
+                notation(next, false, &cube_size)
+            );
+            rotations.reject_next(reason);
+        }
     }
+
+    // check if the current rotation has completed
+    rotations.load_next_rotation(&mut rotation_timer, time.delta());
 }
 
-fn should_rotate_cubie(translation: &Vec3, axis: Vec3, is_center_slice: bool) -> bool {
-    if is_center_slice {
-        let dot = translation.dot(axis);
-        dot <= 0.5 && dot >= 0.0
-    } else {
-        translation.dot(axis) >= 1.0
-    }
+fn should_rotate_cubie(
+    translation: &Vec3,
+    axis: Vec3,
+    layers: &RangeInclusive<usize>,
+    cube_size: &CubeSize,
+) -> bool {
+    // how many layers in from the face the cubie is
+    let half_extent = cube_size.half_extent().dot(axis.abs());
+    let layer = (half_extent - translation.dot(axis)).round();
+    layer >= 0.0 && layers.contains(&(layer as usize))
 }
 
 /// Snaps the given cubie `Transform` to the 'grid'.
-/// Being on the grid means having coordinates in [-1,0,1] i.e. no floating point components.
-fn snap_cubie_to_grid(cubie: &mut Transform) {
-    cubie.translation = snapped_translation(&cubie.translation);
+/// Being on the grid means sitting exactly on a layer along every axis i.e. no floating point error.
+fn snap_cubie_to_grid(cubie: &mut Transform, cube_size: &CubeSize) {
+    cubie.translation = snapped_translation(&cubie.translation, cube_size);
     cubie.rotation = snapped_rotation(&cubie.rotation);
 }
 
-/// Snap the provided translation `Vec3` to {-1,0,1}
-fn snapped_translation(translation: &Vec3) -> Vec3 {
-    translation.map(|coordinate| {
-        if coordinate.abs() < 0.5 {
-            0.0
-        } else {
-            coordinate.signum()
-        }
-    })
+/// Snap the provided translation `Vec3` to the nearest layer along each axis.
+/// For a 3x3x3 the layers are at {-1,0,1} and for a 2x2x2 they are at {-0.5,0.5}.
+fn snapped_translation(translation: &Vec3, cube_size: &CubeSize) -> Vec3 {
+    let half_extent = cube_size.half_extent();
+    (*translation + half_extent).round() - half_extent
 }
 
 /// Snap the provided rotation `Quat` to the nearest 90 degrees (PI/2 radians)
@@ -198,9 +362,46 @@ fn snapped_rotation(rotation: &Quat) -> Quat {
 mod test {
     use bevy::math::Vec3;
 
+    use super::*;
+
     #[test]
     fn test_dot_product() {
         let cubie_face = Vec3::new(1.49, 1.0, 1.0);
         assert_eq!(Vec3::X.dot(cubie_face), 1.0);
     }
+
+    #[test]
+    fn test_snapped_translation() {
+        let translation = Vec3::new(0.98, -0.03, -1.02);
+        assert_eq!(
+            snapped_translation(&translation, &CubeSize::cube(3)),
+            Vec3::new(1.0, 0.0, -1.0)
+        );
+
+        let translation = Vec3::new(0.48, -0.52, -1.49);
+        assert_eq!(
+            snapped_translation(&translation, &CubeSize::cube(4)),
+            Vec3::new(0.5, -0.5, -1.5)
+        );
+
+        let translation = Vec3::new(0.48, -1.02, -0.53);
+        assert_eq!(
+            snapped_translation(&translation, &CubeSize::cuboid(2, 3, 2)),
+            Vec3::new(0.5, -1.0, -0.5)
+        );
+    }
+
+    #[test]
+    fn test_should_rotate_inner_layers() {
+        let size = CubeSize::cube(4);
+        let translation = Vec3::new(0.5, 1.5, -0.5);
+        assert!(should_rotate_cubie(&translation, Vec3::X, &(1..=1), &size));
+        assert!(should_rotate_cubie(&translation, Vec3::Y, &(0..=1), &size));
+        assert!(!should_rotate_cubie(
+            &translation,
+            -Vec3::Z,
+            &(0..=0),
+            &size
+        ));
+    }
 }
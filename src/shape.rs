@@ -0,0 +1,143 @@
+use bevy::{
+    asset::RenderAssetUsages,
+    prelude::*,
+    render::mesh::{Indices, PrimitiveTopology},
+};
+
+use crate::{
+    cube::{CubeSize, Face},
+    cubie::translation,
+    state::CubeState,
+};
+
+/// How far a shape mod's outside is moved from the grid its layers turn on, along each axis, in
+/// cubies. The cuts between layers stay where they are, so each outer layer is thicker on one
+/// side of the cube and thinner on the other, and every cubie ends up a different shape.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct CutGrid {
+    offset: Vec3,
+}
+
+impl CutGrid {
+    /// Mirror Blocks, whose cubies are told apart by their shape rather than their color.
+    pub const MIRROR_BLOCKS: Self = Self {
+        offset: Vec3::new(0.2, -0.45, 0.3),
+    };
+
+    /// The box the cubie with `home` fills, relative to its centre, before it's turned.
+    pub fn cubie_bounds(&self, home: IVec3, size: &CubeSize) -> (Vec3, Vec3) {
+        let outer = size.outer();
+        let mut min = Vec3::splat(-0.5);
+        let mut max = Vec3::splat(0.5);
+        for axis in 0..3 {
+            if home[axis] == outer[axis] {
+                max[axis] += self.offset[axis];
+            }
+            if home[axis] == -outer[axis] {
+                min[axis] += self.offset[axis];
+            }
+        }
+        (min, max)
+    }
+
+    /// The box the whole cube fills when it's solved.
+    fn outside(&self, size: &CubeSize) -> (Vec3, Vec3) {
+        let half = size.half_extent() + 0.5;
+        (self.offset - half, self.offset + half)
+    }
+
+    /// Whether the cubie with `home`, turned by `rotation` and moved to `translation`, is inside
+    /// the solved cube's outside. Cubies can't overlap, so the cube is back in shape once every
+    /// cubie fits.
+    pub fn fits(&self, home: IVec3, size: &CubeSize, rotation: Quat, translation: Vec3) -> bool {
+        let (min, max) = self.cubie_bounds(home, size);
+        let (outside_min, outside_max) = self.outside(size);
+        (0..8).all(|corner| {
+            let corner = Vec3::select(
+                BVec3::new(corner & 1 != 0, corner & 2 != 0, corner & 4 != 0),
+                max,
+                min,
+            );
+            let corner = rotation * corner + translation;
+            corner.cmpge(outside_min - 0.01).all() && corner.cmple(outside_max + 0.01).all()
+        })
+    }
+
+    /// Whether every piece of the cube is back in shape. Pieces that look the same, like centres
+    /// turned in place, don't need to be turned back.
+    pub fn is_solved(&self, state: &CubeState) -> bool {
+        let size = state.size();
+        state.pieces().iter().all(|piece| {
+            let rotation = piece.orientation().to_quat();
+            self.fits(piece.home(), &size, rotation, translation(piece.position()))
+        })
+    }
+}
+
+/// The shape mod the cube is cut with, if any. Shape mods have no stickers, so they're solved by
+/// shape rather than color.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Resource)]
+pub struct ShapeMod(pub Option<CutGrid>);
+
+/// The color of every face of a shape mod.
+pub const SHAPE_MOD_COLOR: Color = Color::srgb(0.8, 0.8, 0.85);
+
+/// A flat mesh covering the side of a box facing `face`, a little inside the box's edges so the
+/// gaps between cubies show. The mesh is centred on the middle of that side, which is returned
+/// alongside it.
+pub fn box_face_mesh(face: &Face, min: Vec3, max: Vec3) -> (Mesh, Vec3) {
+    let normal = face.normal();
+    let centre = (min + max) / 2.0 + normal * (max - min) / 2.0;
+    let half_size = (max - min) / 2.0 - 0.01;
+
+    // two directions along the side, anticlockwise around the normal
+    let across = normal.any_orthonormal_vector();
+    let up = normal.cross(across);
+    let (across, up) = (
+        across * half_size.dot(across.abs()),
+        up * half_size.dot(up.abs()),
+    );
+
+    let positions = vec![-across - up, across - up, across + up, up - across];
+    let normals = vec![normal; positions.len()];
+    let mesh = Mesh::new(
+        PrimitiveTopology::TriangleList,
+        RenderAssetUsages::default(),
+    )
+    .with_inserted_attribute(Mesh::ATTRIBUTE_POSITION, positions)
+    .with_inserted_attribute(Mesh::ATTRIBUTE_NORMAL, normals)
+    .with_inserted_indices(Indices::U32(vec![0, 1, 2, 0, 2, 3]));
+
+    (mesh, centre)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::algorithm::Algorithm;
+
+    #[test]
+    fn test_mirror_blocks_are_solved_by_shape() {
+        let grid = CutGrid::MIRROR_BLOCKS;
+        let mut state = CubeState::default();
+        assert!(grid.is_solved(&state));
+
+        // the right layer turned a quarter sticks out of the cube's outline
+        state.apply_algorithm(&"R".parse().unwrap());
+        assert!(!grid.is_solved(&state));
+
+        let sexy_move: Algorithm = "R' U R U'".parse().unwrap();
+        state.apply_algorithm(&"R'".parse().unwrap());
+        for i in 1..=6 {
+            state.apply_algorithm(&sexy_move);
+            assert_eq!(grid.is_solved(&state), i == 6);
+        }
+
+        // a centre turned in place looks the same
+        let algorithm: Algorithm = "R U'".parse().unwrap();
+        for _ in 0..63 {
+            state.apply_algorithm(&algorithm);
+        }
+        assert!(grid.is_solved(&state));
+    }
+}
@@ -0,0 +1,277 @@
+use std::collections::HashMap;
+
+use bevy::prelude::*;
+use rand::Rng;
+
+use crate::{
+    algorithm::NotationError,
+    cube::Face,
+    puzzle::{AxisDefinition, PuzzleDefinition, PuzzleState, Twist, solid},
+};
+
+/// How far each face is from the centre, the same as a 3x3x3.
+const HALF_EXTENT: f32 = 1.5;
+
+/// Every turn of the skewb is a third of a whole turn.
+const ORDER: u32 = 3;
+
+/// The letter for each corner that turns, as in WCA notation: `R` is the down-right-back
+/// corner, `U` is up-left-back, `L` is down-left-front and `B` is down-left-back. None of them
+/// move the up-right-front corner, so it stays where it is.
+const CORNERS: [(char, IVec3); 4] = [
+    ('R', IVec3::new(1, -1, -1)),
+    ('U', IVec3::new(-1, 1, -1)),
+    ('L', IVec3::new(-1, -1, 1)),
+    ('B', IVec3::new(-1, -1, -1)),
+];
+
+/// The number of random turns taken before finding the shortest scramble to the same state,
+/// which is plenty to reach any state.
+const RANDOM_TURNS: usize = 30;
+
+/// A cube cut through its centre across each of the diagonals through the corners that turn, so
+/// each turn takes half the puzzle with it.
+pub fn definition() -> PuzzleDefinition {
+    let normals = Face::variants().map(|face| face.normal());
+    let colors = Face::variants().map(|face| face.start_color().color());
+    let faces = solid(&normals, HALF_EXTENT, &colors);
+
+    let axes = CORNERS
+        .iter()
+        .map(|(_, corner)| AxisDefinition {
+            normal: corner.as_vec3().normalize(),
+            cuts: vec![0.0],
+            order: ORDER,
+        })
+        .collect();
+
+    PuzzleDefinition { faces, axes }
+}
+
+/// Read WCA skewb notation. `R`, `U`, `L` and `B` turn the half of the puzzle around their
+/// corner clockwise, and `'` turns it anticlockwise. Whitespace between moves is optional.
+pub fn parse(s: &str) -> Result<Vec<Twist>, NotationError> {
+    let mut twists = Vec::new();
+    let mut chars = s.chars().peekable();
+
+    while let Some(letter) = chars.next() {
+        if letter.is_whitespace() {
+            continue;
+        }
+
+        let axis = CORNERS
+            .iter()
+            .position(|(corner, _)| *corner == letter)
+            .ok_or(if letter.is_alphabetic() {
+                NotationError::UnknownMove(letter)
+            } else {
+                NotationError::UnexpectedCharacter(letter)
+            })?;
+        let turns = if chars.next_if(|c| matches!(c, '\'' | '’')).is_some() {
+            -1
+        } else {
+            1
+        };
+
+        twists.push(Twist::new(axis, 0..=0, turns, ORDER));
+    }
+
+    Ok(twists)
+}
+
+/// Every move a solution can use: each corner turned clockwise and then anticlockwise, so the
+/// move undoing move `i` is move `i ^ 1`.
+fn moves() -> Vec<Twist> {
+    (0..CORNERS.len())
+        .flat_map(|axis| [1, -1].map(|turns| Twist::new(axis, 0..=0, turns, ORDER)))
+        .collect()
+}
+
+/// The face each sticker started on, in the order of the places in `PuzzleState`. This is all a
+/// skewb state needs, and is small enough to search through quickly.
+type Stickers = Vec<u8>;
+
+/// The state each state found so far was reached from, along with the move taken from it.
+type Visited = HashMap<Stickers, Option<(Stickers, usize)>>;
+
+/// The moves that solve a skewb in as few moves as possible. No skewb is more than 11 moves
+/// from solved, so this searches out from both the scrambled and solved states at once, which
+/// each only need to go about half as far.
+pub fn solve(state: &PuzzleState) -> Vec<Twist> {
+    let moves = moves();
+    let permutations = moves
+        .iter()
+        .map(|twist| state.permutation(twist))
+        .collect::<Vec<_>>();
+    let turn = |stickers: &Stickers, move_index: usize| {
+        let mut turned = stickers.clone();
+        for (place, to) in permutations[move_index].iter().enumerate() {
+            turned[*to] = stickers[place];
+        }
+        turned
+    };
+    let stickers = |faces: &[usize]| faces.iter().map(|face| *face as u8).collect::<Stickers>();
+
+    let start = stickers(state.stickers());
+    let solved = stickers(state.faces());
+    let mut from_start = Visited::from([(start.clone(), None)]);
+    let mut from_solved = Visited::from([(solved.clone(), None)]);
+    let mut start_frontier = vec![start.clone()];
+    let mut solved_frontier = vec![solved];
+
+    let meeting = if from_solved.contains_key(&start) {
+        start
+    } else {
+        loop {
+            // search one move further from whichever side has fewer states to search from
+            let found = if start_frontier.len() <= solved_frontier.len() {
+                search(
+                    &mut start_frontier,
+                    &mut from_start,
+                    &from_solved,
+                    &turn,
+                    moves.len(),
+                )
+            } else {
+                search(
+                    &mut solved_frontier,
+                    &mut from_solved,
+                    &from_start,
+                    &turn,
+                    moves.len(),
+                )
+            };
+            if let Some(meeting) = found {
+                break meeting;
+            }
+        }
+    };
+
+    let mut solution = path(&from_start, meeting.clone());
+    solution.reverse();
+    solution.extend(path(&from_solved, meeting).into_iter().map(|i| i ^ 1));
+    solution.into_iter().map(|i| moves[i].clone()).collect()
+}
+
+/// Take every move from every state in `frontier`, replacing it with the states found, until
+/// reaching a state `other` has already found.
+fn search(
+    frontier: &mut Vec<Stickers>,
+    visited: &mut Visited,
+    other: &Visited,
+    turn: &impl Fn(&Stickers, usize) -> Stickers,
+    move_count: usize,
+) -> Option<Stickers> {
+    let mut next = Vec::new();
+    for state in frontier.iter() {
+        for move_index in 0..move_count {
+            let turned = turn(state, move_index);
+            if visited.contains_key(&turned) {
+                continue;
+            }
+
+            visited.insert(turned.clone(), Some((state.clone(), move_index)));
+            if other.contains_key(&turned) {
+                return Some(turned);
+            }
+            next.push(turned);
+        }
+    }
+
+    *frontier = next;
+    None
+}
+
+/// The moves from `state` back to where the search that found it started, each as the move
+/// taken away from the start.
+fn path(visited: &Visited, mut state: Stickers) -> Vec<usize> {
+    let mut moves = Vec::new();
+    while let Some(Some((previous, move_index))) = visited.get(&state) {
+        moves.push(*move_index);
+        state = previous.clone();
+    }
+    moves
+}
+
+/// A scramble that leaves a solved skewb in a random state, using as few moves as possible.
+pub fn scramble() -> Vec<Twist> {
+    let mut rng = rand::rng();
+    let moves = moves();
+    let mut state = PuzzleState::solved(&definition());
+    for _ in 0..RANDOM_TURNS {
+        state.apply(&moves[rng.random_range(0..moves.len())]);
+    }
+
+    // undoing the solution to the random state takes a solved skewb to it
+    solve(&state).iter().rev().map(Twist::inverse).collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::puzzle::pieces;
+
+    #[test]
+    fn test_pieces() {
+        let pieces = pieces(&definition());
+        let sticker_counts = [1, 3].map(|stickers| {
+            pieces
+                .iter()
+                .filter(|piece| piece.len() == stickers)
+                .count()
+        });
+
+        // centres and corners
+        assert_eq!(sticker_counts, [6, 8]);
+        assert_eq!(pieces.len(), 14);
+    }
+
+    #[test]
+    fn test_notation() {
+        assert_eq!(
+            parse("R U'L B").unwrap(),
+            [
+                Twist::new(0, 0..=0, 1, ORDER),
+                Twist::new(1, 0..=0, -1, ORDER),
+                Twist::new(2, 0..=0, 1, ORDER),
+                Twist::new(3, 0..=0, 1, ORDER),
+            ]
+        );
+        assert_eq!(parse("F"), Err(NotationError::UnknownMove('F')));
+        assert_eq!(parse("r"), Err(NotationError::UnknownMove('r')));
+    }
+
+    #[test]
+    fn test_solutions_are_optimal() {
+        let definition = definition();
+        let solves = |algorithm: &str| {
+            let mut state = PuzzleState::solved(&definition);
+            for twist in parse(algorithm).unwrap() {
+                state.apply(&twist);
+            }
+            let solution = solve(&state);
+            for twist in &solution {
+                state.apply(twist);
+            }
+            assert!(state.is_solved());
+            solution.len()
+        };
+
+        assert_eq!(solves(""), 0);
+        assert_eq!(solves("R"), 1);
+        // turning a corner twice is the same as turning it back once
+        assert_eq!(solves("U U"), 1);
+        assert_eq!(solves("R U' L B'"), 4);
+
+        let mut state = PuzzleState::solved(&definition);
+        for twist in &scramble() {
+            state.apply(twist);
+        }
+        let solution = solve(&state);
+        assert!(solution.len() <= 11);
+        for twist in &solution {
+            state.apply(twist);
+        }
+        assert!(state.is_solved());
+    }
+}
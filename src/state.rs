@@ -0,0 +1,358 @@
+use std::collections::HashMap;
+
+use bevy::prelude::*;
+
+use crate::{
+    algorithm::Algorithm,
+    cube::{CubeSize, Supercube, VoidCube},
+    cubie::{Kind, grid_positions, is_fixed_centre},
+    rotation::Rotation,
+    shape::{CutGrid, ShapeMod},
+};
+
+/// The orientation of a piece, stored as where the x, y and z unit vectors end up.
+/// Pieces only ever turn in quarter and half turns so this is always exact.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct Orientation([IVec3; 3]);
+
+impl Orientation {
+    pub const IDENTITY: Self = Self([IVec3::X, IVec3::Y, IVec3::Z]);
+
+    /// A quarter turn around `axis`, forwards (right-hand rule) if `forward` is set.
+    pub fn quarter_turn(axis: IVec3, forward: bool) -> Self {
+        let turn = |v: IVec3| {
+            let cross = axis.cross(v);
+            axis * axis.dot(v) + if forward { cross } else { -cross }
+        };
+        Self([turn(IVec3::X), turn(IVec3::Y), turn(IVec3::Z)])
+    }
+
+    /// Rotate a vector by this orientation.
+    pub fn apply(&self, v: IVec3) -> IVec3 {
+        self.0[0] * v.x + self.0[1] * v.y + self.0[2] * v.z
+    }
+
+    pub fn to_quat(self) -> Quat {
+        let [x, y, z] = self.0.map(|column| column.as_vec3());
+        Quat::from_mat3(&Mat3::from_cols(x, y, z))
+    }
+
+    /// The orientation from applying `other` first and then `self`.
+    pub fn compose(&self, other: &Self) -> Self {
+        Self(other.0.map(|column| self.apply(column)))
+    }
+
+    /// The orientation that undoes this one. Rotations are orthogonal so this is the transpose.
+    pub fn inverse(&self) -> Self {
+        let [x, y, z] = self.0;
+        Self([
+            IVec3::new(x.x, y.x, z.x),
+            IVec3::new(x.y, y.y, z.y),
+            IVec3::new(x.z, y.z, z.z),
+        ])
+    }
+}
+
+/// A single piece of the cube. Positions are in half cubies, like `Cubie::home`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Piece {
+    home: IVec3,
+    position: IVec3,
+    orientation: Orientation,
+    /// The outward normals of this piece's stickers in the solved cube.
+    stickers: Vec<IVec3>,
+}
+
+impl Piece {
+    /// Where this piece sits in the solved cube. This is used to identify the piece.
+    pub fn home(&self) -> IVec3 {
+        self.home
+    }
+
+    pub fn position(&self) -> IVec3 {
+        self.position
+    }
+
+    pub fn orientation(&self) -> Orientation {
+        self.orientation
+    }
+
+    pub fn kind(&self) -> Kind {
+        match self.stickers.len() {
+            1 => Kind::Centre,
+            2 => Kind::Edge,
+            _ => Kind::Corner,
+        }
+    }
+
+    /// The outward normals of this piece's stickers in the solved cube.
+    pub fn sticker_normals(&self) -> &[IVec3] {
+        &self.stickers
+    }
+
+    /// Whether the piece is back home with each sticker facing the way it started.
+    pub fn is_solved(&self) -> bool {
+        self.position == self.home
+            && self
+                .stickers
+                .iter()
+                .all(|normal| self.orientation.apply(*normal) == *normal)
+    }
+}
+
+/// The outward normals of the stickers on the piece at `position` in a solved cube of the given
+/// size.
+pub fn sticker_normals(position: IVec3, cube_size: &CubeSize) -> Vec<IVec3> {
+    [IVec3::X, IVec3::Y, IVec3::Z]
+        .into_iter()
+        .zip(position.to_array())
+        .zip(cube_size.outer().to_array())
+        .filter(|((_, coordinate), outer)| coordinate.abs() == *outer)
+        .map(|((axis, coordinate), _)| axis * coordinate.signum())
+        .collect()
+}
+
+/// The turn `rotation` gives a piece at `position`, or `None` if the piece isn't in one of the
+/// turned layers. Half turns are two quarter turns.
+pub fn turn(rotation: &Rotation, position: IVec3, size: &CubeSize) -> Option<Orientation> {
+    let axis = rotation.face().normal().as_ivec3();
+    // layers are two half cubies apart, counting in from the face
+    let layer = ((size.layers(axis) as i32 - 1 - position.dot(axis)) / 2) as usize;
+    let quarter = Orientation::quarter_turn(axis, rotation.direction().signum() > 0.0);
+    rotation.layers().contains(&layer).then(|| {
+        if rotation.is_half_turn() {
+            quarter.compose(&quarter)
+        } else {
+            quarter
+        }
+    })
+}
+
+/// A logical model of the cube that tracks where each piece is and how it is turned,
+/// independent of the rendered cubies.
+#[derive(Clone, Debug, PartialEq, Eq, Resource)]
+pub struct CubeState {
+    size: CubeSize,
+    pieces: Vec<Piece>,
+}
+
+impl CubeState {
+    pub fn solved(size: CubeSize) -> Self {
+        let pieces = grid_positions(&size)
+            .filter(|position| Kind::from_position(*position, &size).is_some())
+            .map(|home| Piece {
+                home,
+                position: home,
+                orientation: Orientation::IDENTITY,
+                stickers: sticker_normals(home, &size),
+            })
+            .collect();
+
+        Self { size, pieces }
+    }
+
+    pub fn size(&self) -> CubeSize {
+        self.size
+    }
+
+    pub fn pieces(&self) -> &[Piece] {
+        &self.pieces
+    }
+
+    /// The piece whose home is `home`.
+    pub fn piece(&self, home: IVec3) -> Option<&Piece> {
+        self.pieces.iter().find(|piece| piece.home == home)
+    }
+
+    /// A cube where each piece has been turned by `orientation(home)`, which also decides where
+    /// the piece is.
+    pub fn from_orientations(size: CubeSize, orientation: impl Fn(IVec3) -> Orientation) -> Self {
+        let mut state = Self::solved(size);
+        for piece in &mut state.pieces {
+            piece.orientation = orientation(piece.home);
+            piece.position = piece.orientation.apply(piece.home);
+        }
+        state
+    }
+
+    /// The piece currently at `position`.
+    pub fn piece_at(&self, position: IVec3) -> Option<&Piece> {
+        self.pieces.iter().find(|piece| piece.position == position)
+    }
+
+    /// Apply a single rotation, turning the same pieces `apply_rotations` would.
+    pub fn apply(&mut self, rotation: &Rotation) {
+        for piece in &mut self.pieces {
+            if let Some(turn) = turn(rotation, piece.position, &self.size) {
+                piece.position = turn.apply(piece.position);
+                piece.orientation = turn.compose(&piece.orientation);
+            }
+        }
+    }
+
+    pub fn apply_algorithm(&mut self, algorithm: &Algorithm) {
+        for rotation in algorithm.rotations() {
+            self.apply(rotation);
+        }
+    }
+
+    /// Whether every face shows a single color. Centre orientation is not visible so it is
+    /// ignored, see `is_super_solved`.
+    pub fn is_solved(&self) -> bool {
+        // the home normal of the stickers seen so far on each face of the cube
+        let mut face_colors: HashMap<IVec3, IVec3> = HashMap::new();
+        for piece in &self.pieces {
+            for normal in &piece.stickers {
+                let facing = piece.orientation.apply(*normal);
+                if face_colors.entry(facing).or_insert(*normal) != normal {
+                    return false;
+                }
+            }
+        }
+
+        true
+    }
+
+    /// Whether every face shows a single color, ignoring the centres as a void cube has none.
+    pub fn is_solved_without_centres(&self) -> bool {
+        let mut without_centres = self.clone();
+        without_centres
+            .pieces
+            .retain(|piece| piece.kind() != Kind::Centre);
+        without_centres.is_solved()
+    }
+
+    /// Whether the cube is solved as a supercube, with every fixed centre also turned the same
+    /// way as the rest of the cube.
+    pub fn is_super_solved(&self) -> bool {
+        let whole_cube = self
+            .pieces
+            .iter()
+            .find(|piece| piece.kind() == Kind::Corner)
+            .map(|corner| corner.orientation);
+        self.is_solved()
+            && self
+                .pieces
+                .iter()
+                .filter(|piece| is_fixed_centre(piece.home))
+                .all(|centre| Some(centre.orientation) == whole_cube)
+    }
+}
+
+/// What counts as solved for the kind of cube being played, so the solved indicator, the
+/// solvers and reconstructions all agree.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct SolvedCheck {
+    pub supercube: bool,
+    pub void_cube: bool,
+    pub shape_mod: Option<CutGrid>,
+}
+
+impl SolvedCheck {
+    pub fn new(supercube: &Supercube, void_cube: &VoidCube, shape_mod: &ShapeMod) -> Self {
+        Self {
+            supercube: supercube.0,
+            void_cube: void_cube.0,
+            shape_mod: shape_mod.0,
+        }
+    }
+
+    /// Whether `state` is solved. A shape mod that's back in shape is solved even if it's turned
+    /// round, and void cubes have no centres to turn back even when played as supercubes.
+    pub fn is_solved(&self, state: &CubeState) -> bool {
+        match self.shape_mod {
+            Some(grid) => grid.is_solved(state),
+            None if self.void_cube => state.is_solved_without_centres(),
+            None if self.supercube => state.is_super_solved(),
+            None => state.is_solved(),
+        }
+    }
+}
+
+impl Default for CubeState {
+    fn default() -> Self {
+        Self::solved(CubeSize::default())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::cube::Face;
+
+    #[test]
+    fn test_quarter_turns() {
+        let turn = Orientation::quarter_turn(IVec3::Y, true);
+        assert_eq!(turn.apply(IVec3::X), -IVec3::Z);
+        assert_eq!(turn.apply(IVec3::Z), IVec3::X);
+        assert_eq!(
+            turn.compose(&Orientation::quarter_turn(IVec3::Y, false)),
+            Orientation::IDENTITY
+        );
+    }
+
+    #[test]
+    fn test_sexy_move_returns_after_six() {
+        let algorithm: Algorithm = "R U R' U'".parse().unwrap();
+        let mut state = CubeState::default();
+        for i in 1..=6 {
+            state.apply_algorithm(&algorithm);
+            assert_eq!(state.is_solved(), i == 6);
+        }
+    }
+
+    #[test]
+    fn test_supercube_needs_centres_turned_back() {
+        let mut state = CubeState::default();
+        assert!(state.is_super_solved());
+
+        // the up and right centres are each turned a quarter turn, and nothing else moves
+        let algorithm: Algorithm = "R U'".parse().unwrap();
+        for _ in 0..63 {
+            state.apply_algorithm(&algorithm);
+        }
+        assert!(state.is_solved());
+        assert!(!state.is_super_solved());
+    }
+
+    #[test]
+    fn test_wide_moves_on_big_cubes() {
+        for size in CubeSize::MIN..=CubeSize::MAX {
+            let mut state = CubeState::solved(CubeSize::cube(size));
+            let algorithm: Algorithm = "Rw U2 3Fw' 2L".parse().unwrap();
+            state.apply_algorithm(&algorithm);
+            assert!(!state.is_solved());
+
+            state.apply_algorithm(&algorithm.inverse());
+            assert!(state.is_solved());
+        }
+    }
+
+    #[test]
+    fn test_cuboids_only_half_turn_faces_that_are_not_square() {
+        let size = CubeSize::cuboid(3, 2, 3);
+        assert!(size.allows_quarter_turns(&Face::Top));
+        assert!(!size.allows_quarter_turns(&Face::Right));
+        assert!(!size.allows_quarter_turns(&Face::Front));
+
+        let mut state = CubeState::solved(size);
+        assert_eq!(state.pieces().len(), 18);
+        let algorithm = "U R2 F2 D'"
+            .parse::<Algorithm>()
+            .unwrap()
+            .for_size(&size)
+            .unwrap();
+        state.apply_algorithm(&algorithm);
+        assert!(!state.is_solved());
+        // every piece is still inside the cuboid
+        assert!(
+            state
+                .pieces()
+                .iter()
+                .all(|piece| { piece.position().abs().cmple(size.outer()).all() })
+        );
+
+        state.apply_algorithm(&algorithm.inverse());
+        assert!(state.is_solved());
+    }
+}
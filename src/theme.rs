@@ -0,0 +1,296 @@
+use bevy::{
+    asset::RenderAssetUsages,
+    prelude::*,
+    render::render_resource::{Extent3d, TextureDimension, TextureFormat},
+};
+
+use crate::{
+    accessibility::ColorVision,
+    cube::Face,
+    cubie::{CubieBody, CubieFace},
+    model::CubieAssets,
+};
+
+/// Where a custom color scheme can be saved, as the colors of the top, bottom, left, right,
+/// front and back faces, e.g. `[(255, 255, 255), (255, 213, 0), ...]`.
+const CUSTOM_SCHEME_PATH: &str = "color_scheme.ron";
+
+/// How big stickers are on a black body, compared to the side of a cubie.
+const STICKER_SCALE: f32 = 0.88;
+
+/// Which color goes on which face.
+#[derive(Clone, Debug, PartialEq)]
+pub enum ColorScheme {
+    /// White opposite yellow, red opposite orange and blue opposite green.
+    Western,
+    /// White opposite blue, red opposite orange and green opposite yellow.
+    Japanese,
+    /// Colors chosen to be as far apart as possible, with purple instead of orange.
+    HighContrast,
+    /// Colors for each face in the order of `Face::variants`.
+    Custom([[u8; 3]; 6]),
+}
+
+impl ColorScheme {
+    pub fn color(&self, face: &Face) -> Color {
+        let rgb = match self {
+            Self::Western => return face.start_color().color(),
+            Self::Japanese => match face {
+                Face::Top => [255, 255, 255],
+                Face::Bottom => [0, 0, 255],
+                Face::Left => [255, 213, 0],
+                Face::Right => [0, 255, 0],
+                Face::Front => [255, 0, 0],
+                Face::Back => [255, 88, 0],
+            },
+            Self::HighContrast => match face {
+                Face::Top => [255, 255, 255],
+                Face::Bottom => [255, 230, 0],
+                Face::Left => [0, 200, 0],
+                Face::Right => [0, 80, 255],
+                Face::Front => [230, 0, 0],
+                Face::Back => [160, 0, 200],
+            },
+            Self::Custom(colors) => colors[face.index()],
+        };
+        let [r, g, b] = rgb;
+        Color::srgb_u8(r, g, b)
+    }
+}
+
+/// What the cubies are made of under the stickers.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Body {
+    /// Colored plastic with no stickers, so each side of a cubie is one color up to its rounded
+    /// edges.
+    Stickerless,
+    /// Black plastic with a smaller sticker on each side, leaving a black border.
+    Black,
+}
+
+/// How shiny the stickers are.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Finish {
+    Satin,
+    Matte,
+    Metallic,
+}
+
+/// How the cube looks. Themes only change materials, so switching doesn't touch the cube itself.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Theme {
+    pub name: String,
+    pub scheme: ColorScheme,
+    pub body: Body,
+    pub finish: Finish,
+    /// Whether stickers have a worn edge and grain, rather than being a flat color.
+    pub textured: bool,
+}
+
+impl Theme {
+    fn new(name: &str, scheme: ColorScheme, body: Body, finish: Finish, textured: bool) -> Self {
+        Self {
+            name: name.to_string(),
+            scheme,
+            body,
+            finish,
+            textured,
+        }
+    }
+
+    /// The themes that can be picked, along with a custom one if a color scheme has been saved.
+    pub fn presets() -> Vec<Self> {
+        let mut presets = vec![
+            Self::new(
+                "Western",
+                ColorScheme::Western,
+                Body::Stickerless,
+                Finish::Satin,
+                false,
+            ),
+            Self::new(
+                "Black body",
+                ColorScheme::Western,
+                Body::Black,
+                Finish::Satin,
+                false,
+            ),
+            Self::new(
+                "Japanese",
+                ColorScheme::Japanese,
+                Body::Black,
+                Finish::Satin,
+                false,
+            ),
+            Self::new(
+                "Vinyl stickers",
+                ColorScheme::Western,
+                Body::Black,
+                Finish::Matte,
+                true,
+            ),
+            Self::new(
+                "Metallic",
+                ColorScheme::Western,
+                Body::Stickerless,
+                Finish::Metallic,
+                false,
+            ),
+            Self::new(
+                "High contrast",
+                ColorScheme::HighContrast,
+                Body::Black,
+                Finish::Matte,
+                false,
+            ),
+        ];
+
+        if let Some(colors) = std::fs::read_to_string(CUSTOM_SCHEME_PATH)
+            .ok()
+            .and_then(|text| ron::from_str::<[(u8, u8, u8); 6]>(&text).ok())
+        {
+            let colors = colors.map(|(r, g, b)| [r, g, b]);
+            presets.push(Self::new(
+                "Custom",
+                ColorScheme::Custom(colors),
+                Body::Black,
+                Finish::Satin,
+                false,
+            ));
+        }
+
+        presets
+    }
+
+    /// Set up a sticker's material for the face it started on, as seen with `vision`.
+    fn style(
+        &self,
+        material: &mut StandardMaterial,
+        face: &Face,
+        texture: &Handle<Image>,
+        vision: ColorVision,
+    ) {
+        material.base_color = vision.simulate(self.scheme.color(face));
+        material.base_color_texture = self.textured.then(|| texture.clone());
+        (material.metallic, material.perceptual_roughness) = match self.finish {
+            Finish::Satin => (0.0, 0.5),
+            Finish::Matte => (0.0, 0.95),
+            Finish::Metallic => (0.9, 0.3),
+        };
+    }
+}
+
+/// Every theme that can be picked, and which one is in use.
+#[derive(Resource)]
+pub struct Themes {
+    presets: Vec<Theme>,
+    current: usize,
+}
+
+impl Default for Themes {
+    fn default() -> Self {
+        Self {
+            presets: Theme::presets(),
+            current: 0,
+        }
+    }
+}
+
+impl Themes {
+    pub fn current(&self) -> &Theme {
+        &self.presets[self.current]
+    }
+
+    /// Switch to the next theme along, wrapping back round to the first.
+    pub fn next(&mut self) -> &Theme {
+        self.current = (self.current + 1) % self.presets.len();
+        self.current()
+    }
+}
+
+/// The texture of textured stickers, which is tinted with each sticker's color.
+#[derive(Resource)]
+pub struct StickerTexture(pub Handle<Image>);
+
+/// A white sticker that darkens towards its edges, with a little grain so it doesn't look flat.
+pub fn sticker_texture() -> Image {
+    const SIZE: u32 = 64;
+    let mut data = Vec::with_capacity((SIZE * SIZE * 4) as usize);
+    for y in 0..SIZE {
+        for x in 0..SIZE {
+            let from_centre = (Vec2::new(x as f32, y as f32) + 0.5) / SIZE as f32 * 2.0 - 1.0;
+            let edge = from_centre.abs().max_element();
+            let shade = 1.0 - 0.35 * ((edge - 0.8) / 0.2).clamp(0.0, 1.0);
+            // cheap repeatable noise for the grain
+            let grain = ((x * 7919 + y * 104_729) % 17) as f32 / 17.0 * 0.06;
+            let value = ((shade - grain) * 255.0) as u8;
+            data.extend([value, value, value, 255]);
+        }
+    }
+
+    Image::new(
+        Extent3d {
+            width: SIZE,
+            height: SIZE,
+            depth_or_array_layers: 1,
+        },
+        TextureDimension::D2,
+        data,
+        TextureFormat::Rgba8UnormSrgb,
+        RenderAssetUsages::default(),
+    )
+}
+
+/// Restyle the sticker materials when the theme or simulated color vision changes, and resize
+/// every sticker and show or hide every body, including new ones when the cube is respawned.
+#[allow(clippy::too_many_arguments)]
+pub fn apply_theme(
+    themes: Res<Themes>,
+    vision: Res<ColorVision>,
+    texture: Res<StickerTexture>,
+    cubie_assets: Res<CubieAssets>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+    mut stickers: Query<&mut Transform, (With<CubieFace>, With<Face>)>,
+    mut bodies: Query<&mut Visibility, With<CubieBody>>,
+    added: Query<(), Added<CubieFace>>,
+) {
+    if !themes.is_changed() && !vision.is_changed() && added.is_empty() {
+        return;
+    }
+
+    let theme = themes.current();
+    // a stickerless cubie is colored plastic all over, so the black body under the stickers is
+    // hidden and the stickers cover each side
+    let (scale, body) = match theme.body {
+        Body::Stickerless => (1.0, Visibility::Hidden),
+        Body::Black => (STICKER_SCALE, Visibility::Inherited),
+    };
+    if themes.is_changed() || vision.is_changed() {
+        for face in Face::variants() {
+            if let Some(material) = materials.get_mut(cubie_assets.sticker_material(&face)) {
+                theme.style(material, &face, &texture.0, *vision);
+            }
+        }
+    }
+    for mut transform in &mut stickers {
+        transform.scale = Vec3::splat(scale);
+    }
+    for mut visibility in &mut bodies {
+        visibility.set_if_neq(body);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_schemes_color_every_face_differently() {
+        for theme in Theme::presets() {
+            let colors = Face::variants().map(|face| theme.scheme.color(&face));
+            for (i, color) in colors.iter().enumerate() {
+                assert!(!colors[i + 1..].contains(color), "{}", theme.name);
+            }
+        }
+    }
+}
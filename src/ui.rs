@@ -1,11 +1,35 @@
-use std::f32::consts::{FRAC_PI_2, PI};
+use std::{
+    f32::consts::{FRAC_PI_2, PI},
+    sync::Arc,
+};
 
 use bevy::prelude::*;
 
+use bevy::{ecs::spawn::SpawnIter, input::keyboard::KeyboardInput, ui::RelativeCursorPosition};
+
 use crate::{
-    PlayMode, camera_start_position,
-    cube::{Face, IsCubeSolved},
+    PendingMoves, PlayMode,
+    accessibility::{ColorVision, StickerSymbols},
+    algorithm::{Algorithm, notation},
+    analysis::AlgorithmAnalysis,
+    bandage::{BandageEditor, Bandaging},
+    camera::{CameraPreset, FollowTurns, OrbitCamera},
+    camera_start_position,
+    cube::{CubeSize, Face, IsCubeSolved, Supercube, VoidCube},
+    custom::{CustomPuzzle, CustomPuzzleHandle, PuzzleFile},
+    diagram::{DiagramMask, export_diagrams},
+    history::{Timeline, TimelineJumped},
+    mirror::HiddenFaceMirrors,
+    net::net_panel,
+    pocket::POCKET_SIZE,
+    puzzle::{Puzzle, Twist},
+    puzzle_camera_start_position,
+    reconstruction::Reconstruction,
+    reduction::ReductionSolver,
     rotation::{Direction, Rotation, Rotations},
+    shape::{CutGrid, ShapeMod},
+    state::SolvedCheck,
+    theme::Themes,
 };
 
 #[derive(Debug, Component)]
@@ -13,10 +37,35 @@ pub enum ButtonType {
     ResetCamera,
     Shuffle,
     Solve,
+    ApplyAlgorithm,
+    PreviousBranch,
+    NextBranch,
+    PlayTimeline,
+    ShareSolve,
+    ShrinkCube,
+    GrowCube,
+    PocketCube,
+    Pyraminx,
+    Megaminx,
+    Skewb,
+    Cuboid,
+    CustomPuzzle,
+    Bandage,
+    Supercube,
+    VoidCube,
+    MirrorBlocks,
+    Theme,
+    StickerSymbols,
+    ColorVision,
+    ExportImage,
+    DiagramMask,
+    Mirrors,
+    FollowTurns,
+    CameraPreset(CameraPreset),
 }
 
 /// Setup the UI :D
-pub fn setup_ui(mut commands: Commands, asset_server: &AssetServer) {
+pub fn setup_ui(mut commands: Commands, asset_server: &AssetServer, cube_size: &CubeSize) {
     let ui = (
         Node {
             width: Val::Percent(100.),
@@ -27,131 +76,146 @@ pub fn setup_ui(mut commands: Commands, asset_server: &AssetServer) {
         },
         children![
             filler(),
-            cube_solved_indicator(),
-            cube_controls(&asset_server),
+            top_bar(),
+            algorithm_panel(),
+            timeline_panel(),
+            reconstruction_panel(),
             toolbar(),
         ],
     );
 
     commands.spawn(ui);
+    commands.spawn(cube_controls(asset_server, cube_size));
+    commands.spawn(net_panel(cube_size));
 }
 
+/// Marks the cube controls so they can be replaced when the cube changes size.
+#[derive(Component)]
+pub struct CubeControls;
+
 /// Controls for rotating the different slices of the cube.
-fn cube_controls(asset_server: &AssetServer) -> impl Bundle {
-    let top = cube_control_button_pair(
-        Face::Top,
-        (
-            GridPlacement::start_span(2, 3),
-            GridPlacement::start_span(2, 1),
-        ),
-        FlexDirection::Row,
-        asset_server,
-    );
-    let bottom = cube_control_button_pair(
-        Face::Bottom,
-        (
-            GridPlacement::start_span(2, 3),
-            GridPlacement::start_span(6, 1),
-        ),
-        FlexDirection::Row,
-        asset_server,
-    );
-    let left = cube_control_button_pair(
-        Face::Left,
-        (
-            GridPlacement::start_span(1, 1),
-            GridPlacement::start_span(3, 3),
-        ),
-        FlexDirection::ColumnReverse,
-        asset_server,
-    );
-    let right = cube_control_button_pair(
-        Face::Right,
-        (
-            GridPlacement::start_span(5, 1),
-            GridPlacement::start_span(3, 3),
-        ),
-        FlexDirection::ColumnReverse,
-        asset_server,
-    );
-    let front = cube_control_button_pair(
-        Face::Front,
-        (
-            GridPlacement::start_span(2, 3),
-            GridPlacement::start_span(1, 1),
-        ),
-        FlexDirection::Row,
-        asset_server,
-    );
-    let back = cube_control_button_pair(
-        Face::Back,
-        (
-            GridPlacement::start_span(2, 3),
-            GridPlacement::start_span(7, 1),
-        ),
-        FlexDirection::Row,
-        asset_server,
-    );
-    let horizontal_center = cube_control_button_pair(
-        Face::HorizontalCentre,
-        (
-            GridPlacement::start_span(2, 3),
-            GridPlacement::start_span(4, 1),
-        ),
-        FlexDirection::Row,
-        asset_server,
-    );
-    let vertical_center = cube_control_button_pair(
-        Face::VerticalCentre,
-        (
-            GridPlacement::start_span(3, 1),
-            GridPlacement::start_span(3, 3),
-        ),
-        FlexDirection::ColumnReverse,
-        asset_server,
-    );
+/// Slices along the X axis are columns, slices along the Y axis are rows in the middle, and
+/// slices along the Z axis are rows above (front half) and below (back half) those.
+fn cube_controls(asset_server: &AssetServer, cube_size: &CubeSize) -> impl Bundle {
+    let [width, height, depth] =
+        [IVec3::X, IVec3::Y, IVec3::Z].map(|axis| cube_size.layers(axis) as u16);
+    let columns = 2 * width - 1;
+    let middle_rows = 2 * height - 1;
+    let front_rows = depth.div_ceil(2);
+    let rows = front_rows + middle_rows + (depth - front_rows);
+    let across_columns = GridPlacement::start_span(2, columns - 2);
+
+    let mut pairs = Vec::new();
+    // columns, from left to right
+    for i in 0..width {
+        pairs.push(cube_control_button_pair(
+            layer_rotation(Face::Right, width - 1 - i, cube_size),
+            (
+                GridPlacement::start_span(2 * i as i16 + 1, 1),
+                GridPlacement::start_span(front_rows as i16 + 2, middle_rows - 2),
+            ),
+            FlexDirection::ColumnReverse,
+            asset_server,
+        ));
+    }
+    // rows, from top to bottom
+    for i in 0..height {
+        pairs.push(cube_control_button_pair(
+            layer_rotation(Face::Top, i, cube_size),
+            (
+                across_columns,
+                GridPlacement::start_span((front_rows + 2 * i) as i16 + 1, 1),
+            ),
+            FlexDirection::Row,
+            asset_server,
+        ));
+    }
+    // front to back
+    for i in 0..depth {
+        let row = if i < front_rows {
+            i + 1
+        } else {
+            front_rows + middle_rows + i - front_rows + 1
+        };
+        pairs.push(cube_control_button_pair(
+            layer_rotation(Face::Front, i, cube_size),
+            (across_columns, GridPlacement::start_span(row as i16, 1)),
+            FlexDirection::Row,
+            asset_server,
+        ));
+    }
 
     (
+        CubeControls,
         Node {
             position_type: PositionType::Absolute,
             left: Val::Percent(10.0),
             top: Val::Percent(50.0),
             width: Val::Vw(20.0),
-            aspect_ratio: Some(1.0),
+            aspect_ratio: Some(columns as f32 / rows as f32),
             display: Display::Grid,
-            grid_template_columns: vec![RepeatedGridTrack::fr(5, 1.)],
-            grid_template_rows: vec![RepeatedGridTrack::fr(7, 1.)],
+            grid_template_columns: vec![RepeatedGridTrack::fr(columns, 1.)],
+            grid_template_rows: vec![RepeatedGridTrack::fr(rows, 1.)],
             ..default()
         },
-        children![
-            top,
-            bottom,
-            left,
-            right,
-            front,
-            back,
-            horizontal_center,
-            vertical_center,
-        ],
+        Children::spawn(SpawnIter(pairs.into_iter())),
     )
 }
 
+/// The rotation for the layer `layer` in from `face`, turned from whichever of `face` and its
+/// opposite the layer is nearest, so that the buttons for the middle of the cube behave like
+/// the ones for the face they're closest to. Faces of cuboids that aren't square make half turns.
+fn layer_rotation(face: Face, layer: u16, cube_size: &CubeSize) -> Rotation {
+    let layer = layer as usize;
+    let layers = cube_size.layers(face.normal().as_ivec3());
+    let (face, layer) = if layer < layers.div_ceil(2) {
+        (face, layer)
+    } else {
+        (face.opposite(), layers - 1 - layer)
+    };
+    Rotation::for_size(face, layer..=layer, Direction::Backward, cube_size)
+}
+
+/// Replace the cube controls whenever the cube changes size, and hide them while another
+/// puzzle is picked.
+pub fn respawn_cube_controls(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    cube_size: Res<CubeSize>,
+    puzzle: Res<Puzzle>,
+    controls: Query<Entity, With<CubeControls>>,
+) {
+    if !(cube_size.is_changed() || puzzle.is_changed()) || cube_size.is_added() {
+        return;
+    }
+
+    for entity in &controls {
+        commands.entity(entity).despawn();
+    }
+    if *puzzle == Puzzle::Cube {
+        commands.spawn(cube_controls(&asset_server, &cube_size));
+    }
+}
+
 /// A pair of buttons to rotate a cube slice in the forward and backward directions.
+/// Only the outer layers are labelled, with the face they turn, and slices that only make half
+/// turns are marked with a `2`.
 fn cube_control_button_pair(
-    face: Face,
+    rotation: Rotation,
     position: (GridPlacement, GridPlacement),
     flex_direction: FlexDirection,
     asset_server: &AssetServer,
 ) -> impl Bundle {
-    let label = match &face {
-        Face::HorizontalCentre | Face::VerticalCentre => String::new(),
-        other => other.to_string(),
+    let face = if *rotation.layers() == (0..=0) {
+        rotation.face().to_string()
+    } else {
+        String::new()
+    };
+    let label = if rotation.is_half_turn() {
+        format!("{face} 2").trim_start().to_string()
+    } else {
+        face
     };
-
-    // let directions: [Direction; 2] = match FlexDirection {
-    //     FlexDirection::Row => [Direction::Backward, Direction::Forward],
-    //     FlexDirection::Column => [Direction::Forward, Direction::Backward],
-    // }
 
     (
         Node {
@@ -164,7 +228,7 @@ fn cube_control_button_pair(
         },
         BorderColor(Color::WHITE),
         children![
-            cube_control_button(face.clone(), Direction::Backward, asset_server),
+            cube_control_button(rotation.clone(), asset_server),
             (
                 Node {
                     flex_grow: 1.0,
@@ -174,42 +238,22 @@ fn cube_control_button_pair(
                 },
                 children![(Text(label), TextFont::from_font_size(14.0),)]
             ),
-            cube_control_button(face, Direction::Forward, asset_server),
+            cube_control_button(rotation.inverse(), asset_server),
         ],
     )
 }
 
 #[derive(Component)]
-pub struct CubeControlButton {
-    face: Face,
-    rotate_direction: Direction,
-}
-
-impl CubeControlButton {
-    pub fn new(face: Face, rotate_direction: Direction) -> Self {
-        Self {
-            face,
-            rotate_direction,
-        }
-    }
-
-    pub fn rotation(&self) -> Rotation {
-        Rotation::new(self.face.clone(), self.rotate_direction.clone())
-    }
-}
+pub struct CubeControlButton(Rotation);
 
 /// An individual cube control button that rotates a cube slice in a fixed direction.
-fn cube_control_button(
-    face: Face,
-    rotate_direction: Direction,
-    asset_server: &AssetServer,
-) -> impl Bundle {
+fn cube_control_button(rotation: Rotation, asset_server: &AssetServer) -> impl Bundle {
     let arrow_image = asset_server.load("arrow.png");
-    let arrow_rotation_radians = arrow_rotation(&face, &rotate_direction);
+    let arrow_rotation_radians = arrow_rotation(rotation.face(), rotation.direction());
 
     (
         Button,
-        CubeControlButton::new(face, rotate_direction),
+        CubeControlButton(rotation),
         Node {
             display: Display::Flex,
             justify_content: JustifyContent::Center,
@@ -234,6 +278,7 @@ fn cube_control_button(
 }
 
 /// Handle cube control button interactions.
+#[allow(clippy::type_complexity)]
 pub fn cube_control_button_system(
     mut interaction_query: Query<
         (&Interaction, &CubeControlButton, &mut BackgroundColor),
@@ -250,7 +295,7 @@ pub fn cube_control_button_system(
                 background_color.0 = Color::srgb(0.5, 0.5, 0.5);
             }
             Interaction::Pressed => {
-                rotations.enqueue(cube_control_button.rotation());
+                rotations.enqueue(cube_control_button.0.clone());
             }
         }
     }
@@ -258,13 +303,11 @@ pub fn cube_control_button_system(
 
 fn arrow_rotation(face: &Face, direction: &Direction) -> f32 {
     match face {
-        Face::Top | Face::Bottom | Face::Front | Face::Back | Face::HorizontalCentre => {
-            match direction {
-                Direction::Forward => FRAC_PI_2,
-                Direction::Backward => -FRAC_PI_2,
-            }
-        }
-        Face::Left | Face::Right | Face::VerticalCentre => match direction {
+        Face::Top | Face::Bottom | Face::Front | Face::Back => match direction {
+            Direction::Forward => FRAC_PI_2,
+            Direction::Backward => -FRAC_PI_2,
+        },
+        Face::Left | Face::Right => match direction {
             Direction::Forward => 0.0,
             Direction::Backward => PI,
         },
@@ -276,22 +319,64 @@ fn toolbar() -> impl Bundle {
     (
         Node {
             flex_direction: FlexDirection::Row,
+            // there are too many buttons for one row on most windows
+            flex_wrap: FlexWrap::Wrap,
             column_gap: Val::Px(10.),
+            row_gap: Val::Px(10.),
             padding: UiRect::all(Val::Px(25.)),
             justify_content: JustifyContent::Center,
             align_items: AlignItems::Center,
             ..default()
         },
+        Children::spawn(SpawnIter(
+            [
+                ("Reset Camera", ButtonType::ResetCamera),
+                ("Shuffle", ButtonType::Shuffle),
+                ("Share solve", ButtonType::ShareSolve),
+                ("Cube -", ButtonType::ShrinkCube),
+                ("Cube +", ButtonType::GrowCube),
+                ("Pocket cube", ButtonType::PocketCube),
+                ("Pyraminx", ButtonType::Pyraminx),
+                ("Megaminx", ButtonType::Megaminx),
+                ("Skewb", ButtonType::Skewb),
+                ("Cuboid", ButtonType::Cuboid),
+                ("Custom", ButtonType::CustomPuzzle),
+                ("Bandage", ButtonType::Bandage),
+                ("Supercube", ButtonType::Supercube),
+                ("Void cube", ButtonType::VoidCube),
+                ("Mirror blocks", ButtonType::MirrorBlocks),
+                ("Theme: Western", ButtonType::Theme),
+                ("Symbols", ButtonType::StickerSymbols),
+                ("Vision: normal", ButtonType::ColorVision),
+                ("Mirrors", ButtonType::Mirrors),
+                ("Follow turns", ButtonType::FollowTurns),
+                ("Export image", ButtonType::ExportImage),
+                ("Mask: none", ButtonType::DiagramMask),
+                ("Solve", ButtonType::Solve),
+            ]
+            .into_iter()
+            .map(|(text, button_type)| button(text, button_type)),
+        )),
+    )
+}
+
+/// The camera presets along the top, with the solved indicator and any refused rotation under
+/// them however many rows the presets wrap onto.
+fn top_bar() -> impl Bundle {
+    (
+        Node {
+            position_type: PositionType::Absolute,
+            top: Val::Px(15.),
+            width: Val::Percent(100.),
+            flex_direction: FlexDirection::Column,
+            align_items: AlignItems::Center,
+            row_gap: Val::Px(10.),
+            ..default()
+        },
         children![
-            button("Reset Camera", ButtonType::ResetCamera),
-            button("Shuffle", ButtonType::Shuffle),
-            (
-                Node {
-                    display: Display::None,
-                    ..default()
-                },
-                children![button("Solve", ButtonType::Solve)],
-            ),
+            camera_preset_bar(),
+            cube_solved_indicator(),
+            rotation_rejection_text(),
         ],
     )
 }
@@ -304,9 +389,6 @@ fn cube_solved_indicator() -> impl Bundle {
     (
         CubeSolvedIndicator,
         Node {
-            position_type: PositionType::Absolute,
-            top: Val::Px(25.0),
-            margin: UiRect::horizontal(Val::Auto),
             padding: UiRect::axes(Val::Px(10.), Val::Px(5.)),
             ..default()
         },
@@ -332,6 +414,28 @@ pub fn update_cube_solved_indicator(
     }
 }
 
+/// Text saying why the last rotation was refused, e.g. because it would split a bandaged block.
+#[derive(Component)]
+pub struct RotationRejectionText;
+
+fn rotation_rejection_text() -> impl Bundle {
+    (
+        RotationRejectionText,
+        Text::new(""),
+        TextColor(Color::srgb(1.0, 0.4, 0.4)),
+    )
+}
+
+pub fn update_rotation_rejection_text(
+    rotations: Res<Rotations>,
+    mut text: Single<&mut Text, With<RotationRejectionText>>,
+) {
+    let rejection = rotations.rejection().unwrap_or_default();
+    if text.0 != rejection {
+        text.0 = rejection.to_string();
+    }
+}
+
 /// A button with the given text.
 fn button(text: &'static str, button_type: ButtonType) -> impl Bundle {
     (
@@ -352,14 +456,40 @@ fn button(text: &'static str, button_type: ButtonType) -> impl Bundle {
 }
 
 /// Handle scene button interactions.
+#[allow(clippy::type_complexity, clippy::too_many_arguments)]
 pub fn scene_button_system(
     mut interaction_query: Query<
         (&ButtonType, &Interaction, &mut BackgroundColor, &Children),
         (Changed<Interaction>, With<Button>),
     >,
-    mut camera_query: Single<&mut Transform, With<Camera>>,
+    mut camera: Single<&mut OrbitCamera>,
     mut text_query: Query<(&mut Text, &mut TextColor)>,
     mut play_mode: ResMut<PlayMode>,
+    mut rotations: ResMut<Rotations>,
+    algorithm_input: Res<AlgorithmInput>,
+    mut timeline: ResMut<Timeline>,
+    mut timeline_jumped: EventWriter<TimelineJumped>,
+    mut shared_reconstruction: ResMut<SharedReconstruction>,
+    mut cube_size: ResMut<CubeSize>,
+    mut puzzle: ResMut<Puzzle>,
+    mut twists: ResMut<Rotations<Twist>>,
+    custom_puzzle: Res<CustomPuzzleHandle>,
+    puzzle_files: Res<Assets<PuzzleFile>>,
+    (mut bandaging, mut bandage_editor, mut supercube, mut void_cube, mut shape_mod, mut mirrors): (
+        ResMut<Bandaging>,
+        ResMut<BandageEditor>,
+        ResMut<Supercube>,
+        ResMut<VoidCube>,
+        ResMut<ShapeMod>,
+        ResMut<HiddenFaceMirrors>,
+    ),
+    (mut themes, mut sticker_symbols, mut color_vision, mut diagram_mask, mut follow_turns): (
+        ResMut<Themes>,
+        ResMut<StickerSymbols>,
+        ResMut<ColorVision>,
+        ResMut<DiagramMask>,
+        ResMut<FollowTurns>,
+    ),
 ) {
     for (button_type, interaction, mut background_color, children) in &mut interaction_query {
         let (mut text, mut text_color) = text_query.get_mut(children[0]).unwrap();
@@ -374,19 +504,275 @@ pub fn scene_button_system(
             }
             Interaction::Pressed => match button_type {
                 ButtonType::ResetCamera => {
-                    **camera_query = camera_start_position();
+                    camera.move_to(puzzle_camera_start_position(&puzzle, &cube_size));
                 }
                 ButtonType::Shuffle => {
                     handle_shuffle_press(&mut play_mode, &mut text);
                 }
                 ButtonType::Solve => {
-                    handle_solve_press(&mut play_mode, &mut text);
+                    // stopping still works if the cube was bandaged while solving
+                    let unsolvable = unsolvable_reason(&puzzle, &cube_size, &bandaging).is_some();
+                    if !unsolvable || matches!(*play_mode, PlayMode::Solve) {
+                        handle_solve_press(&mut play_mode, &mut text);
+                    }
+                }
+                ButtonType::ApplyAlgorithm => {
+                    if *puzzle != Puzzle::Cube {
+                        for twist in puzzle.parse(&algorithm_input.text).unwrap_or_default() {
+                            twists.enqueue(twist);
+                        }
+                    } else if let Ok(algorithm) = algorithm_input
+                        .text
+                        .parse::<Algorithm>()
+                        .and_then(|algorithm| algorithm.for_size(&cube_size))
+                    {
+                        for rotation in algorithm.rotations() {
+                            rotations.enqueue(rotation.clone());
+                        }
+                    }
+                }
+                ButtonType::PreviousBranch | ButtonType::NextBranch => {
+                    let branch = match button_type {
+                        ButtonType::PreviousBranch => timeline.branch().saturating_sub(1),
+                        _ => timeline.branch() + 1,
+                    };
+                    timeline.switch_branch(branch);
+                    timeline_jumped.write(TimelineJumped);
+                }
+                ButtonType::ShareSolve => {
+                    let (solution, times) = timeline.attempt();
+                    shared_reconstruction.0 = Some(Reconstruction::new(
+                        timeline.size(),
+                        SolvedCheck::new(&supercube, &void_cube, &shape_mod),
+                        timeline.scramble(),
+                        solution,
+                        times,
+                    ));
+                }
+                ButtonType::ShrinkCube | ButtonType::GrowCube | ButtonType::PocketCube => {
+                    let size = match button_type {
+                        ButtonType::ShrinkCube => cube_size.largest().saturating_sub(1),
+                        ButtonType::GrowCube => cube_size.largest() + 1,
+                        _ => POCKET_SIZE.largest(),
+                    };
+                    let size = CubeSize::cube(size.clamp(CubeSize::MIN, CubeSize::MAX));
+                    let puzzle_changed = puzzle.set_if_neq(Puzzle::Cube);
+                    if cube_size.set_if_neq(size) || puzzle_changed {
+                        camera.set(camera_start_position(&size));
+                    }
+                }
+                ButtonType::Cuboid => {
+                    // each press picks the next cuboid along, starting from the first
+                    let next = CubeSize::CUBOIDS
+                        .iter()
+                        .position(|cuboid| *cuboid == *cube_size)
+                        .map_or(0, |i| (i + 1) % CubeSize::CUBOIDS.len());
+                    let size = CubeSize::CUBOIDS[next];
+                    puzzle.set_if_neq(Puzzle::Cube);
+                    cube_size.set_if_neq(size);
+                    camera.set(camera_start_position(&size));
+                }
+                ButtonType::Pyraminx | ButtonType::Megaminx | ButtonType::Skewb => {
+                    let picked = match button_type {
+                        ButtonType::Pyraminx => Puzzle::Pyraminx,
+                        ButtonType::Megaminx => Puzzle::Megaminx,
+                        _ => Puzzle::Skewb,
+                    };
+                    if puzzle.set_if_neq(picked) {
+                        camera.set(puzzle_camera_start_position(&puzzle, &cube_size));
+                    }
+                }
+                ButtonType::CustomPuzzle => {
+                    // nothing happens until the file has loaded
+                    if let Some(file) = puzzle_files.get(&custom_puzzle.0) {
+                        let picked = Puzzle::Custom(CustomPuzzle(Arc::new(file.clone())));
+                        if puzzle.set_if_neq(picked) {
+                            camera.set(puzzle_camera_start_position(&puzzle, &cube_size));
+                        }
+                    }
+                }
+                ButtonType::Bandage => {
+                    // the first press starts picking cubies, and the second fuses them
+                    if let Some(selected) = bandage_editor.0.take() {
+                        if selected.len() > 1 {
+                            bandaging.fuse(*cube_size, &selected);
+                        }
+                        if let Err(error) = bandaging.save() {
+                            warn!("Couldn't save the bandaging: {error}");
+                        }
+                        *text = Text::new("Bandage");
+                    } else {
+                        bandage_editor.0 = Some(Vec::new());
+                        *text = Text::new("Fuse picked cubies");
+                    }
+                }
+                ButtonType::Supercube => {
+                    supercube.0 = !supercube.0;
+                    *text = Text::new(if supercube.0 {
+                        "Plain cube"
+                    } else {
+                        "Supercube"
+                    });
+                }
+                ButtonType::VoidCube => {
+                    void_cube.0 = !void_cube.0;
+                    *text = Text::new(if void_cube.0 {
+                        "Solid cube"
+                    } else {
+                        "Void cube"
+                    });
+                }
+                ButtonType::MirrorBlocks => {
+                    if shape_mod.0.take().is_some() {
+                        *text = Text::new("Mirror blocks");
+                    } else {
+                        shape_mod.0 = Some(CutGrid::MIRROR_BLOCKS);
+                        *text = Text::new("Stickered cube");
+                    }
+                }
+                ButtonType::Theme => {
+                    *text = Text::new(format!("Theme: {}", themes.next().name));
+                }
+                ButtonType::StickerSymbols => {
+                    sticker_symbols.0 = !sticker_symbols.0;
+                    *text = Text::new(if sticker_symbols.0 {
+                        "Hide symbols"
+                    } else {
+                        "Symbols"
+                    });
+                }
+                ButtonType::ColorVision => {
+                    *color_vision = color_vision.next();
+                    *text = Text::new(format!("Vision: {}", color_vision.name()));
+                }
+                ButtonType::Mirrors => {
+                    mirrors.0 = !mirrors.0;
+                    *text = Text::new(if mirrors.0 { "Hide mirrors" } else { "Mirrors" });
+                }
+                ButtonType::FollowTurns => {
+                    follow_turns.0 = !follow_turns.0;
+                    *text = Text::new(if follow_turns.0 {
+                        "Stop following"
+                    } else {
+                        "Follow turns"
+                    });
+                }
+                ButtonType::CameraPreset(preset) => {
+                    let distance = puzzle_camera_start_position(&puzzle, &cube_size)
+                        .translation
+                        .length();
+                    camera.move_to(preset.view(distance));
+                }
+                ButtonType::ExportImage => {
+                    let scheme = &themes.current().scheme;
+                    match export_diagrams(&timeline.state(), scheme, *diagram_mask)
+                        .and_then(|files| save_diagrams(&files))
+                    {
+                        Ok(()) => info!("Exported diagrams of the cube"),
+                        Err(error) => warn!("Couldn't export diagrams of the cube: {error}"),
+                    }
+                }
+                ButtonType::DiagramMask => {
+                    *diagram_mask = diagram_mask.next();
+                    *text = Text::new(format!("Mask: {}", diagram_mask.name()));
+                }
+                ButtonType::PlayTimeline => {
+                    // animate the rest of the branch, which the timeline recognises as a replay
+                    if rotations.is_idle() {
+                        for rotation in &timeline.moves()[timeline.position()..] {
+                            rotations.enqueue(rotation.clone());
+                        }
+                    }
                 }
             },
         }
     }
 }
 
+/// A row of small buttons along the top, one for each camera preset.
+fn camera_preset_bar() -> impl Bundle {
+    let buttons = CameraPreset::all().map(|preset| {
+        (
+            Button,
+            ButtonType::CameraPreset(preset),
+            Node {
+                border: UiRect::all(Val::Px(2.)),
+                padding: UiRect::axes(Val::Px(8.), Val::Px(4.)),
+                ..default()
+            },
+            BorderColor(Color::WHITE),
+            BorderRadius::all(Val::Px(10.)),
+            children![(
+                Text::new(preset.name()),
+                TextFont::from_font_size(14.0),
+                TextColor(Color::WHITE),
+            )],
+        )
+    });
+
+    (
+        Node {
+            width: Val::Percent(100.),
+            flex_direction: FlexDirection::Row,
+            flex_wrap: FlexWrap::Wrap,
+            column_gap: Val::Px(6.),
+            row_gap: Val::Px(6.),
+            justify_content: JustifyContent::Center,
+            ..default()
+        },
+        Children::spawn(SpawnIter(buttons.into_iter())),
+    )
+}
+
+/// Move the camera to a preset view when a number key is pressed: 1 to 3 for the front, back
+/// and top, and 4 to 7 for the top corners, or the bottom corners while shift is held. Keys
+/// typed into the algorithm panel are left alone.
+pub fn camera_preset_keyboard_system(
+    keys: Res<ButtonInput<KeyCode>>,
+    algorithm_input: Res<AlgorithmInput>,
+    puzzle: Res<Puzzle>,
+    cube_size: Res<CubeSize>,
+    mut camera: Single<&mut OrbitCamera>,
+) {
+    if algorithm_input.focused {
+        return;
+    }
+
+    let digits = [
+        KeyCode::Digit1,
+        KeyCode::Digit2,
+        KeyCode::Digit3,
+        KeyCode::Digit4,
+        KeyCode::Digit5,
+        KeyCode::Digit6,
+        KeyCode::Digit7,
+    ];
+    let Some(i) = digits.iter().position(|key| keys.just_pressed(*key)) else {
+        return;
+    };
+    let shifted = keys.any_pressed([KeyCode::ShiftLeft, KeyCode::ShiftRight]);
+    let i = if shifted && i >= 3 { i + 4 } else { i };
+
+    let distance = puzzle_camera_start_position(&puzzle, &cube_size)
+        .translation
+        .length();
+    camera.move_to(CameraPreset::all()[i].view(distance));
+}
+
+/// Save exported diagrams in the working directory, with `cube_` in front of their names.
+#[cfg(not(target_arch = "wasm32"))]
+fn save_diagrams(files: &[(String, Vec<u8>)]) -> Result<(), String> {
+    for (name, bytes) in files {
+        std::fs::write(format!("cube_{name}"), bytes).map_err(|error| error.to_string())?;
+    }
+    Ok(())
+}
+
+#[cfg(target_arch = "wasm32")]
+fn save_diagrams(_files: &[(String, Vec<u8>)]) -> Result<(), String> {
+    Err("there are no files to save to on the web".to_string())
+}
+
 /// Handles the 'shuffle' button being pressed
 fn handle_shuffle_press(play_mode: &mut PlayMode, button_text: &mut Text) {
     match play_mode {
@@ -419,6 +805,457 @@ fn handle_solve_press(play_mode: &mut PlayMode, button_text: &mut Text) {
     }
 }
 
+/// Show on the shuffle or solve button when its moves are still being worked out, which takes a
+/// while the first time a solver is used, and why the cube can't be solved if it can't.
+pub fn update_play_mode_buttons(
+    play_mode: Res<PlayMode>,
+    pending: Res<PendingMoves>,
+    (puzzle, cube_size, bandaging): (Res<Puzzle>, Res<CubeSize>, Res<Bandaging>),
+    buttons: Query<(&ButtonType, &Children)>,
+    mut text_query: Query<&mut Text>,
+) {
+    let bandaging_changed = puzzle.is_changed() || cube_size.is_changed() || bandaging.is_changed();
+    if !pending.is_changed() && !bandaging_changed {
+        return;
+    }
+
+    let unsolvable = unsolvable_reason(&puzzle, &cube_size, &bandaging);
+    for (button_type, children) in &buttons {
+        let label = match (button_type, &*play_mode, pending.is_pending()) {
+            (ButtonType::Shuffle, PlayMode::Shuffle, true) => "Shuffling...",
+            (ButtonType::Shuffle, PlayMode::Shuffle, false) => "Stop shuffling",
+            (ButtonType::Solve, PlayMode::Solve, true) => "Solving...",
+            (ButtonType::Solve, PlayMode::Solve, false) => "Stop solving",
+            (ButtonType::Solve, _, _) => unsolvable.unwrap_or("Solve"),
+            _ => continue,
+        };
+        if let Ok(mut text) = text_query.get_mut(children[0])
+            && text.0 != label
+        {
+            *text = Text::new(label);
+        }
+    }
+}
+
+/// Why the solvers can't solve the cube, if they can't. The solvers would split bandaged blocks,
+/// and cuboids and cubes bigger than `MAX_SOLVED_SIZE` have no solver.
+fn unsolvable_reason(
+    puzzle: &Puzzle,
+    cube_size: &CubeSize,
+    bandaging: &Bandaging,
+) -> Option<&'static str> {
+    if *puzzle != Puzzle::Cube {
+        None
+    } else if bandaging.is_bandaged(cube_size) {
+        Some("Can't solve bandaged")
+    } else if *cube_size != POCKET_SIZE && !ReductionSolver::can_solve(cube_size) {
+        Some("Can't solve this size")
+    } else {
+        None
+    }
+}
+
+/// The algorithm typed into the algorithm panel.
+#[derive(Debug, Default, Resource)]
+pub struct AlgorithmInput {
+    pub text: String,
+    pub focused: bool,
+}
+
+/// The text field the algorithm is typed into.
+#[derive(Component)]
+pub struct AlgorithmInputField;
+
+/// Text showing the analysis of the typed algorithm.
+#[derive(Component)]
+pub struct AlgorithmAnalysisText;
+
+/// Panel for typing in an algorithm to study what it does, and to apply it to the cube.
+fn algorithm_panel() -> impl Bundle {
+    (
+        Node {
+            position_type: PositionType::Absolute,
+            right: Val::Percent(2.0),
+            top: Val::Px(80.0),
+            width: Val::Vw(25.0),
+            flex_direction: FlexDirection::Column,
+            row_gap: Val::Px(10.),
+            padding: UiRect::all(Val::Px(15.)),
+            ..default()
+        },
+        BackgroundColor(Color::srgba(0.0, 0.0, 0.0, 0.5)),
+        BorderRadius::all(Val::Px(10.)),
+        children![
+            (Text::new("Algorithm"), TextColor(Color::WHITE)),
+            (
+                Button,
+                AlgorithmInputField,
+                Node {
+                    min_height: Val::Px(30.),
+                    border: UiRect::all(Val::Px(2.)),
+                    padding: UiRect::axes(Val::Px(8.), Val::Px(4.)),
+                    align_items: AlignItems::Center,
+                    ..default()
+                },
+                BorderColor(Color::srgb(0.5, 0.5, 0.5)),
+                BorderRadius::all(Val::Px(5.)),
+                children![(
+                    Text::new("Click to type e.g. R U R' U' or [R: [U, D]]"),
+                    TextFont::from_font_size(14.0),
+                    TextColor(Color::WHITE),
+                )],
+            ),
+            (
+                AlgorithmAnalysisText,
+                Text::new(""),
+                TextFont::from_font_size(14.0),
+                TextColor(Color::WHITE),
+            ),
+            button("Apply", ButtonType::ApplyAlgorithm),
+        ],
+    )
+}
+
+/// Focus the algorithm input when it is clicked.
+pub fn algorithm_input_focus_system(
+    interaction_query: Query<&Interaction, (Changed<Interaction>, With<AlgorithmInputField>)>,
+    mut field_query: Query<&mut BorderColor, With<AlgorithmInputField>>,
+    mut algorithm_input: ResMut<AlgorithmInput>,
+) {
+    for interaction in &interaction_query {
+        if *interaction == Interaction::Pressed {
+            algorithm_input.focused = true;
+        }
+    }
+
+    if algorithm_input.is_changed() {
+        for mut border_color in &mut field_query {
+            border_color.0 = if algorithm_input.focused {
+                Color::WHITE
+            } else {
+                Color::srgb(0.5, 0.5, 0.5)
+            };
+        }
+    }
+}
+
+/// Type into the algorithm input while it is focused. Enter or escape stop typing.
+pub fn algorithm_input_keyboard_system(
+    mut keyboard_events: EventReader<KeyboardInput>,
+    mut algorithm_input: ResMut<AlgorithmInput>,
+) {
+    for event in keyboard_events.read() {
+        if !algorithm_input.focused || !event.state.is_pressed() {
+            continue;
+        }
+
+        match event.key_code {
+            KeyCode::Backspace => {
+                algorithm_input.text.pop();
+            }
+            KeyCode::Enter | KeyCode::Escape => {
+                algorithm_input.focused = false;
+            }
+            _ => {
+                if let Some(text) = &event.text {
+                    algorithm_input
+                        .text
+                        .extend(text.chars().filter(|c| !c.is_control()));
+                }
+            }
+        }
+    }
+}
+
+/// Show the typed algorithm and what it does to the cube.
+pub fn update_algorithm_panel(
+    algorithm_input: Res<AlgorithmInput>,
+    puzzle: Res<Puzzle>,
+    cube_size: Res<CubeSize>,
+    field_query: Query<&Children, With<AlgorithmInputField>>,
+    mut analysis_query: Query<&mut Text, With<AlgorithmAnalysisText>>,
+    mut text_query: Query<&mut Text, Without<AlgorithmAnalysisText>>,
+) {
+    if !algorithm_input.is_changed() && !puzzle.is_changed() && !cube_size.is_changed() {
+        return;
+    }
+
+    let cursor = if algorithm_input.focused { "|" } else { "" };
+    for children in &field_query {
+        if let Ok(mut text) = text_query.get_mut(children[0]) {
+            *text = Text::new(format!("{}{cursor}", algorithm_input.text));
+        }
+    }
+
+    // only 3x3x3 algorithms are analysed, other puzzles and sizes just check the notation
+    let analysis = if *puzzle != Puzzle::Cube {
+        match puzzle.parse(&algorithm_input.text) {
+            Ok(twists) => format!("Moves: {}", twists.len()),
+            Err(error) => format!("Can't read algorithm: {error}"),
+        }
+    } else if *cube_size != CubeSize::cube(3) {
+        match algorithm_input
+            .text
+            .parse::<Algorithm>()
+            .and_then(|algorithm| algorithm.for_size(&cube_size))
+        {
+            Ok(algorithm) => format!("Moves: {}", algorithm.rotations().len()),
+            Err(error) => format!("Can't read algorithm: {error}"),
+        }
+    } else {
+        cube_analysis(&algorithm_input.text)
+    };
+    for mut text in &mut analysis_query {
+        *text = Text::new(analysis.clone());
+    }
+}
+
+/// What a 3x3x3 algorithm does, or why it can't be read.
+fn cube_analysis(text: &str) -> String {
+    let parsed = text.parse::<Algorithm>().and_then(|algorithm| {
+        algorithm.for_size(&CubeSize::cube(3))?;
+        Ok(algorithm)
+    });
+    match parsed {
+        Ok(algorithm) => {
+            let mut lines = AlgorithmAnalysis::new(&algorithm).lines();
+            if let Some(commutator) = algorithm.as_commutator() {
+                lines.push(format!("Commutator: {commutator}"));
+            }
+            lines.join("\n")
+        }
+        Err(error) => format!("Can't read algorithm: {error}"),
+    }
+}
+
+/// The slider used to scrub through the timeline.
+#[derive(Component)]
+pub struct TimelineSlider;
+
+/// The part of the timeline slider showing how far along the timeline the cube is.
+#[derive(Component)]
+pub struct TimelineSliderFill;
+
+/// Text listing the moves in the timeline.
+#[derive(Component)]
+pub struct TimelineMovesText;
+
+/// Text showing the position and branch in the timeline.
+#[derive(Component)]
+pub struct TimelinePositionText;
+
+/// The number of moves either side of the current position that are listed in the timeline.
+const TIMELINE_MOVES_SHOWN: usize = 10;
+
+/// Panel listing every move applied to the cube, with a slider to jump back and forth through them.
+fn timeline_panel() -> impl Bundle {
+    (
+        Node {
+            position_type: PositionType::Absolute,
+            left: Val::Percent(2.0),
+            top: Val::Px(80.0),
+            width: Val::Vw(25.0),
+            flex_direction: FlexDirection::Column,
+            row_gap: Val::Px(10.),
+            padding: UiRect::all(Val::Px(15.)),
+            ..default()
+        },
+        BackgroundColor(Color::srgba(0.0, 0.0, 0.0, 0.5)),
+        BorderRadius::all(Val::Px(10.)),
+        children![
+            (Text::new("Timeline"), TextColor(Color::WHITE)),
+            (
+                TimelineMovesText,
+                Text::new(""),
+                TextFont::from_font_size(14.0),
+                TextColor(Color::WHITE),
+            ),
+            (
+                Button,
+                TimelineSlider,
+                RelativeCursorPosition::default(),
+                Node {
+                    height: Val::Px(12.),
+                    ..default()
+                },
+                BackgroundColor(Color::srgb(0.3, 0.3, 0.3)),
+                BorderRadius::all(Val::Px(6.)),
+                children![(
+                    TimelineSliderFill,
+                    Node {
+                        width: Val::Percent(0.),
+                        height: Val::Percent(100.),
+                        ..default()
+                    },
+                    BackgroundColor(Color::WHITE),
+                    BorderRadius::all(Val::Px(6.)),
+                )],
+            ),
+            (
+                TimelinePositionText,
+                Text::new(""),
+                TextFont::from_font_size(14.0),
+                TextColor(Color::WHITE),
+            ),
+            (
+                Node {
+                    flex_direction: FlexDirection::Row,
+                    column_gap: Val::Px(10.),
+                    justify_content: JustifyContent::Center,
+                    ..default()
+                },
+                children![
+                    button("<", ButtonType::PreviousBranch),
+                    button("Play", ButtonType::PlayTimeline),
+                    button(">", ButtonType::NextBranch),
+                ],
+            ),
+        ],
+    )
+}
+
+/// Jump to the point in the timeline under the cursor while the slider is pressed.
+pub fn timeline_slider_system(
+    slider_query: Query<(&Interaction, &RelativeCursorPosition), With<TimelineSlider>>,
+    mut timeline: ResMut<Timeline>,
+    mut timeline_jumped: EventWriter<TimelineJumped>,
+) {
+    for (interaction, cursor_position) in &slider_query {
+        if *interaction != Interaction::Pressed {
+            continue;
+        }
+
+        if let Some(cursor_position) = cursor_position.normalized {
+            let fraction = cursor_position.x.clamp(0.0, 1.0);
+            let position = (fraction * timeline.moves().len() as f32).round() as usize;
+            if position != timeline.position() {
+                timeline.jump(position);
+                timeline_jumped.write(TimelineJumped);
+            }
+        }
+    }
+}
+
+/// Show the moves in the timeline and where along them the cube is.
+pub fn update_timeline_panel(
+    timeline: Res<Timeline>,
+    mut moves_query: Query<&mut Text, (With<TimelineMovesText>, Without<TimelinePositionText>)>,
+    mut position_query: Query<&mut Text, (With<TimelinePositionText>, Without<TimelineMovesText>)>,
+    mut fill_query: Query<&mut Node, With<TimelineSliderFill>>,
+) {
+    if !timeline.is_changed() {
+        return;
+    }
+
+    let moves = timeline.moves();
+    let position = timeline.position();
+    let start = position.saturating_sub(TIMELINE_MOVES_SHOWN);
+    let end = (position + TIMELINE_MOVES_SHOWN).min(moves.len());
+
+    let mut tokens = Vec::new();
+    if start > 0 {
+        tokens.push("...".to_string());
+    }
+    let size = timeline.size();
+    tokens.extend(
+        moves[start..position]
+            .iter()
+            .map(|r| notation(r, false, &size)),
+    );
+    tokens.push("|".to_string());
+    tokens.extend(
+        moves[position..end]
+            .iter()
+            .map(|r| notation(r, false, &size)),
+    );
+    if end < moves.len() {
+        tokens.push("...".to_string());
+    }
+
+    for mut text in &mut moves_query {
+        *text = Text::new(tokens.join(" "));
+    }
+    for mut text in &mut position_query {
+        *text = Text::new(format!(
+            "Move {position} / {}, branch {} / {}",
+            moves.len(),
+            timeline.branch() + 1,
+            timeline.branch_count()
+        ));
+    }
+    for mut node in &mut fill_query {
+        node.width = if moves.is_empty() {
+            Val::Percent(0.)
+        } else {
+            Val::Percent(100. * position as f32 / moves.len() as f32)
+        };
+    }
+}
+
+/// The most recently shared reconstruction of the current attempt.
+#[derive(Debug, Default, Resource)]
+pub struct SharedReconstruction(pub Option<Reconstruction>);
+
+/// The panel showing the shared reconstruction, hidden until something is shared.
+#[derive(Component)]
+pub struct ReconstructionPanel;
+
+/// Text showing the shared reconstruction.
+#[derive(Component)]
+pub struct ReconstructionText;
+
+/// Panel showing a reconstruction of the current attempt, along with a link to it.
+fn reconstruction_panel() -> impl Bundle {
+    (
+        ReconstructionPanel,
+        Node {
+            display: Display::None,
+            position_type: PositionType::Absolute,
+            right: Val::Percent(2.0),
+            bottom: Val::Px(120.0),
+            width: Val::Vw(25.0),
+            flex_direction: FlexDirection::Column,
+            row_gap: Val::Px(10.),
+            padding: UiRect::all(Val::Px(15.)),
+            ..default()
+        },
+        BackgroundColor(Color::srgba(0.0, 0.0, 0.0, 0.5)),
+        BorderRadius::all(Val::Px(10.)),
+        children![
+            (Text::new("Reconstruction"), TextColor(Color::WHITE)),
+            (
+                ReconstructionText,
+                Text::new(""),
+                TextFont::from_font_size(12.0),
+                TextColor(Color::WHITE),
+            ),
+        ],
+    )
+}
+
+/// Show the shared reconstruction.
+pub fn update_reconstruction_panel(
+    shared_reconstruction: Res<SharedReconstruction>,
+    mut panel_query: Query<&mut Node, With<ReconstructionPanel>>,
+    mut text_query: Query<&mut Text, With<ReconstructionText>>,
+) {
+    if !shared_reconstruction.is_changed() {
+        return;
+    }
+    let Some(reconstruction) = &shared_reconstruction.0 else {
+        return;
+    };
+
+    let text = format!("{}\n\n{}", reconstruction.text(), reconstruction.url());
+
+    for mut node in &mut panel_query {
+        node.display = Display::Flex;
+    }
+    for mut reconstruction_text in &mut text_query {
+        *reconstruction_text = Text::new(text.clone());
+    }
+}
+
 /// A filler item that just grows into any flex box empty space.
 fn filler() -> Node {
     Node {